@@ -0,0 +1,149 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use tracing::{debug, info, warn, Span};
+
+use super::node_state::{NodeState, NodeStatus};
+
+static STATUS_COMMAND: &str = "STATUS";
+
+/// Atiende pedidos de administracion en `addr`, uno por linea. `STATUS`
+/// se responde con el estado del nodo en una linea JSON y cualquier otro
+/// comando con `ERROR <motivo>`.
+///
+/// # Errors
+///
+/// Devuelve error si no se pudo escuchar en `addr`
+pub fn serve(addr: SocketAddr, state: NodeState) -> io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr)?;
+    info!(addr = %addr, "Atendiendo pedidos de administracion");
+    let span = Span::current();
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let state = state.clone();
+            let span = span.clone();
+            thread::spawn(move || {
+                let _entered = span.enter();
+                if let Err(err) = attend(stream, &state) {
+                    warn!(error = %err, "Conexion de administracion fallida");
+                }
+            });
+        }
+    }))
+}
+
+fn attend(stream: TcpStream, state: &NodeState) -> io::Result<()> {
+    let peer = stream.peer_addr()?;
+    let reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+    for line in reader.lines() {
+        let line = line?;
+        let command = line.trim();
+        if command.is_empty() {
+            continue;
+        }
+        let reply = if command.eq_ignore_ascii_case(STATUS_COMMAND) {
+            serde_json::to_string(&state.status()).unwrap_or_else(|err| format!("ERROR {}", err))
+        } else {
+            "ERROR comando desconocido".to_string()
+        };
+        debug!(client = %peer, command, "Pedido de administracion respondido");
+        writeln!(stream, "{}", reply)?;
+        stream.flush()?;
+    }
+    Ok(())
+}
+
+/// Pide el estado del nodo que atiende en `addr`, esperando la
+/// respuesta hasta `timeout`
+///
+/// # Errors
+///
+/// Devuelve el motivo si no se pudo conectar, no hubo respuesta o
+/// la respuesta no es un estado
+pub fn query_status(addr: &SocketAddr, timeout: Duration) -> Result<NodeStatus, String> {
+    let stream = TcpStream::connect_timeout(addr, timeout)
+        .map_err(|err| format!("No se pudo conectar: {}", err))?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .map_err(|err| err.to_string())?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|err| err.to_string())?);
+    let mut stream = stream;
+    writeln!(stream, "{}", STATUS_COMMAND).map_err(|err| err.to_string())?;
+    let mut reply = String::new();
+    reader
+        .read_line(&mut reply)
+        .map_err(|err| format!("Sin respuesta: {}", err))?;
+    serde_json::from_str(reply.trim_end())
+        .map_err(|_| format!("Respuesta invalida: {}", reply.trim_end()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admin::node_state::{LeaderInfo, Role};
+    use crate::alglobo::{retry_queue::RetryQueue, transaction::Transaction};
+    use crate::services::service_name::ServiceName;
+    use crate::submit::submit_queue::SubmitQueue;
+    use ntest::timeout;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Condvar, Mutex, RwLock};
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_reply_the_status_of_the_node() {
+        let ended = Arc::new((Mutex::new(true), Condvar::new()));
+        let leader = LeaderInfo {
+            node_id: 1,
+            epoch: 3,
+            data_addr: "127.0.0.1:49152".parse().unwrap(),
+            info_addr: "127.0.0.1:49353".parse().unwrap(),
+        };
+        let transaction = Transaction::new(
+            7,
+            &HashMap::from([
+                (ServiceName::Airline.string_name(), 100.0),
+                (ServiceName::Hotel.string_name(), 200.0),
+                (ServiceName::Bank.string_name(), 300.0),
+            ]),
+        );
+        let retries = RetryQueue::new(ended.clone());
+        retries
+            .push(4, HashMap::new(), "127.0.0.1:49160".parse().unwrap())
+            .unwrap();
+        let state = NodeState {
+            node_id: 2,
+            known_leader: Arc::new(Mutex::new((3, Some(leader)))),
+            leading: Arc::new(RwLock::new(false)),
+            curr_transaction: Arc::new((Mutex::new(Some(Box::new(transaction))), Condvar::new())),
+            retries,
+            submissions: SubmitQueue::new(ended),
+            last_decided: Arc::new(Mutex::new(Some(6))),
+        };
+        let addr: SocketAddr = "127.0.0.1:59204".parse().unwrap();
+        serve(addr, state).unwrap();
+
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut stream = stream;
+        writeln!(stream, "HOLA").unwrap();
+        let mut reply = String::new();
+        reader.read_line(&mut reply).unwrap();
+        assert_eq!(reply.trim_end(), "ERROR comando desconocido");
+
+        let status = query_status(&addr, Duration::from_secs(1)).unwrap();
+        assert_eq!(status.node_id, 2);
+        assert_eq!(status.role, Role::Follower);
+        assert_eq!((status.epoch, status.leader), (3, Some(leader)));
+        let current = status.current_transaction.as_ref().unwrap();
+        assert_eq!(current.id, 7);
+        assert!(current.representation.starts_with("Id:7, "));
+        assert_eq!((status.retry_queue, status.submit_queue), (1, 0));
+        assert_eq!(status.last_decided, Some(6));
+        assert!(status.report().contains("Rol: seguidor"));
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::alglobo::{
+    retry_queue::RetryQueue,
+    types::{CurrentTransaction, LastDecided, Leading},
+};
+use crate::submit::submit_queue::SubmitQueue;
+
+/// Rol del nodo en el cluster
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Leader,
+    Follower,
+    /// No es lider ni conoce al lider, por ejemplo durante una eleccion
+    Candidate,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Role::Leader => write!(f, "lider"),
+            Role::Follower => write!(f, "seguidor"),
+            Role::Candidate => write!(f, "candidato"),
+        }
+    }
+}
+
+/// Lider conocido por el nodo y la epoca en que asumio
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeaderInfo {
+    pub node_id: u64,
+    pub epoch: u64,
+    pub data_addr: SocketAddr,
+    pub info_addr: SocketAddr,
+}
+
+/// Epoca del nodo y lider que conoce, que actualiza el candidato
+pub type KnownLeader = Arc<Mutex<(u64, Option<LeaderInfo>)>>;
+
+/// Transaccion en curso, con el estado de cada servicio
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CurrentStatus {
+    pub id: u64,
+    pub representation: String,
+}
+
+/// Respuesta a un pedido `STATUS`, por ejemplo
+/// `{"node_id":1,"role":"leader","epoch":2,"leader":{...},
+/// "current_transaction":{"id":7,"representation":"Id:7, ..."},
+/// "retry_queue":0,"submit_queue":0,"last_decided":6}`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NodeStatus {
+    pub node_id: u64,
+    pub role: Role,
+    pub epoch: u64,
+    pub leader: Option<LeaderInfo>,
+    pub current_transaction: Option<CurrentStatus>,
+    /// Reintentos esperando a ser ejecutados
+    pub retry_queue: usize,
+    /// Pedidos SUBMIT esperando a ser procesados
+    pub submit_queue: usize,
+    pub last_decided: Option<u64>,
+}
+
+impl NodeStatus {
+    /// Estado en lineas legibles, para `alglobo admin status`
+    #[must_use]
+    pub fn report(&self) -> String {
+        let leader = self.leader.map_or_else(
+            || "desconocido".to_string(),
+            |leader| format!("{} ({})", leader.node_id, leader.data_addr),
+        );
+        let current = self
+            .current_transaction
+            .as_ref()
+            .map_or("ninguna", |current| &current.representation);
+        let last_decided = self
+            .last_decided
+            .map_or_else(|| "ninguna".to_string(), |id| id.to_string());
+        format!(
+            "Nodo: {}\nRol: {}\nLider: {}\nEpoca: {}\nTransaccion actual: {}\nReintentos encolados: {}\nReservas encoladas: {}\nUltima decision: {}",
+            self.node_id,
+            self.role,
+            leader,
+            self.epoch,
+            current,
+            self.retry_queue,
+            self.submit_queue,
+            last_decided
+        )
+    }
+}
+
+/// Partes compartidas del nodo de las que se arma su estado
+#[derive(Clone)]
+pub struct NodeState {
+    pub node_id: u64,
+    pub known_leader: KnownLeader,
+    pub leading: Leading,
+    pub curr_transaction: CurrentTransaction,
+    pub retries: RetryQueue,
+    pub submissions: SubmitQueue,
+    pub last_decided: LastDecided,
+}
+
+impl NodeState {
+    #[must_use]
+    pub fn status(&self) -> NodeStatus {
+        let (epoch, leader) = *self
+            .known_leader
+            .lock()
+            .expect("[Node State] Lock de lider envenenado");
+        let leading = *self
+            .leading
+            .read()
+            .expect("[Node State] Lock de liderazgo envenenado");
+        let role = match (leading, leader) {
+            (true, _) => Role::Leader,
+            (false, Some(_)) => Role::Follower,
+            (false, None) => Role::Candidate,
+        };
+        let current_transaction = self
+            .curr_transaction
+            .0
+            .lock()
+            .expect("[Node State] Lock de transaccion envenenado")
+            .as_ref()
+            .map(|transaction| CurrentStatus {
+                id: transaction.get_id(),
+                representation: transaction.representation(true),
+            });
+        let last_decided = *self
+            .last_decided
+            .lock()
+            .expect("[Node State] Lock de ultima decision envenenado");
+        NodeStatus {
+            node_id: self.node_id,
+            role,
+            epoch,
+            leader,
+            current_transaction,
+            retry_queue: self.retries.len(),
+            submit_queue: self.submissions.len(),
+            last_decided,
+        }
+    }
+}
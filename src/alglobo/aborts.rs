@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::transaction_state::TransactionState;
+
+/// Motivo por el que se aborto una transaccion
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AbortReason {
+    /// Algun servicio voto abortar
+    Vote,
+    /// Algun servicio no respondio a tiempo
+    Timeout,
+    /// Algun servicio envio una respuesta que no se pudo decodificar
+    DecodeError,
+}
+
+impl fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AbortReason::Vote => write!(f, "vote"),
+            AbortReason::Timeout => write!(f, "timeout"),
+            AbortReason::DecodeError => write!(f, "decode_error"),
+        }
+    }
+}
+
+/// Estado de un servicio al terminar la fase de preparacion
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ServiceVote {
+    pub state: TransactionState,
+    pub fee: f64,
+}
+
+/// Una linea del registro de transacciones abortadas, por ejemplo
+/// `{"id":3,"timestamp_ms":1700000000000,"reason":"vote","services":
+/// {"Airline":{"state":"aborted","fee":100.0},...},"attempts":1}`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AbortRecord {
+    pub id: u64,
+    /// Milisegundos desde la epoca Unix
+    pub timestamp_ms: u64,
+    pub reason: AbortReason,
+    pub services: BTreeMap<String, ServiceVote>,
+    /// Cantidad de veces que se aborto la transaccion, contando esta
+    pub attempts: u32,
+}
+
+/// Registro de transacciones abortadas en formato JSON Lines
+pub struct AbortStore {
+    file: File,
+    attempts: HashMap<u64, u32>,
+}
+
+impl AbortStore {
+    /// Abre el registro para agregarle abortos. Los intentos se siguen
+    /// numerando a partir de los registros existentes.
+    /// # Errors:
+    /// Arroja error si no es posible leer o abrir el archivo
+    pub fn open(path: &str) -> Result<AbortStore, String> {
+        let mut attempts = HashMap::new();
+        if Path::new(path).exists() {
+            for record in read(path)? {
+                let count = attempts.entry(record.id).or_insert(0);
+                *count = record.attempts.max(*count);
+            }
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| format!("No se pudo abrir el registro de abortos: {}", err))?;
+        Ok(AbortStore { file, attempts })
+    }
+
+    /// Agrega el aborto de la transaccion `id` y devuelve el registro escrito
+    /// # Errors:
+    /// Arroja error si no es posible escribir en el archivo
+    pub fn record(
+        &mut self,
+        id: u64,
+        reason: AbortReason,
+        services: BTreeMap<String, ServiceVote>,
+    ) -> io::Result<AbortRecord> {
+        let attempts = self.attempts.entry(id).or_insert(0);
+        *attempts += 1;
+        let record = AbortRecord {
+            id,
+            timestamp_ms: unix_millis(),
+            reason,
+            services,
+            attempts: *attempts,
+        };
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        Ok(record)
+    }
+}
+
+/// Lee los registros de `path` en el orden en que se escribieron. Las
+/// lineas que no son un registro, como las del formato CSV anterior,
+/// se ignoran.
+/// # Errors:
+/// Arroja error si no es posible leer el archivo
+pub fn read(path: &str) -> Result<Vec<AbortRecord>, String> {
+    let file = File::open(path)
+        .map_err(|err| format!("No se pudo leer el registro de abortos: {}", err))?;
+    let mut records = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line =
+            line.map_err(|err| format!("No se pudo leer el registro de abortos: {}", err))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(err) => warn!(line = index + 1, error = %err, "Registro de aborto ignorado"),
+        }
+    }
+    Ok(records)
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    fn votes(state: TransactionState) -> BTreeMap<String, ServiceVote> {
+        BTreeMap::from([("Airline".to_string(), ServiceVote { state, fee: 100.0 })])
+    }
+
+    #[test]
+    fn it_should_number_the_attempts_of_each_transaction() {
+        let path = env::temp_dir().join(format!("alglobo_{}_aborts.jsonl", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        fs::write(&path, "3,100,200,300\n").unwrap();
+
+        let mut store = AbortStore::open(&path).unwrap();
+        store
+            .record(3, AbortReason::Vote, votes(TransactionState::Aborted))
+            .unwrap();
+        store
+            .record(4, AbortReason::Timeout, votes(TransactionState::Waiting))
+            .unwrap();
+        let mut reopened = AbortStore::open(&path).unwrap();
+        let last = reopened
+            .record(
+                3,
+                AbortReason::DecodeError,
+                votes(TransactionState::Aborted),
+            )
+            .unwrap();
+
+        let records = read(&path).unwrap();
+        let summary: Vec<(u64, AbortReason, u32)> = records
+            .iter()
+            .map(|record| (record.id, record.reason, record.attempts))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (3, AbortReason::Vote, 1),
+                (4, AbortReason::Timeout, 1),
+                (3, AbortReason::DecodeError, 2)
+            ]
+        );
+        assert_eq!(records[2], last);
+        assert_eq!(
+            records[1].services["Airline"].state,
+            TransactionState::Waiting
+        );
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("\"reason\":\"decode_error\""));
+        fs::remove_file(path).unwrap();
+    }
+}
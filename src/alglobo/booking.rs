@@ -0,0 +1,11 @@
+use serde_json::{Map, Value};
+
+/// Datos de la reserva que acompanan a una transaccion cuando el
+/// origen los provee. No viajan a los servicios: se usan en los logs
+/// y en las trazas.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Booking {
+    pub currency: Option<String>,
+    pub customer: Option<String>,
+    pub metadata: Map<String, Value>,
+}
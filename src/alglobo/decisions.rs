@@ -0,0 +1,213 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::alglobo::retry_queue::RETRY_ID_BASE;
+use crate::submit::submit_queue::SUBMIT_ID_BASE;
+
+/// Cantidad maxima de decisiones que se conservan de las filas leidas
+/// con un mismo offset, y aparte de los reintentos y reservas SUBMIT
+pub const DECISIONS_CAPACITY: usize = 1024;
+
+/// Decisiones (id => commiteada) en el orden en que se tomaron. Al
+/// superar `DECISIONS_CAPACITY` se descarta la mas antigua.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Outcomes {
+    order: VecDeque<u64>,
+    outcomes: HashMap<u64, bool>,
+}
+
+impl Outcomes {
+    fn insert(&mut self, id: u64, commited: bool) {
+        if self.outcomes.insert(id, commited).is_none() {
+            self.order.push_back(id);
+        }
+        if self.order.len() > DECISIONS_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.outcomes.remove(&oldest);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+        self.outcomes.clear();
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (u64, bool)> + '_ {
+        self.order.iter().map(move |id| (*id, self.outcomes[id]))
+    }
+}
+
+/// Decisiones tomadas por el lider. Las de las filas del archivo de
+/// transacciones se conservan desde el offset de lectura actual: las
+/// filas anteriores no se vuelven a leer, por lo que sus decisiones se
+/// descartan al avanzar la lectura. Las de los reintentos y reservas
+/// SUBMIT, que tienen sus propios ids, no dependen del offset y se
+/// conservan aparte. Asi el historial del lider, el que se replica en
+/// los demas nodos y el que se traspasa no crecen indefinidamente.
+/// Tambien lleva el proximo id de SUBMIT, para que un nuevo lider no
+/// reuse el de una reserva ya ejecutada.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Decisions {
+    offset: u64,
+    rows: Outcomes,
+    others: Outcomes,
+    /// Posicion de la proxima reserva SUBMIT, a partir de `SUBMIT_ID_BASE`
+    next_submit: u64,
+}
+
+impl Decisions {
+    #[must_use]
+    pub fn new() -> Self {
+        Decisions::default()
+    }
+
+    /// Registra la decision tomada con la lectura en `offset`. Si el
+    /// offset avanzo, descarta las decisiones anteriores de las filas
+    /// del archivo.
+    pub fn insert(&mut self, id: u64, commited: bool, offset: u64) {
+        if offset > self.offset {
+            self.offset = offset;
+            self.rows.clear();
+        }
+        // Los ids de los reintentos y de SUBMIT estan por encima de
+        // los del archivo
+        if id >= SUBMIT_ID_BASE {
+            if id < RETRY_ID_BASE {
+                self.skip_submits(id - SUBMIT_ID_BASE + 1);
+            }
+            self.others.insert(id, commited);
+        } else {
+            self.rows.insert(id, commited);
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, id: u64) -> Option<bool> {
+        self.rows
+            .outcomes
+            .get(&id)
+            .or_else(|| self.others.outcomes.get(&id))
+            .copied()
+    }
+
+    #[must_use]
+    pub fn contains(&self, id: u64) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Posicion de la proxima reserva SUBMIT
+    #[must_use]
+    pub fn next_submit(&self) -> u64 {
+        self.next_submit
+    }
+
+    /// Id con el que se ejecuta la proxima reserva SUBMIT
+    #[must_use]
+    pub fn next_submit_id(&self) -> u64 {
+        SUBMIT_ID_BASE + self.next_submit
+    }
+
+    /// Avanza la posicion de la proxima reserva SUBMIT hasta `next`, si
+    /// todavia no la supero
+    pub fn skip_submits(&mut self, next: u64) {
+        self.next_submit = self.next_submit.max(next);
+    }
+
+    /// Offset de lectura con el que se tomaron las decisiones
+    #[must_use]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.rows.order.len() + self.others.order.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Decisiones de las filas del archivo y luego las de los
+    /// reintentos y reservas SUBMIT, en el orden en que se tomaron
+    pub fn iter(&self) -> impl Iterator<Item = (u64, bool)> + '_ {
+        self.rows.iter().chain(self.others.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_drop_the_decisions_taken_before_the_offset_advanced() {
+        let mut decisions = Decisions::new();
+        decisions.insert(3, true, 10);
+        decisions.insert(9, false, 10);
+        assert_eq!(decisions.get(3), Some(true));
+        assert_eq!(decisions.get(9), Some(false));
+
+        decisions.insert(4, true, 20);
+        // Una decision con un offset anterior no descarta las demas
+        decisions.insert(1, false, 15);
+        assert_eq!(decisions.offset(), 20);
+        assert!(!decisions.contains(3));
+        assert_eq!(
+            decisions.iter().collect::<Vec<_>>(),
+            vec![(4, true), (1, false)]
+        );
+    }
+
+    #[test]
+    fn it_should_keep_the_retry_and_submit_decisions_when_the_offset_advances() {
+        let mut decisions = Decisions::new();
+        decisions.insert(RETRY_ID_BASE, true, 10);
+        decisions.insert(SUBMIT_ID_BASE + 7, false, 10);
+        decisions.insert(3, true, 10);
+
+        decisions.insert(4, true, 20);
+        assert!(!decisions.contains(3));
+        assert_eq!(decisions.get(RETRY_ID_BASE), Some(true));
+        assert_eq!(decisions.get(SUBMIT_ID_BASE + 7), Some(false));
+        assert_eq!(
+            decisions.iter().collect::<Vec<_>>(),
+            vec![
+                (4, true),
+                (RETRY_ID_BASE, true),
+                (SUBMIT_ID_BASE + 7, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_not_reuse_a_submit_id_after_its_decision_is_dropped() {
+        let mut decisions = Decisions::new();
+        assert_eq!(decisions.next_submit_id(), SUBMIT_ID_BASE);
+        decisions.insert(SUBMIT_ID_BASE + 4, true, 0);
+        decisions.insert(RETRY_ID_BASE + 9, true, 0);
+        assert_eq!(decisions.next_submit_id(), SUBMIT_ID_BASE + 5);
+
+        for id in 0..DECISIONS_CAPACITY as u64 {
+            decisions.insert(RETRY_ID_BASE + id, true, 0);
+        }
+        assert!(!decisions.contains(SUBMIT_ID_BASE + 4));
+        assert_eq!(decisions.next_submit(), 5);
+        decisions.skip_submits(3);
+        assert_eq!(decisions.next_submit(), 5);
+    }
+
+    #[test]
+    fn it_should_keep_at_most_the_capacity_of_each_kind() {
+        let mut decisions = Decisions::new();
+        for id in 0..=DECISIONS_CAPACITY as u64 {
+            decisions.insert(id, true, 0);
+            decisions.insert(RETRY_ID_BASE + id, true, 0);
+        }
+        assert_eq!(decisions.len(), 2 * DECISIONS_CAPACITY);
+        assert!(!decisions.contains(0));
+        assert!(!decisions.contains(RETRY_ID_BASE));
+        assert!(decisions.contains(DECISIONS_CAPACITY as u64));
+        assert!(decisions.contains(RETRY_ID_BASE + DECISIONS_CAPACITY as u64));
+    }
+}
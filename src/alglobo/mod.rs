@@ -1,3 +1,7 @@
+pub mod aborts;
+pub mod booking;
+pub mod decisions;
+pub mod retry_queue;
 pub mod transaction;
 pub mod transaction_error;
 pub mod transaction_manager;
@@ -0,0 +1,240 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use super::transaction::Transaction;
+use crate::transaction_messages::transaction_retry_ack::DenyReason;
+
+/// Cantidad maxima de reintentos esperando a ser ejecutados
+pub const RETRY_QUEUE_CAPACITY: usize = 1024;
+
+/// Cantidad maxima de ids commiteados que se recuerdan para denegar
+/// sus reintentos
+pub const COMMITED_CAPACITY: usize = 4096;
+
+/// Los reintentos se ejecutan con ids a partir de este valor, para no
+/// confundirlos con los ids del archivo de transacciones
+pub const RETRY_ID_BASE: u64 = 1 << 63;
+
+/// Reintento encolado
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedRetry {
+    /// Posicion en la cola, unica en todo el cluster
+    pub seq: u64,
+    /// Id de la transaccion reintentada
+    pub id: u64,
+    pub fees: HashMap<String, f64>,
+    /// Cliente al que se le avisa el resultado; solo lo conoce el
+    /// lider que concedio el reintento
+    pub client_opt: Option<SocketAddr>,
+}
+
+impl QueuedRetry {
+    /// Id con el que se ejecuta el reintento. Si un nuevo lider lo
+    /// vuelve a ejecutar, los servicios reconocen el id y repiten
+    /// su respuesta.
+    #[must_use]
+    pub fn retry_id(&self) -> u64 {
+        RETRY_ID_BASE + self.seq
+    }
+
+    #[must_use]
+    pub fn transaction(&self) -> Transaction {
+        Transaction::new(self.retry_id(), &self.fees)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    pending: VecDeque<QueuedRetry>,
+    next_seq: u64,
+    /// Los reintentos se terminan en orden: todos los anteriores a
+    /// esta posicion ya se ejecutaron
+    done_until: u64,
+    /// Ids commiteados, del mas antiguo al mas reciente. No dependen
+    /// del offset de lectura del archivo, para que un reintento de una
+    /// transaccion ya commiteada no se vuelva a ejecutar.
+    commited_order: VecDeque<u64>,
+    commited: HashSet<u64>,
+}
+
+/// Cola de reintentos que ejecuta el `TransactionManager` del lider,
+/// en orden y entre las transacciones del archivo. El lider encola los
+/// reintentos que recibe y los demas nodos, los que replica.
+#[derive(Clone)]
+pub struct RetryQueue {
+    state: Arc<Mutex<State>>,
+    ended: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl RetryQueue {
+    #[must_use]
+    pub fn new(ended: Arc<(Mutex<bool>, Condvar)>) -> Self {
+        RetryQueue {
+            state: Arc::new(Mutex::new(State::default())),
+            ended,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("[Retry Queue] Lock de la cola envenenado")
+    }
+
+    /// Cantidad de reintentos esperando a ser ejecutados
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.state().pending.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Proximo reintento a ejecutar. Sigue encolado hasta que se
+    /// marque como terminado, para que no se pierda si el lider cae.
+    #[must_use]
+    pub fn front(&self) -> Option<QueuedRetry> {
+        self.state().pending.front().cloned()
+    }
+
+    /// Encola el reintento de la transaccion `id` y lo devuelve con su
+    /// posicion
+    /// # Errors:
+    /// Devuelve el motivo si la transaccion ya fue commiteada o la cola
+    /// esta llena
+    pub fn push(
+        &self,
+        id: u64,
+        fees: HashMap<String, f64>,
+        client: SocketAddr,
+    ) -> Result<QueuedRetry, DenyReason> {
+        let retry = {
+            let mut state = self.state();
+            if state.commited.contains(&id) {
+                return Err(DenyReason::AlreadyCommited);
+            }
+            if state.pending.len() >= RETRY_QUEUE_CAPACITY {
+                return Err(DenyReason::QueueFull);
+            }
+            let retry = QueuedRetry {
+                seq: state.next_seq,
+                id,
+                fees,
+                client_opt: Some(client),
+            };
+            state.next_seq += 1;
+            state.pending.push_back(retry.clone());
+            retry
+        };
+        let _ended = self
+            .ended
+            .0
+            .lock()
+            .expect("[Retry Queue] Lock de finalizacion envenenado");
+        self.ended.1.notify_all();
+        Ok(retry)
+    }
+
+    /// Recuerda que la transaccion `id` se commiteo. Al superar
+    /// `COMMITED_CAPACITY` se olvida la mas antigua.
+    pub fn commited(&self, id: u64) {
+        let mut state = self.state();
+        if !state.commited.insert(id) {
+            return;
+        }
+        state.commited_order.push_back(id);
+        if state.commited_order.len() > COMMITED_CAPACITY {
+            if let Some(oldest) = state.commited_order.pop_front() {
+                state.commited.remove(&oldest);
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn is_commited(&self, id: u64) -> bool {
+        self.state().commited.contains(&id)
+    }
+
+    /// Agrega un reintento replicado por el lider, en orden de posicion.
+    /// Los repetidos y los ya terminados se ignoran.
+    pub fn replicate(&self, retry: QueuedRetry) {
+        let mut state = self.state();
+        state.next_seq = state.next_seq.max(retry.seq + 1);
+        if retry.seq < state.done_until || state.pending.iter().any(|prev| prev.seq == retry.seq) {
+            return;
+        }
+        let index = state
+            .pending
+            .iter()
+            .position(|prev| prev.seq > retry.seq)
+            .unwrap_or(state.pending.len());
+        state.pending.insert(index, retry);
+    }
+
+    /// Quita de la cola el reintento terminado y los anteriores
+    pub fn done(&self, seq: u64) {
+        let mut state = self.state();
+        state.next_seq = state.next_seq.max(seq + 1);
+        state.done_until = state.done_until.max(seq + 1);
+        let done_until = state.done_until;
+        state.pending.retain(|retry| retry.seq >= done_until);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replicated(seq: u64, id: u64) -> QueuedRetry {
+        QueuedRetry {
+            seq,
+            id,
+            fees: HashMap::new(),
+            client_opt: None,
+        }
+    }
+
+    #[test]
+    fn it_should_number_the_retries_after_the_replicated_ones() {
+        let queue = RetryQueue::new(Arc::new((Mutex::new(true), Condvar::new())));
+        queue.replicate(replicated(1, 20));
+        queue.replicate(replicated(0, 10));
+        queue.replicate(replicated(1, 20));
+        queue.done(0);
+        // Un reintento terminado que llega tarde no se vuelve a encolar
+        queue.replicate(replicated(0, 10));
+
+        let client: SocketAddr = "127.0.0.1:49160".parse().unwrap();
+        let pushed = queue.push(30, HashMap::new(), client).unwrap();
+
+        assert_eq!(pushed.seq, 2);
+        assert_eq!(pushed.retry_id(), RETRY_ID_BASE + 2);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.front(), Some(replicated(1, 20)));
+        queue.done(1);
+        assert_eq!(queue.front(), Some(pushed));
+        queue.done(2);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn it_should_deny_the_retries_of_commited_transactions() {
+        let queue = RetryQueue::new(Arc::new((Mutex::new(true), Condvar::new())));
+        let client: SocketAddr = "127.0.0.1:49160".parse().unwrap();
+        for id in 0..=COMMITED_CAPACITY as u64 {
+            queue.commited(id);
+        }
+
+        assert_eq!(
+            queue.push(1, HashMap::new(), client),
+            Err(DenyReason::AlreadyCommited)
+        );
+        assert!(queue.is_commited(COMMITED_CAPACITY as u64));
+        // El id commiteado mas antiguo se olvida
+        assert!(!queue.is_commited(0));
+        assert!(queue.push(0, HashMap::new(), client).is_ok());
+    }
+}
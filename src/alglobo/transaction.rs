@@ -8,11 +8,15 @@ use crate::{
     },
 };
 
-use super::{transaction_state::TransactionState, transactionable::Transactionable};
+use super::{
+    booking::Booking, transaction_state::TransactionState, transactionable::Transactionable,
+};
 
 pub struct Transaction {
     id: u64,
     services: HashMap<String, (TransactionState, f64)>,
+    booking_opt: Option<Booking>,
+    rejected: Vec<String>,
 }
 
 impl Transaction {
@@ -23,7 +27,24 @@ impl Transaction {
             .map(|(name, fee)| (name.clone(), (TransactionState::Waiting, *fee)))
             .collect();
 
-        Transaction { id, services }
+        Transaction {
+            id,
+            services,
+            booking_opt: None,
+            rejected: Vec::new(),
+        }
+    }
+
+    /// Asocia a la transaccion los datos de la reserva leidos del origen
+    #[must_use]
+    pub fn with_booking(mut self, booking: Booking) -> Self {
+        self.booking_opt = Some(booking);
+        self
+    }
+
+    #[must_use]
+    pub fn booking(&self) -> Option<&Booking> {
+        self.booking_opt.as_ref()
     }
 
     fn update_state(
@@ -93,6 +114,18 @@ impl Transactionable for Transaction {
         self.update_state(&name, TransactionState::Commited, &pre_states, opt_fee)
     }
 
+    fn reject(&mut self, name: String) -> bool {
+        let is_valid = self.abort(name.clone(), None);
+        if is_valid && !self.rejected.contains(&name) {
+            self.rejected.push(name);
+        }
+        is_valid
+    }
+
+    fn rejected_services(&self) -> Vec<String> {
+        self.rejected.clone()
+    }
+
     fn waiting_services(&self) -> HashMap<String, f64> {
         let mut result = HashMap::new();
         for (name, (state, fee)) in self.services.clone() {
@@ -104,7 +137,7 @@ impl Transactionable for Transaction {
     }
 
     fn not_aborted_services(&self) -> HashMap<String, f64> {
-        let pre_states = vec![TransactionState::Waiting, TransactionState::Accepted];
+        let pre_states = [TransactionState::Waiting, TransactionState::Accepted];
         let mut result = HashMap::new();
         for (name, (state, fee)) in self.services.clone() {
             if pre_states.contains(&state) {
@@ -115,7 +148,7 @@ impl Transactionable for Transaction {
     }
 
     fn accepted_services(&self) -> HashMap<String, f64> {
-        let pre_states = vec![TransactionState::Accepted];
+        let pre_states = [TransactionState::Accepted];
         let mut result = HashMap::new();
         for (name, (state, fee)) in self.services.clone() {
             if pre_states.contains(&state) {
@@ -133,6 +166,13 @@ impl Transactionable for Transaction {
         result
     }
 
+    fn service_states(&self) -> HashMap<String, TransactionState> {
+        self.services
+            .iter()
+            .map(|(name, (state, _))| (name.clone(), *state))
+            .collect()
+    }
+
     fn is_any_waiting(&self) -> bool {
         for (_, (state, _)) in self.services.clone() {
             if state == TransactionState::Waiting {
@@ -440,4 +480,25 @@ mod tests {
         assert!(!transaction.is_aborted());
         assert!(!transaction.is_commited());
     }
+
+    #[test]
+    fn reject_should_abort_the_service_and_remember_it() {
+        let airline = (ServiceName::Airline.string_name(), 100.0);
+        let hotel = (ServiceName::Hotel.string_name(), 200.0);
+        let mut transaction = Transaction::new(0, &HashMap::from([airline, hotel]));
+
+        transaction.accept(ServiceName::Hotel.string_name(), None);
+        transaction.commit(ServiceName::Hotel.string_name(), None);
+
+        assert!(transaction.reject(ServiceName::Airline.string_name()));
+        assert!(!transaction.reject(ServiceName::Hotel.string_name()));
+        assert_eq!(
+            transaction.rejected_services(),
+            vec![ServiceName::Airline.string_name()]
+        );
+        assert_eq!(
+            transaction.service_states()[&ServiceName::Airline.string_name()],
+            TransactionState::Aborted
+        );
+    }
 }
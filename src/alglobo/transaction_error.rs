@@ -5,6 +5,7 @@ pub enum TransactionError {
     None,
     Timeout,
     WrongId,
+    StepDown,
 }
 
 impl fmt::Display for TransactionError {
@@ -13,6 +14,7 @@ impl fmt::Display for TransactionError {
             TransactionError::None => write!(f, "No hay transaccion siendo procesada"),
             TransactionError::Timeout => write!(f, "Timeout en transaccion"),
             TransactionError::WrongId => write!(f, "No es la transaccion actual"),
+            TransactionError::StepDown => write!(f, "El lider esta renunciando"),
         }
     }
 }
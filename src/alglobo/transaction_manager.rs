@@ -1,98 +1,200 @@
-use std::fs::{File, OpenOptions};
-use std::io::Write;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
 use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock};
 use std::thread::sleep;
+use std::time::Instant;
 use std::{collections::HashMap, time::Duration};
-
-use crate::candidates::constants::{DEFAULT_IP, END_TIMEOUT, SLEEP_MANAGER};
-use crate::file_reader::file_iterator::FileIterator;
+use tracing::{debug, error, info, warn};
+
+use crate::candidates::step_down::StepDown;
+use crate::file_reader::transaction_source::SourceSpec;
+use crate::metrics::registry::metrics;
+use crate::submit::submit_queue::{Submission, SubmitQueue};
+use crate::telemetry::{
+    trace_context::TraceContext,
+    trace_span::{SpanKind, TraceSpan},
+};
 use crate::{
     sockets::udp_socket_sender::UdpSocketSender,
     transaction_messages::{
-        transaction_code::TransactionCode, transaction_request::TransactionRequest,
+        transaction_checkpoint::TransactionCheckpoint,
+        transaction_code::TransactionCode,
+        transaction_handoff::TransactionHandoff,
+        transaction_info::TransactionInfo,
+        transaction_request::TransactionRequest,
+        transaction_retry_ack::{RetryReply, TransactionRetryAck},
+        transaction_retry_queue::TransactionRetryQueue,
     },
 };
 
 use super::{
-    transaction::Transaction, transaction_error::TransactionError,
-    transactionable::Transactionable, types::CurrentTransaction,
+    aborts::{AbortReason, AbortStore, ServiceVote},
+    decisions::Decisions,
+    retry_queue::{QueuedRetry, RetryQueue, RETRY_ID_BASE},
+    transaction::Transaction,
+    transaction_error::TransactionError,
+    transaction_state::TransactionState,
+    transactionable::Transactionable,
+    types::{CurrentTransaction, LastDecided},
 };
 
 pub struct TransactionManager {
-    my_port: u64,
+    my_addr: SocketAddr,
     udp_sender: Box<dyn UdpSocketSender + Send>,
     curr_transaction: CurrentTransaction,
     ended: Arc<(Mutex<bool>, Condvar)>,
-    services_addrs: HashMap<String, String>,
-    replicas_addrs: Vec<String>,
+    services_addrs: HashMap<String, SocketAddr>,
+    replicas_addrs: Vec<SocketAddr>,
     timeout: Duration,
-    abort_file_opt: Option<File>,
+    sleep_manager: Duration,
+    end_timeout: Duration,
+    abort_store_opt: Option<AbortStore>,
+    decisions: Decisions,
+    offset_opt: Option<u64>,
+    trace_opt: Option<TraceContext>,
+    submissions_opt: Option<SubmitQueue>,
+    retries_opt: Option<RetryQueue>,
+    last_decided_opt: Option<LastDecided>,
+    /// Id original del reintento en ejecucion, con el que se registra
+    /// su aborto
+    retried_id_opt: Option<u64>,
 }
 
+// Nombre con el que se exportan los spans del coordinador
+static SPAN_SERVICE: &str = "coordinator";
+
 impl TransactionManager {
     #[must_use]
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        my_port: u64,
+        my_addr: SocketAddr,
         udp_sender: Box<dyn UdpSocketSender + Send>,
         curr_transaction: CurrentTransaction,
         ended: Arc<(Mutex<bool>, Condvar)>,
-        services_addrs_str: &HashMap<&str, String>,
-        replicas_addrs_str: &[String],
+        services_addrs: &HashMap<SocketAddr, String>,
+        replicas_addrs: &[SocketAddr],
         timeout: Duration,
+        sleep_manager: Duration,
+        end_timeout: Duration,
         path_opt: Option<String>,
     ) -> Self {
-        let services_addrs = services_addrs_str
-            .clone()
-            .iter()
-            .map(|(addr, name)| (name.clone(), (*addr).to_string()))
-            .collect();
-        let replicas_addrs = replicas_addrs_str
+        let services_addrs = services_addrs
             .iter()
-            .map(std::string::ToString::to_string)
+            .map(|(addr, name)| (name.clone(), *addr))
             .collect();
-        let mut abort_file_opt = None;
-        if let Some(path) = path_opt {
-            let mut options = OpenOptions::new();
-            abort_file_opt = Some(
-                options
-                    .create(true)
-                    .append(true)
-                    .open(path)
-                    .expect("[Transaction Manager] Error al abrir archivo de fallas"),
-            );
-        }
+        let replicas_addrs = replicas_addrs.to_vec();
+        let abort_store_opt = path_opt.map(|path| {
+            AbortStore::open(&path).expect("[Transaction Manager] Error al abrir archivo de fallas")
+        });
 
         TransactionManager {
-            my_port,
+            my_addr,
             udp_sender,
             curr_transaction,
             ended,
             services_addrs,
             replicas_addrs,
             timeout,
-            abort_file_opt,
+            sleep_manager,
+            end_timeout,
+            abort_store_opt,
+            decisions: Decisions::new(),
+            offset_opt: None,
+            trace_opt: None,
+            submissions_opt: None,
+            retries_opt: None,
+            last_decided_opt: None,
+            retried_id_opt: None,
         }
     }
 
+    /// Retoma el trabajo del lider anterior, con lo que traspaso al
+    /// renunciar o lo que replico antes de caerse: la lectura del
+    /// archivo continua desde `offset` y no se vuelven a procesar
+    /// las transacciones ya decididas, sin importar el orden de sus ids
+    pub fn resume_from(&mut self, offset: u64, decisions: Decisions) {
+        self.offset_opt = Some(offset);
+        self.decisions = decisions;
+    }
+
+    /// Procesa tambien las reservas recibidas por la API SUBMIT. El
+    /// lider no finaliza al terminar el archivo, sino que sigue
+    /// esperando pedidos hasta que se le pida la renuncia.
+    pub fn accept_submissions(&mut self, queue: SubmitQueue) {
+        self.submissions_opt = Some(queue);
+    }
+
+    /// Ejecuta tambien los reintentos que el `TransactionReceiver` deja
+    /// en `queue`, antes de la proxima transaccion del archivo
+    pub fn accept_retries(&mut self, queue: RetryQueue) {
+        self.retries_opt = Some(queue);
+    }
+
+    /// Deja en `last_decided` el id de cada transaccion que decide,
+    /// para consultarlo por el puerto de administracion
+    pub fn report_decisions(&mut self, last_decided: LastDecided) {
+        self.last_decided_opt = Some(last_decided);
+    }
+
     pub fn process(&mut self, opt_transaction: Option<Transaction>) -> u64 {
+        let started = Instant::now();
+        // Cada transaccion es una traza; sus fases son spans hijos
+        let mut span = TraceSpan::root(SPAN_SERVICE, "transaction", SpanKind::Internal);
+        span.set_attribute("node.addr", self.my_addr);
         if let Some(transaction) = opt_transaction {
+            if let Some(booking) = transaction.booking() {
+                if let Some(customer) = &booking.customer {
+                    span.set_attribute("booking.customer", customer);
+                }
+                if let Some(currency) = &booking.currency {
+                    span.set_attribute("booking.currency", currency);
+                }
+                debug!(
+                    transaction_id = transaction.get_id(),
+                    customer = booking.customer.as_deref(),
+                    currency = booking.currency.as_deref(),
+                    "Reserva leida"
+                );
+            }
             self.update_current(transaction);
         }
-        if self.prepare() {
+        self.trace_opt = Some(span.context);
+        let commited = self.prepare();
+        if commited {
             // Seguir commiteando hasta que
             // todos los servicios respondan
             while !self.commit() {}
         } else {
+            // Los votos se toman antes de que la fase de abort
+            // deje a todos los servicios abortados
+            let (reason, services) = self.votes();
             self.abort();
-            self.persist_aborted();
+            self.persist_aborted(reason, services);
         }
-
-        let opt_transaction = self.get_current();
-        let transaction = opt_transaction
+        let outcome = [("outcome", if commited { "commit" } else { "abort" })];
+        metrics().transactions.inc(&outcome);
+        metrics()
+            .transaction_duration
+            .observe(&outcome, started.elapsed());
+
+        let transaction_id = self
+            .get_current()
             .as_ref()
-            .expect("[Transaction Manager] La transaccion actual deberia exitir");
-        transaction.get_id()
+            .expect("[Transaction Manager] La transaccion actual deberia exitir")
+            .get_id();
+        self.record_decision(transaction_id, commited);
+        if let Some(last_decided) = &self.last_decided_opt {
+            *last_decided
+                .lock()
+                .expect("[Transaction Manager] Lock de ultima decision envenenado") =
+                Some(transaction_id);
+        }
+        self.send_checkpoint(transaction_id, commited);
+        span.set_attribute("transaction.id", transaction_id);
+        span.set_attribute("transaction.outcome", outcome[0].1);
+        span.end();
+        self.trace_opt = None;
+        transaction_id
     }
 
     pub fn update_current(&mut self, transaction: Transaction) {
@@ -115,13 +217,22 @@ impl TransactionManager {
             transaction_id = transaction.get_id();
             waiting_services = transaction.waiting_services();
         }
-        self.send_messages(TransactionCode::Prepare, transaction_id, waiting_services);
+        let started = Instant::now();
+        let span = self.phase_span("prepare", transaction_id);
+        self.send_messages(
+            TransactionCode::Prepare,
+            transaction_id,
+            waiting_services,
+            &span.context,
+        );
         let _ = self.wait_update(|opt_transaction| {
             opt_transaction
                 .as_ref()
                 .expect("[Transaction Manager] La transaccion actual deberia existir")
                 .is_any_waiting()
         });
+        metrics().prepare_duration.observe(&[], started.elapsed());
+        span.end();
         self.send_transaction_logs();
         let opt_transaction = self.get_current();
         let transaction = opt_transaction
@@ -141,13 +252,20 @@ impl TransactionManager {
             transaction_id = transaction.get_id();
             all_services = transaction.not_aborted_services();
         }
-        self.send_messages(TransactionCode::Abort, transaction_id, all_services);
+        let span = self.phase_span("abort", transaction_id);
+        self.send_messages(
+            TransactionCode::Abort,
+            transaction_id,
+            all_services,
+            &span.context,
+        );
         let _ = self.wait_update(|opt_transaction| {
             !opt_transaction
                 .as_ref()
                 .expect("[Transaction Manager] La transaccion actual deberia existir")
                 .is_aborted()
         });
+        span.end();
         self.send_transaction_logs();
         let opt_transaction = self.get_current();
         let transaction = opt_transaction
@@ -167,13 +285,20 @@ impl TransactionManager {
             transaction_id = transaction.get_id();
             all_services = transaction.accepted_services();
         }
-        self.send_messages(TransactionCode::Commit, transaction_id, all_services);
+        let span = self.phase_span("commit", transaction_id);
+        self.send_messages(
+            TransactionCode::Commit,
+            transaction_id,
+            all_services,
+            &span.context,
+        );
         let _ = self.wait_update(|opt_transaction| {
             !opt_transaction
                 .as_ref()
                 .expect("[Transaction Manager] La transacci\u{f3}n actual deberia existir")
                 .is_commited()
         });
+        span.end();
         self.send_transaction_logs();
         let opt_transaction = self.get_current();
         let transaction = opt_transaction
@@ -202,23 +327,41 @@ impl TransactionManager {
         Ok(())
     }
 
+    /// Span de una fase de la transaccion actual. Si la fase se
+    /// ejecuta fuera de `process` inicia su propia traza.
+    fn phase_span(&self, name: &'static str, transaction_id: u64) -> TraceSpan {
+        let mut span = match &self.trace_opt {
+            Some(trace) => TraceSpan::child_of(SPAN_SERVICE, name, SpanKind::Client, trace),
+            None => TraceSpan::root(SPAN_SERVICE, name, SpanKind::Client),
+        };
+        span.set_attribute("transaction.id", transaction_id);
+        span
+    }
+
     fn send_messages(
         &mut self,
         code: TransactionCode,
         id: u64,
         services_info: HashMap<String, f64>,
+        trace: &TraceContext,
     ) {
         for (name, fee) in services_info {
             let addr = self.services_addrs.get(&name).expect(
                 "[Transaction Manager] La direcci\u{f3}n IP del servicio web deberia existir",
             );
-            println!(
-                "[Transaction Manager] Transaccion: {} - Entidad: {} - Operacion: {}",
-                id, name, code
+            info!(
+                transaction_id = id,
+                service = %name,
+                code = %code,
+                trace_id = %trace.trace_id_hex(),
+                "Mensaje de transaccion enviado"
             );
 
             self.udp_sender
-                .send_to(&TransactionRequest::build(code, id, fee), addr)
+                .send_to(
+                    &TransactionRequest::build_with_trace(code, id, fee, Some(trace)),
+                    addr,
+                )
                 .expect(
                     "[Transaction Manager] Enviar mensaje de transacci\u{f3}n no deberia fallar",
                 );
@@ -236,41 +379,114 @@ impl TransactionManager {
             transaction_log = transaction.log();
             transaction_id = transaction.get_id();
         }
-        let my_addr = DEFAULT_IP.to_string() + self.my_port.to_string().as_str();
+        self.replicate_log(transaction_id, &transaction_log);
+    }
+
+    fn replicate_log(&mut self, transaction_id: u64, transaction_log: &[u8]) {
         for addr in self.replicas_addrs.clone() {
-            if addr == my_addr {
+            if addr == self.my_addr {
                 continue;
             }
-            println!(
-                "[Transaction Manager] Log Transaccion: {} - Addr: {}",
-                transaction_id, addr
-            );
+            debug!(transaction_id, replica = %addr, "Log de transaccion enviado");
             self.udp_sender
-                .send_to(&transaction_log, &addr)
+                .send_to(transaction_log, &addr)
                 .expect("[Transaction Manager] Enviar mensaje de log no deberia fallar");
         }
     }
 
-    fn persist_aborted(&mut self) {
-        if let Some(abort_file) = &mut self.abort_file_opt {
-            let opt_transaction = self
-                .curr_transaction
-                .0
-                .lock()
-                .expect("[Transaction Manager] Lock de transaccion envenenado");
-            let transaction = opt_transaction
-                .as_ref()
-                .expect("[Transaction Manager] La transaccion actual deberia exitir");
-            let mut representation = transaction.representation(false);
-            representation.push('\n');
-            abort_file
-                .write_all(representation.as_bytes())
+    /// Replica en los demas nodos la decision y el offset de lectura,
+    /// para que un lider elegido tras una caida retome desde ahi
+    pub fn send_checkpoint(&mut self, transaction_id: u64, commited: bool) {
+        let offset = self.offset_opt.unwrap_or(0);
+        let mut message = TransactionCheckpoint::build(transaction_id, commited, offset);
+        TransactionInfo::add_padding(&mut message);
+        for addr in self.replicas_addrs.clone() {
+            if addr == self.my_addr {
+                continue;
+            }
+            debug!(transaction_id, offset, replica = %addr, "Progreso replicado");
+            self.udp_sender
+                .send_to(&message, &addr)
+                .expect("[Transaction Manager] Enviar progreso no deberia fallar");
+        }
+    }
+
+    /// Offset de lectura y decisiones (ver `Decisions`), que conserva
+    /// el nodo al dejar de ser lider
+    #[must_use]
+    pub fn progress(&self) -> (u64, Decisions) {
+        (self.offset_opt.unwrap_or(0), self.decisions.clone())
+    }
+
+    /// Envia al sucesor, en un unico mensaje, el offset del archivo de
+    /// transacciones y las decisiones que conserva. Si no se puede
+    /// enviar, el sucesor retoma desde el progreso replicado.
+    pub fn hand_off(&mut self, addr: &SocketAddr) {
+        let offset = self.offset_opt.unwrap_or(0);
+        info!(
+            offset,
+            decisions = self.decisions.len(),
+            successor = %addr,
+            "Traspasando liderazgo"
+        );
+        let mut message = TransactionHandoff::build(offset, &self.decisions);
+        TransactionInfo::add_padding(&mut message);
+        if let Err(err) = self.udp_sender.send_to(&message, addr) {
+            warn!(successor = %addr, error = %err, "No se pudo enviar el traspaso");
+        }
+    }
+
+    /// Motivo del aborto y estado de cada servicio en la transaccion actual
+    fn votes(&self) -> (AbortReason, BTreeMap<String, ServiceVote>) {
+        let opt_transaction = self.get_current();
+        let transaction = opt_transaction
+            .as_ref()
+            .expect("[Transaction Manager] La transaccion actual deberia exitir");
+        let states = transaction.service_states();
+        let services: BTreeMap<String, ServiceVote> = transaction
+            .all_services()
+            .into_iter()
+            .map(|(name, fee)| {
+                let state = states[&name];
+                (name, ServiceVote { state, fee })
+            })
+            .collect();
+        let reason = if !transaction.rejected_services().is_empty() {
+            AbortReason::DecodeError
+        } else if services
+            .values()
+            .any(|vote| vote.state == TransactionState::Aborted)
+        {
+            AbortReason::Vote
+        } else {
+            AbortReason::Timeout
+        };
+        (reason, services)
+    }
+
+    fn persist_aborted(&mut self, reason: AbortReason, services: BTreeMap<String, ServiceVote>) {
+        let current_id = self
+            .get_current()
+            .as_ref()
+            .expect("[Transaction Manager] La transaccion actual deberia exitir")
+            .get_id();
+        // Los reintentos se registran con el id original, para
+        // contar sus intentos y volver a reintentarlos
+        let transaction_id = self.retried_id_opt.unwrap_or(current_id);
+        if let Some(abort_store) = &mut self.abort_store_opt {
+            let record = abort_store
+                .record(transaction_id, reason, services)
                 .expect("[Transaction Manager] Persistir transaccion abortada no deberia fallar");
-            println!("[Transaction Manager] Transaccion abortada persistida");
+            info!(
+                transaction_id,
+                reason = %record.reason,
+                attempts = record.attempts,
+                "Transaccion abortada persistida"
+            );
         }
     }
 
-    fn get_current(&self) -> MutexGuard<'_, Option<Box<(dyn Transactionable + Send + 'static)>>> {
+    fn get_current(&self) -> MutexGuard<'_, Option<Box<dyn Transactionable + Send + 'static>>> {
         self.curr_transaction
             .0
             .lock()
@@ -278,45 +494,253 @@ impl TransactionManager {
     }
 
     #[allow(clippy::mutex_atomic)]
-    fn wait_end_while(&mut self, dur: Duration) -> Result<(), TransactionError> {
+    fn wait_end_while(
+        &mut self,
+        dur: Duration,
+        step_down: &StepDown,
+    ) -> Result<(), TransactionError> {
         let lock_err_msg = "[Transaction Manager] Lock de espera de finalizacion envenenado";
         {
             let mut ended = self.ended.0.lock().expect(lock_err_msg);
             *ended = true;
         }
-        println!(
-            "[Transaction Manager] Esperando {:#?} antes de finalizar",
-            dur
+        debug!(
+            wait_ms = dur.as_millis() as u64,
+            "Esperando antes de finalizar"
         );
         let res = self
             .ended
             .1
             .wait_timeout_while(self.ended.0.lock().expect(lock_err_msg), dur, |ended| {
                 *ended
+                    && !step_down.requested()
+                    && self
+                        .submissions_opt
+                        .as_ref()
+                        .is_none_or(SubmitQueue::is_empty)
+                    && self.retries_opt.as_ref().is_none_or(RetryQueue::is_empty)
             })
             .expect(lock_err_msg);
         if res.1.timed_out() {
             return Err(TransactionError::Timeout);
         }
+        if *res.0 {
+            return Err(TransactionError::StepDown);
+        }
         Ok(())
     }
 
-    pub fn run(&mut self, path: &str, finish_lock: &Arc<RwLock<bool>>) {
-        let start_line = self.process(None);
-        if let Ok(mut reader) = FileIterator::new(path) {
-            while !reader.ended() {
-                if let Some(transaction) = reader.next() {
-                    if transaction.get_id() > start_line {
-                        sleep(SLEEP_MANAGER);
-                        self.process(Some(transaction));
+    /// Espera hasta `dur` un reintento o un pedido SUBMIT y lo procesa.
+    /// Devuelve verdadero si proceso alguno.
+    fn serve_idle(&mut self, dur: Duration, step_down: &StepDown) -> bool {
+        let _woken = self.wait_end_while(dur, step_down);
+        if step_down.requested() {
+            return false;
+        }
+        self.process_queued_retry() || self.process_submission()
+    }
+
+    /// Ejecuta el proximo reintento encolado con su propio id y le avisa
+    /// el resultado al cliente. Una transaccion ya commiteada no se
+    /// vuelve a ejecutar.
+    fn process_queued_retry(&mut self) -> bool {
+        let retry = match self.retries_opt.as_ref().and_then(RetryQueue::front) {
+            Some(value) => value,
+            None => return false,
+        };
+        let retry_id = retry.retry_id();
+        // Un lider anterior pudo haberlo ejecutado sin llegar a quitarlo
+        let commited = if let Some(commited) = self.decisions.get(retry_id) {
+            commited
+        } else if self.is_commited(retry.id) {
+            warn!(
+                transaction_id = retry.id,
+                "Reintento de una transaccion commiteada ignorado"
+            );
+            true
+        } else {
+            info!(
+                transaction_id = retry.id,
+                retry_id,
+                seq = retry.seq,
+                "Reintentando transaccion"
+            );
+            self.retried_id_opt = Some(retry.id);
+            self.process(Some(retry.transaction()));
+            self.retried_id_opt = None;
+            self.decisions.get(retry_id) == Some(true)
+        };
+        if commited && !self.is_commited(retry.id) {
+            self.record_decision(retry.id, true);
+            self.send_checkpoint(retry.id, true);
+        }
+        self.finish_retry(&retry, commited);
+        true
+    }
+
+    /// Registra la decision con el offset actual y, si se commiteo una
+    /// transaccion que se puede reintentar, la recuerda en la cola de
+    /// reintentos para denegar sus reintentos
+    fn record_decision(&mut self, id: u64, commited: bool) {
+        self.decisions
+            .insert(id, commited, self.offset_opt.unwrap_or(0));
+        if commited && id < RETRY_ID_BASE {
+            if let Some(retries) = &self.retries_opt {
+                retries.commited(id);
+            }
+        }
+    }
+
+    /// Indica si la transaccion `id` se commiteo, aunque su decision ya
+    /// se haya descartado al avanzar la lectura del archivo
+    fn is_commited(&self, id: u64) -> bool {
+        self.decisions.get(id) == Some(true)
+            || self
+                .retries_opt
+                .as_ref()
+                .is_some_and(|retries| retries.is_commited(id))
+    }
+
+    /// Quita el reintento de la cola, en este nodo y en los demas, y
+    /// le avisa el resultado al cliente si lo conoce
+    fn finish_retry(&mut self, retry: &QueuedRetry, commited: bool) {
+        if let Some(retries) = &self.retries_opt {
+            retries.done(retry.seq);
+        }
+        let mut message = TransactionRetryQueue::build_done(retry.seq);
+        TransactionInfo::add_padding(&mut message);
+        for addr in self.replicas_addrs.clone() {
+            if addr == self.my_addr {
+                continue;
+            }
+            self.udp_sender
+                .send_to(&message, &addr)
+                .expect("[Transaction Manager] Enviar reintento terminado no deberia fallar");
+        }
+        let client = match retry.client_opt {
+            Some(value) => value,
+            None => return,
+        };
+        let reply = if commited {
+            RetryReply::Commited
+        } else {
+            RetryReply::Aborted
+        };
+        let mut message = TransactionRetryAck::build(retry.id, reply);
+        TransactionInfo::add_padding(&mut message);
+        debug!(transaction_id = retry.id, client = %client, reply = %reply, "Resultado del reintento enviado");
+        if let Err(err) = self.udp_sender.send_to(&message, &client) {
+            warn!(transaction_id = retry.id, client = %client, error = %err, "No se pudo enviar el resultado del reintento");
+        }
+    }
+
+    /// Procesa el proximo pedido SUBMIT encolado, con el proximo id de
+    /// SUBMIT, y le responde al cliente. Cada reserva usa un id nuevo
+    /// aunque el cliente repita el suyo, para que los servicios no le
+    /// respondan con los votos de otra.
+    fn process_submission(&mut self) -> bool {
+        let submission = match self.submissions_opt.as_ref().and_then(SubmitQueue::pop) {
+            Some(value) => value,
+            None => return false,
+        };
+        let submit_id = self.decisions.next_submit_id();
+        let Submission {
+            mut transaction,
+            reply,
+        } = submission;
+        info!(
+            transaction_id = transaction.get_id(),
+            submit_id, "Procesando reserva recibida por SUBMIT"
+        );
+        transaction.set_id(submit_id);
+        // Se replica antes de preparar: si el lider se cae, el siguiente
+        // retoma esta reserva en lugar de darle su id a otra
+        self.replicate_log(submit_id, &transaction.log());
+        self.process(Some(transaction));
+        reply.send(self.decisions.get(submit_id).unwrap_or(false));
+        true
+    }
+
+    pub fn run(
+        &mut self,
+        source: &SourceSpec,
+        finish_lock: &Arc<RwLock<bool>>,
+        step_down: &StepDown,
+    ) {
+        let reader_res = source.open();
+        if let Err(err) = &reader_res {
+            error!(error = %err, "No se pudo abrir el archivo de transacciones");
+        }
+        let mut reader_opt = reader_res.ok();
+        if let Some(reader) = &mut reader_opt {
+            if let Some(offset) = self.offset_opt {
+                reader
+                    .seek(offset)
+                    .expect("[Transaction Manager] Offset heredado invalido");
+            } else if let Some(offset) = source.checkpoint_offset() {
+                if reader.seek(offset).is_err() {
+                    warn!(
+                        offset,
+                        "Checkpoint invalido, se lee el archivo desde el principio"
+                    );
+                    let _drop = reader.seek(0);
+                }
+            }
+            self.offset_opt = Some(reader.offset());
+        }
+        // La transaccion actual se retoma salvo que ya este decidida,
+        // porque la traspaso o la replico el lider anterior
+        let current_id_opt = self
+            .get_current()
+            .as_ref()
+            .map(|transaction| transaction.get_id());
+        if current_id_opt.is_some_and(|id| !self.decisions.contains(id)) {
+            self.process(None);
+        }
+        if let Some(mut reader) = reader_opt {
+            let checkpoint_opt = source.checkpoint();
+            let mut saved_opt = None;
+            while !step_down.requested() {
+                // Los reintentos y las reservas en vivo tienen prioridad
+                // sobre el archivo
+                if self.process_queued_retry() || self.process_submission() {
+                    continue;
+                }
+                match reader.next() {
+                    Some(transaction) => {
+                        // El offset se actualiza antes de procesar para
+                        // replicarlo junto con la decision
+                        self.offset_opt = Some(reader.offset());
+                        if !self.decisions.contains(transaction.get_id()) {
+                            sleep(self.sleep_manager);
+                            self.process(Some(transaction));
+                        }
+                    }
+                    // Siguiendo el archivo, mientras no haya filas nuevas
+                    // se atienden los reintentos y los pedidos SUBMIT
+                    None => match &source.follow_opt {
+                        Some(follow) => {
+                            self.serve_idle(follow.poll, step_down);
+                        }
+                        None => break,
+                    },
+                }
+                let offset = reader.offset();
+                self.offset_opt = Some(offset);
+                if let Some(checkpoint) = &checkpoint_opt {
+                    if saved_opt != Some(offset) {
+                        saved_opt = Some(offset);
+                        if let Err(err) = checkpoint.save(&source.path, offset) {
+                            warn!(error = %err, offset, "No se pudo guardar el checkpoint");
+                        }
                     }
                 }
             }
         }
 
-        while self.wait_end_while(END_TIMEOUT).is_ok() {
-            println!("[Transaction Manager] Reintentando transaccion");
-            self.process(None);
+        while !step_down.requested()
+            && (self.serve_idle(self.end_timeout, step_down) || self.submissions_opt.is_some())
+        {
         }
 
         let mut finish = finish_lock
@@ -337,25 +761,40 @@ mod tests {
             socket_error::SocketError, udp_socket_receiver::MockUdpSocketReceiver,
             udp_socket_sender::MockUdpSocketSender,
         },
+        submit::submit_queue::SUBMIT_ID_BASE,
+        telemetry::trace_context::TRACE_CONTEXT_SIZE,
         transaction_messages::{
             transaction_info::TransactionInfo, transaction_log::TransactionLog,
-            transaction_response::TransactionResponse,
+            transaction_response::TransactionResponse, types::LOG_BYTE,
         },
     };
 
     use std::{
         collections::HashMap,
+        net::SocketAddr,
         sync::{Arc, Condvar, Mutex},
         thread,
     };
 
+    // Los pedidos llevan un contexto de traza aleatorio: se verifica
+    // que exista y se lo reemplaza por ceros para comparar el resto.
+    // Un pedido sin traza no coincide con ningun mensaje esperado.
+    fn without_trace(buf: &[u8]) -> Vec<u8> {
+        if TransactionRequest::parse_trace(buf).is_none() {
+            return Vec::new();
+        }
+        let mut message = buf.to_vec();
+        message[TransactionRequest::size() - TRACE_CONTEXT_SIZE..].fill(0);
+        message
+    }
+
     #[test]
     fn process_transaction_should_send_msg_prepare_to_all_services_in_transaction() {
-        let id = 0;
+        let my_addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
 
-        let airline_addr = "127.0.0.1:49156";
-        let hotel_addr = "127.0.0.1:49157";
-        let bank_addr = "127.0.0.1:49158";
+        let airline_addr: SocketAddr = "127.0.0.1:49156".parse().unwrap();
+        let hotel_addr: SocketAddr = "127.0.0.1:49157".parse().unwrap();
+        let bank_addr: SocketAddr = "127.0.0.1:49158".parse().unwrap();
         let services_addrs_str = &HashMap::from([
             (airline_addr, ServiceName::Airline.string_name()),
             (hotel_addr, ServiceName::Hotel.string_name()),
@@ -394,18 +833,20 @@ mod tests {
         mock_sender
             .expect_send_to()
             .withf(move |buf, addr| {
-                messages_clone.contains(&buf.to_vec()) && addresses_clone.contains(&addr)
+                messages_clone.contains(&without_trace(buf)) && addresses_clone.contains(addr)
             })
             .times(waiting_services.len())
             .returning(|_, _| Ok(()));
 
         let mut manager = TransactionManager::new(
-            id,
+            my_addr,
             Box::new(mock_sender),
             curr_transaction.clone(),
             Arc::new((Mutex::new(false), Condvar::new())),
-            &services_addrs_str,
-            &vec![],
+            services_addrs_str,
+            &[],
+            Duration::from_secs(0),
+            Duration::from_secs(0),
             Duration::from_secs(0),
             None,
         );
@@ -417,11 +858,11 @@ mod tests {
     #[test]
     fn process_transaction_should_send_msg_abort_to_all_services_if_any_service_does_not_respond_to_prepare_msg(
     ) {
-        let id = 0;
+        let my_addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
 
-        let airline_addr = "127.0.0.1:49156";
-        let hotel_addr = "127.0.0.1:49157";
-        let bank_addr = "127.0.0.1:49158";
+        let airline_addr: SocketAddr = "127.0.0.1:49156".parse().unwrap();
+        let hotel_addr: SocketAddr = "127.0.0.1:49157".parse().unwrap();
+        let bank_addr: SocketAddr = "127.0.0.1:49158".parse().unwrap();
         let services_addrs_str = &HashMap::from([
             (airline_addr, ServiceName::Airline.string_name()),
             (hotel_addr, ServiceName::Hotel.string_name()),
@@ -466,14 +907,14 @@ mod tests {
         mock_sender
             .expect_send_to()
             .withf(move |buf, addr| {
-                messages_clone.contains(&buf.to_vec()) && addresses_clone.contains(&addr)
+                messages_clone.contains(&without_trace(buf)) && addresses_clone.contains(addr)
             })
             .times(waiting_services.len())
             .returning(|_, _| Ok(()));
 
         let mut receiver = TransactionReceiver::new(
             Box::new(mock_receiver),
-            &services_addrs_str,
+            services_addrs_str,
             curr_transaction.clone(),
             Arc::new((Mutex::new(false), Condvar::new())),
         );
@@ -483,12 +924,14 @@ mod tests {
         });
 
         let mut manager = TransactionManager::new(
-            id,
+            my_addr,
             Box::new(mock_sender),
             curr_transaction.clone(),
             Arc::new((Mutex::new(false), Condvar::new())),
-            &services_addrs_str,
-            &vec![],
+            services_addrs_str,
+            &[],
+            Duration::from_secs(0),
+            Duration::from_secs(0),
             Duration::from_secs(0),
             None,
         );
@@ -501,11 +944,11 @@ mod tests {
     #[test]
     fn process_transaction_should_send_msg_commit_to_all_services_if_all_services_responded_with_accept_msg(
     ) {
-        let id = 0;
+        let my_addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
 
-        let airline_addr = "127.0.0.1:49156";
-        let hotel_addr = "127.0.0.1:49157";
-        let bank_addr = "127.0.0.1:49158";
+        let airline_addr: SocketAddr = "127.0.0.1:49156".parse().unwrap();
+        let hotel_addr: SocketAddr = "127.0.0.1:49157".parse().unwrap();
+        let bank_addr: SocketAddr = "127.0.0.1:49158".parse().unwrap();
         let addresses = [airline_addr, hotel_addr, bank_addr];
         let services_addrs_str = &HashMap::from([
             (airline_addr, ServiceName::Airline.string_name()),
@@ -553,26 +996,26 @@ mod tests {
             .expect_recv()
             .withf(move |_| true)
             .times(1)
-            .returning(move |_| Ok((accept_msg_clone.clone(), airline_addr.to_string())));
+            .returning(move |_| Ok((accept_msg_clone.clone(), airline_addr)));
 
         accept_msg_clone = accept_msg.clone();
         mock_receiver
             .expect_recv()
             .withf(move |_| true)
             .times(1)
-            .returning(move |_| Ok((accept_msg_clone.clone(), hotel_addr.to_string())));
+            .returning(move |_| Ok((accept_msg_clone.clone(), hotel_addr)));
 
         accept_msg_clone = accept_msg.clone();
         mock_receiver
             .expect_recv()
             .withf(move |_| true)
             .times(1)
-            .returning(move |_| Ok((accept_msg_clone.clone(), bank_addr.to_string())));
+            .returning(move |_| Ok((accept_msg_clone.clone(), bank_addr)));
 
         mock_sender
             .expect_send_to()
             .withf(move |buf, addr| {
-                commit_messages.contains(&buf.to_vec()) && addresses.contains(&addr)
+                commit_messages.contains(&without_trace(buf)) && addresses.contains(addr)
             })
             .times(n_services)
             .returning(|_, _| Ok(()));
@@ -587,7 +1030,7 @@ mod tests {
 
         let mut receiver = TransactionReceiver::new(
             Box::new(mock_receiver),
-            &services_addrs_str,
+            services_addrs_str,
             curr_transaction.clone(),
             Arc::new((Mutex::new(false), Condvar::new())),
         );
@@ -597,13 +1040,15 @@ mod tests {
         });
 
         let mut manager = TransactionManager::new(
-            id,
+            my_addr,
             Box::new(mock_sender),
             curr_transaction.clone(),
             Arc::new((Mutex::new(false), Condvar::new())),
-            &services_addrs_str,
-            &vec![],
+            services_addrs_str,
+            &[],
             Duration::from_secs(2),
+            Duration::from_secs(0),
+            Duration::from_secs(0),
             None,
         );
 
@@ -614,18 +1059,17 @@ mod tests {
 
     #[test]
     fn it_should_send_log_after_prepare_phase() {
-        let id = 0;
+        let my_addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
 
         let replicas_addrs = vec![
-            "127.0.0.1:49159".to_string(),
-            "127.0.0.1:49160".to_string(),
-            "127.0.0.1:49161".to_string(),
+            "127.0.0.1:49159".parse().unwrap(),
+            "127.0.0.1:49160".parse().unwrap(),
+            "127.0.0.1:49161".parse().unwrap(),
         ];
-        let replicas_addrs_clone;
 
-        let airline_addr = "127.0.0.1:49156";
-        let hotel_addr = "127.0.0.1:49157";
-        let bank_addr = "127.0.0.1:49158";
+        let airline_addr: SocketAddr = "127.0.0.1:49156".parse().unwrap();
+        let hotel_addr: SocketAddr = "127.0.0.1:49157".parse().unwrap();
+        let bank_addr: SocketAddr = "127.0.0.1:49158".parse().unwrap();
         let services_addrs_str = &HashMap::from([
             (airline_addr, ServiceName::Airline.string_name()),
             (hotel_addr, ServiceName::Hotel.string_name()),
@@ -662,7 +1106,6 @@ mod tests {
             (TransactionState::Accepted, bank_fee),
         );
         TransactionInfo::add_padding(&mut log_msg);
-        let log_msg_clone;
 
         mock_sender
             .expect_send_to()
@@ -675,28 +1118,28 @@ mod tests {
             .expect_recv()
             .withf(move |_| true)
             .times(1)
-            .returning(move |_| Ok((accept_msg_clone.clone(), airline_addr.to_string())));
+            .returning(move |_| Ok((accept_msg_clone.clone(), airline_addr)));
 
         accept_msg_clone = accept_msg.clone();
         mock_receiver
             .expect_recv()
             .withf(move |_| true)
             .times(1)
-            .returning(move |_| Ok((accept_msg_clone.clone(), hotel_addr.to_string())));
+            .returning(move |_| Ok((accept_msg_clone.clone(), hotel_addr)));
 
         accept_msg_clone = accept_msg.clone();
         mock_receiver
             .expect_recv()
             .withf(move |_| true)
             .times(1)
-            .returning(move |_| Ok((accept_msg_clone.clone(), bank_addr.to_string())));
+            .returning(move |_| Ok((accept_msg_clone.clone(), bank_addr)));
 
-        log_msg_clone = log_msg.clone();
-        replicas_addrs_clone = replicas_addrs.clone();
+        let log_msg_clone = log_msg.clone();
+        let replicas_addrs_clone = replicas_addrs.clone();
         mock_sender
             .expect_send_to()
             .withf(move |buf, addr| {
-                &buf.to_vec() == &log_msg_clone && replicas_addrs_clone.contains(&addr.to_string())
+                buf.to_vec() == log_msg_clone && replicas_addrs_clone.contains(addr)
             })
             .times(n_services)
             .returning(|_, _| Ok(()));
@@ -707,7 +1150,7 @@ mod tests {
 
         let mut receiver = TransactionReceiver::new(
             Box::new(mock_receiver),
-            &services_addrs_str,
+            services_addrs_str,
             curr_transaction.clone(),
             Arc::new((Mutex::new(false), Condvar::new())),
         );
@@ -717,13 +1160,15 @@ mod tests {
         });
 
         let mut manager = TransactionManager::new(
-            id,
+            my_addr,
             Box::new(mock_sender),
             curr_transaction.clone(),
             Arc::new((Mutex::new(false), Condvar::new())),
-            &services_addrs_str,
+            services_addrs_str,
             &replicas_addrs,
             Duration::from_secs(2),
+            Duration::from_secs(0),
+            Duration::from_secs(0),
             None,
         );
 
@@ -733,18 +1178,17 @@ mod tests {
 
     #[test]
     fn it_should_send_log_after_abort_phase() {
-        let id = 0;
+        let my_addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
 
         let replicas_addrs = vec![
-            "127.0.0.1:49159".to_string(),
-            "127.0.0.1:49160".to_string(),
-            "127.0.0.1:49161".to_string(),
+            "127.0.0.1:49159".parse().unwrap(),
+            "127.0.0.1:49160".parse().unwrap(),
+            "127.0.0.1:49161".parse().unwrap(),
         ];
-        let replicas_addrs_clone;
 
-        let airline_addr = "127.0.0.1:49156";
-        let hotel_addr = "127.0.0.1:49157";
-        let bank_addr = "127.0.0.1:49158";
+        let airline_addr: SocketAddr = "127.0.0.1:49156".parse().unwrap();
+        let hotel_addr: SocketAddr = "127.0.0.1:49157".parse().unwrap();
+        let bank_addr: SocketAddr = "127.0.0.1:49158".parse().unwrap();
         let services_addrs_str = &HashMap::from([
             (airline_addr, ServiceName::Airline.string_name()),
             (hotel_addr, ServiceName::Hotel.string_name()),
@@ -789,12 +1233,12 @@ mod tests {
             (TransactionState::Aborted, bank_fee),
         );
         TransactionInfo::add_padding(&mut log_msg);
-        let log_msg_clone;
 
         mock_sender
             .expect_send_to()
             .withf(move |buf, addr| {
-                abort_requests.contains(&buf.to_vec()) && not_abort_services_addrs.contains(&addr)
+                abort_requests.contains(&without_trace(buf))
+                    && not_abort_services_addrs.contains(addr)
             })
             .times(not_abort_services_addrs.len())
             .returning(|_, _| Ok(()));
@@ -804,21 +1248,21 @@ mod tests {
             .expect_recv()
             .withf(move |_| true)
             .times(1)
-            .returning(move |_| Ok((abort_response_clone.clone(), hotel_addr.to_string())));
+            .returning(move |_| Ok((abort_response_clone.clone(), hotel_addr)));
 
         abort_response_clone = abort_response.clone();
         mock_receiver
             .expect_recv()
             .withf(move |_| true)
             .times(1)
-            .returning(move |_| Ok((abort_response_clone.clone(), bank_addr.to_string())));
+            .returning(move |_| Ok((abort_response_clone.clone(), bank_addr)));
 
-        log_msg_clone = log_msg.clone();
-        replicas_addrs_clone = replicas_addrs.clone();
+        let log_msg_clone = log_msg.clone();
+        let replicas_addrs_clone = replicas_addrs.clone();
         mock_sender
             .expect_send_to()
             .withf(move |buf, addr| {
-                &buf.to_vec() == &log_msg_clone && replicas_addrs_clone.contains(&addr.to_string())
+                buf.to_vec() == log_msg_clone && replicas_addrs_clone.contains(addr)
             })
             .times(n_services)
             .returning(|_, _| Ok(()));
@@ -829,7 +1273,7 @@ mod tests {
 
         let mut receiver = TransactionReceiver::new(
             Box::new(mock_receiver),
-            &services_addrs_str,
+            services_addrs_str,
             curr_transaction.clone(),
             Arc::new((Mutex::new(false), Condvar::new())),
         );
@@ -839,13 +1283,15 @@ mod tests {
         });
 
         let mut manager = TransactionManager::new(
-            id,
+            my_addr,
             Box::new(mock_sender),
             curr_transaction.clone(),
             Arc::new((Mutex::new(false), Condvar::new())),
-            &services_addrs_str,
+            services_addrs_str,
             &replicas_addrs,
             Duration::from_secs(1),
+            Duration::from_secs(0),
+            Duration::from_secs(0),
             None,
         );
 
@@ -855,18 +1301,17 @@ mod tests {
 
     #[test]
     fn it_should_send_log_after_commit_phase() {
-        let id = 0;
+        let my_addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
 
         let replicas_addrs = vec![
-            "127.0.0.1:49159".to_string(),
-            "127.0.0.1:49160".to_string(),
-            "127.0.0.1:49161".to_string(),
+            "127.0.0.1:49159".parse().unwrap(),
+            "127.0.0.1:49160".parse().unwrap(),
+            "127.0.0.1:49161".parse().unwrap(),
         ];
-        let replicas_addrs_clone;
 
-        let airline_addr = "127.0.0.1:49156";
-        let hotel_addr = "127.0.0.1:49157";
-        let bank_addr = "127.0.0.1:49158";
+        let airline_addr: SocketAddr = "127.0.0.1:49156".parse().unwrap();
+        let hotel_addr: SocketAddr = "127.0.0.1:49157".parse().unwrap();
+        let bank_addr: SocketAddr = "127.0.0.1:49158".parse().unwrap();
         let services_addrs_str = &HashMap::from([
             (airline_addr, ServiceName::Airline.string_name()),
             (hotel_addr, ServiceName::Hotel.string_name()),
@@ -914,12 +1359,12 @@ mod tests {
             (TransactionState::Commited, bank_fee),
         );
         TransactionInfo::add_padding(&mut log_msg);
-        let log_msg_clone;
 
         mock_sender
             .expect_send_to()
             .withf(move |buf, addr| {
-                commit_requests.contains(&buf.to_vec()) && accepted_services_addrs.contains(&addr)
+                commit_requests.contains(&without_trace(buf))
+                    && accepted_services_addrs.contains(addr)
             })
             .times(accepted_services_addrs.len())
             .returning(|_, _| Ok(()));
@@ -929,21 +1374,21 @@ mod tests {
             .expect_recv()
             .withf(move |_| true)
             .times(1)
-            .returning(move |_| Ok((commit_response_clone.clone(), hotel_addr.to_string())));
+            .returning(move |_| Ok((commit_response_clone.clone(), hotel_addr)));
 
         commit_response_clone = commit_response.clone();
         mock_receiver
             .expect_recv()
             .withf(move |_| true)
             .times(1)
-            .returning(move |_| Ok((commit_response_clone.clone(), bank_addr.to_string())));
+            .returning(move |_| Ok((commit_response_clone.clone(), bank_addr)));
 
-        log_msg_clone = log_msg.clone();
-        replicas_addrs_clone = replicas_addrs.clone();
+        let log_msg_clone = log_msg.clone();
+        let replicas_addrs_clone = replicas_addrs.clone();
         mock_sender
             .expect_send_to()
             .withf(move |buf, addr| {
-                &buf.to_vec() == &log_msg_clone && replicas_addrs_clone.contains(&addr.to_string())
+                buf.to_vec() == log_msg_clone && replicas_addrs_clone.contains(addr)
             })
             .times(n_services)
             .returning(|_, _| Ok(()));
@@ -954,7 +1399,7 @@ mod tests {
 
         let mut receiver = TransactionReceiver::new(
             Box::new(mock_receiver),
-            &services_addrs_str,
+            services_addrs_str,
             curr_transaction.clone(),
             Arc::new((Mutex::new(false), Condvar::new())),
         );
@@ -964,17 +1409,294 @@ mod tests {
         });
 
         let mut manager = TransactionManager::new(
-            id,
+            my_addr,
             Box::new(mock_sender),
             curr_transaction.clone(),
             Arc::new((Mutex::new(false), Condvar::new())),
-            &services_addrs_str,
+            services_addrs_str,
             &replicas_addrs,
             Duration::from_secs(1),
+            Duration::from_secs(0),
+            Duration::from_secs(0),
             None,
         );
 
         manager.update_current(transaction);
         manager.commit();
     }
+
+    fn decisions(offset: u64, outcomes: &[(u64, bool)]) -> Decisions {
+        let mut decisions = Decisions::new();
+        for (id, commited) in outcomes {
+            decisions.insert(*id, *commited, offset);
+        }
+        decisions
+    }
+
+    #[test]
+    fn it_should_send_offset_and_decisions_to_successor_in_one_message_on_hand_off() {
+        let successor_addr: SocketAddr = "127.0.0.1:49153".parse().unwrap();
+        let services_addrs_str = &HashMap::from([
+            (
+                "127.0.0.1:49156".parse().unwrap(),
+                ServiceName::Airline.string_name(),
+            ),
+            (
+                "127.0.0.1:49157".parse().unwrap(),
+                ServiceName::Hotel.string_name(),
+            ),
+            (
+                "127.0.0.1:49158".parse().unwrap(),
+                ServiceName::Bank.string_name(),
+            ),
+        ]);
+
+        let mut mock_sender = MockUdpSocketSender::new();
+        mock_sender
+            .expect_send_to()
+            .withf(move |buf, addr| {
+                TransactionHandoff::parse(buf)
+                    == Some((28, decisions(28, &[(0, true), (1, false)])))
+                    && *addr == successor_addr
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let mut manager = TransactionManager::new(
+            "127.0.0.1:49152".parse().unwrap(),
+            Box::new(mock_sender),
+            Arc::new((Mutex::new(None), Condvar::new())),
+            Arc::new((Mutex::new(false), Condvar::new())),
+            services_addrs_str,
+            &[],
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+            None,
+        );
+
+        manager.resume_from(28, decisions(28, &[(0, true), (1, false)]));
+        manager.hand_off(&successor_addr);
+    }
+
+    #[test]
+    fn it_should_not_panic_if_the_hand_off_cannot_be_sent() {
+        let successor_addr: SocketAddr = "127.0.0.1:49153".parse().unwrap();
+        let mut mock_sender = MockUdpSocketSender::new();
+        mock_sender
+            .expect_send_to()
+            .times(1)
+            .returning(|_, _| Err(SocketError::Truncated));
+
+        let mut manager = TransactionManager::new(
+            "127.0.0.1:49152".parse().unwrap(),
+            Box::new(mock_sender),
+            Arc::new((Mutex::new(None), Condvar::new())),
+            Arc::new((Mutex::new(false), Condvar::new())),
+            &HashMap::new(),
+            &[],
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+            None,
+        );
+
+        manager.resume_from(28, decisions(28, &[(0, true)]));
+        manager.hand_off(&successor_addr);
+    }
+
+    #[test]
+    fn it_should_replicate_the_checkpoint_to_the_other_replicas() {
+        let my_addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let replica_addr: SocketAddr = "127.0.0.1:49153".parse().unwrap();
+
+        let mut checkpoint_msg = TransactionCheckpoint::build(5, false, 28);
+        TransactionInfo::add_padding(&mut checkpoint_msg);
+
+        let mut mock_sender = MockUdpSocketSender::new();
+        mock_sender
+            .expect_send_to()
+            .withf(move |buf, addr| buf.to_vec() == checkpoint_msg && *addr == replica_addr)
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let mut manager = TransactionManager::new(
+            my_addr,
+            Box::new(mock_sender),
+            Arc::new((Mutex::new(None), Condvar::new())),
+            Arc::new((Mutex::new(false), Condvar::new())),
+            &HashMap::new(),
+            &[my_addr, replica_addr],
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+            None,
+        );
+
+        manager.resume_from(28, Decisions::new());
+        manager.send_checkpoint(5, false);
+    }
+
+    #[test]
+    fn it_should_answer_a_queued_retry_of_a_commited_transaction_without_running_it() {
+        let my_addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let replica_addr: SocketAddr = "127.0.0.1:49153".parse().unwrap();
+        let client: SocketAddr = "127.0.0.1:49160".parse().unwrap();
+        let ended = Arc::new((Mutex::new(false), Condvar::new()));
+        let retries = RetryQueue::new(ended.clone());
+        retries.push(5, HashMap::new(), client).unwrap();
+
+        let mut done_msg = TransactionRetryQueue::build_done(0);
+        TransactionInfo::add_padding(&mut done_msg);
+        let mut commited_msg = TransactionRetryAck::build(5, RetryReply::Commited);
+        TransactionInfo::add_padding(&mut commited_msg);
+
+        let mut mock_sender = MockUdpSocketSender::new();
+        let mut seq = mockall::Sequence::new();
+        mock_sender
+            .expect_send_to()
+            .withf(move |buf, addr| buf.to_vec() == done_msg && *addr == replica_addr)
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _| Ok(()));
+        mock_sender
+            .expect_send_to()
+            .withf(move |buf, addr| buf.to_vec() == commited_msg && *addr == client)
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _| Ok(()));
+
+        let mut manager = TransactionManager::new(
+            my_addr,
+            Box::new(mock_sender),
+            Arc::new((Mutex::new(None), Condvar::new())),
+            ended,
+            &HashMap::new(),
+            &[my_addr, replica_addr],
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+            None,
+        );
+        manager.resume_from(0, decisions(0, &[(5, true)]));
+        manager.accept_retries(retries.clone());
+
+        assert!(manager.process_queued_retry());
+        assert!(retries.is_empty());
+        assert!(!manager.process_queued_retry());
+    }
+
+    #[test]
+    fn it_should_not_run_a_queued_retry_commited_before_the_offset_advanced() {
+        let my_addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let client: SocketAddr = "127.0.0.1:49160".parse().unwrap();
+        let ended = Arc::new((Mutex::new(false), Condvar::new()));
+        let retries = RetryQueue::new(ended.clone());
+        // Replicado por un lider anterior, antes de que se commiteara
+        retries.replicate(QueuedRetry {
+            seq: 0,
+            id: 5,
+            fees: HashMap::new(),
+            client_opt: Some(client),
+        });
+        retries.commited(5);
+
+        let mut commited_msg = TransactionRetryAck::build(5, RetryReply::Commited);
+        TransactionInfo::add_padding(&mut commited_msg);
+        let mut mock_sender = MockUdpSocketSender::new();
+        mock_sender
+            .expect_send_to()
+            .withf(move |buf, addr| buf.to_vec() == commited_msg && *addr == client)
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let mut manager = TransactionManager::new(
+            my_addr,
+            Box::new(mock_sender),
+            Arc::new((Mutex::new(None), Condvar::new())),
+            ended,
+            &HashMap::new(),
+            &[my_addr],
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+            None,
+        );
+        // La decision de la fila 5 se descarto al avanzar la lectura
+        manager.resume_from(40, decisions(40, &[(9, true)]));
+        manager.accept_retries(retries.clone());
+
+        assert!(manager.process_queued_retry());
+        assert!(retries.is_empty());
+        assert!(!manager.progress().1.contains(RETRY_ID_BASE));
+    }
+
+    #[test]
+    fn it_should_run_a_submission_with_a_new_id_replicated_before_preparing() {
+        let my_addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let replica_addr: SocketAddr = "127.0.0.1:49153".parse().unwrap();
+        let ended = Arc::new((Mutex::new(false), Condvar::new()));
+        let submissions = SubmitQueue::new(ended.clone());
+        submissions.open();
+
+        let submit_id = SUBMIT_ID_BASE + 8;
+        let mut mock_sender = MockUdpSocketSender::new();
+        let mut seq = mockall::Sequence::new();
+        mock_sender
+            .expect_send_to()
+            .withf(move |buf, addr| {
+                buf[0] == LOG_BYTE
+                    && TransactionLog::new_transaction(buf).get_id() == submit_id
+                    && *addr == replica_addr
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _| Ok(()));
+        // Los mensajes a los servicios y el resto de los logs
+        mock_sender.expect_send_to().returning(|_, _| Ok(()));
+        let services_addrs = HashMap::from([
+            (
+                "127.0.0.1:49156".parse().unwrap(),
+                ServiceName::Airline.string_name(),
+            ),
+            (
+                "127.0.0.1:49157".parse().unwrap(),
+                ServiceName::Hotel.string_name(),
+            ),
+            (
+                "127.0.0.1:49158".parse().unwrap(),
+                ServiceName::Bank.string_name(),
+            ),
+        ]);
+
+        let mut manager = TransactionManager::new(
+            my_addr,
+            Box::new(mock_sender),
+            Arc::new((Mutex::new(None), Condvar::new())),
+            ended,
+            &services_addrs,
+            &[my_addr, replica_addr],
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+            None,
+        );
+        // Un lider anterior ya ejecuto la reserva 7 con ese id
+        manager.resume_from(0, decisions(0, &[(SUBMIT_ID_BASE + 7, false)]));
+        manager.accept_submissions(submissions.clone());
+
+        let fees = HashMap::from([
+            (ServiceName::Airline.string_name(), 100.0),
+            (ServiceName::Hotel.string_name(), 200.0),
+            (ServiceName::Bank.string_name(), 300.0),
+        ]);
+        let reply = submissions.push(Transaction::new(7, &fees)).unwrap();
+        assert!(manager.process_submission());
+        // Sin respuesta de los servicios la reserva se aborta
+        assert_eq!(reply.recv(), Ok(false));
+        let (_, decisions) = manager.progress();
+        assert_eq!(decisions.get(SUBMIT_ID_BASE + 7), Some(false));
+        assert_eq!(decisions.get(submit_id), Some(false));
+        assert_eq!(decisions.next_submit_id(), submit_id + 1);
+    }
 }
@@ -1,46 +1,124 @@
+use std::cmp::max;
 use std::collections::HashMap;
-use std::sync::{Arc, Condvar, Mutex};
+use std::net::SocketAddr;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use tracing::{debug, info, warn};
 
 use crate::alglobo::transaction_error::TransactionError;
+use crate::metrics::registry::metrics;
 use crate::sockets::socket_error::SocketError;
 use crate::sockets::udp_socket_receiver::UdpSocketReceiver;
+use crate::sockets::udp_socket_sender::UdpSocketSender;
+use crate::transaction_messages::transaction_checkpoint::TransactionCheckpoint;
 use crate::transaction_messages::transaction_code::TransactionCode;
+use crate::transaction_messages::transaction_handoff::TransactionHandoff;
 use crate::transaction_messages::transaction_info::TransactionInfo;
 use crate::transaction_messages::transaction_log::TransactionLog;
 use crate::transaction_messages::transaction_response::TransactionResponse;
 use crate::transaction_messages::transaction_retry::TransactionRetry;
-use crate::transaction_messages::types::{LOG_BYTE, RESPONSE_BYTE, RETRY_BYTE};
-
+use crate::transaction_messages::transaction_retry_ack::{
+    DenyReason, RetryReply, TransactionRetryAck,
+};
+use crate::transaction_messages::transaction_retry_queue::TransactionRetryQueue;
+use crate::transaction_messages::types::{
+    CHECKPOINT_BYTE, HANDOFF_BYTE, LOG_BYTE, RESPONSE_BYTE, RETRY_BYTE, RETRY_QUEUE_BYTE,
+};
+
+use super::decisions::Decisions;
+use super::retry_queue::{RetryQueue, RETRY_ID_BASE};
 use super::transactionable::Transactionable;
-use super::types::CurrentTransaction;
+use super::types::{CurrentTransaction, HandOff, LastDecided, Leading, Progress};
 
 pub struct TransactionReceiver {
     udp_receiver: Box<dyn UdpSocketReceiver + Send>,
-    services_addrs: HashMap<String, String>,
+    services_addrs: HashMap<SocketAddr, String>,
     curr_transaction: CurrentTransaction,
-    ended: Arc<(Mutex<bool>, Condvar)>,
+    handoff: HandOff,
+    progress: Progress,
+    last_decided: LastDecided,
+    leading: Leading,
+    retries: RetryQueue,
+    replicas_addrs: Vec<SocketAddr>,
+    udp_sender_opt: Option<Box<dyn UdpSocketSender + Send>>,
 }
 
 impl TransactionReceiver {
     #[must_use]
     pub fn new(
         udp_receiver: Box<dyn UdpSocketReceiver + Send>,
-        services_addrs_str: &HashMap<&str, String>,
+        services_addrs: &HashMap<SocketAddr, String>,
         curr_transaction: CurrentTransaction,
         ended: Arc<(Mutex<bool>, Condvar)>,
     ) -> Self {
-        let services_addrs = services_addrs_str
-            .iter()
-            .map(|(addr, name)| ((*addr).to_string(), name.clone()))
-            .collect();
         TransactionReceiver {
             udp_receiver,
-            services_addrs,
+            services_addrs: services_addrs.clone(),
             curr_transaction,
-            ended,
+            handoff: Arc::new(Mutex::new(None)),
+            progress: Arc::new(Mutex::new((0, Decisions::new()))),
+            last_decided: Arc::new(Mutex::new(None)),
+            leading: Arc::new(RwLock::new(false)),
+            retries: RetryQueue::new(ended),
+            replicas_addrs: Vec::new(),
+            udp_sender_opt: None,
         }
     }
 
+    /// Responde los reintentos por `udp_sender`: si se conceden o se
+    /// deniegan y por que
+    #[must_use]
+    pub fn with_replies(mut self, udp_sender: Box<dyn UdpSocketSender + Send>) -> Self {
+        self.udp_sender_opt = Some(udp_sender);
+        self
+    }
+
+    /// Replica en los demas nodos los reintentos que encola como lider.
+    /// Requiere `with_replies`.
+    #[must_use]
+    pub fn with_replicas(mut self, my_addr: SocketAddr, replicas_addrs: &[SocketAddr]) -> Self {
+        self.replicas_addrs = replicas_addrs
+            .iter()
+            .filter(|addr| **addr != my_addr)
+            .copied()
+            .collect();
+        self
+    }
+
+    /// Devuelve el indicador compartido de liderazgo: solo el lider
+    /// concede reintentos
+    #[must_use]
+    pub fn leading(&self) -> Leading {
+        self.leading.clone()
+    }
+
+    /// Devuelve la cola de reintentos compartida: la que llena el nodo
+    /// como lider o la que le replica el lider
+    #[must_use]
+    pub fn retries(&self) -> RetryQueue {
+        self.retries.clone()
+    }
+
+    /// Devuelve el traspaso de liderazgo compartido, que
+    /// contiene un valor una vez recibido el mensaje de traspaso
+    #[must_use]
+    pub fn handoff(&self) -> HandOff {
+        self.handoff.clone()
+    }
+
+    /// Devuelve el progreso replicado por el lider, con el que este
+    /// nodo retoma la lectura si es elegido lider tras una caida
+    #[must_use]
+    pub fn progress(&self) -> Progress {
+        self.progress.clone()
+    }
+
+    /// Devuelve el id de la ultima transaccion decidida, que replica
+    /// el lider o registra el propio nodo como lider
+    #[must_use]
+    pub fn last_decided(&self) -> LastDecided {
+        self.last_decided.clone()
+    }
+
     /// # Errors
     ///
     /// `TransactionError::WrongId` => La respuesta
@@ -50,17 +128,29 @@ impl TransactionReceiver {
     pub fn process_response(
         &mut self,
         response: &[u8],
-        addr: &str,
+        addr: &SocketAddr,
     ) -> Result<(), TransactionError> {
-        let (transaction_code, transaction_id) = TransactionResponse::parse(response);
-        let service_name = self
-            .services_addrs
-            .get(addr)
-            .expect("[Transaction Receiver] Direccion de servicio web desconocida");
-        println!(
-            "[Transaction Receiver] Id-Transaccion: {}, Operacion: {}, Entidad: {}",
-            transaction_id, transaction_code, service_name
+        let (code_opt, transaction_id) = TransactionResponse::try_parse(response);
+        let code = code_opt.map_or_else(|| "INVALIDO".to_string(), |code| code.to_string());
+        let service_name = match self.services_addrs.get(addr) {
+            Some(value) => value,
+            None => {
+                warn!(from = %addr, "Respuesta de una direccion que no es un servicio descartada");
+                return Ok(());
+            }
+        };
+        let trace_opt = TransactionResponse::parse_trace(response);
+        info!(
+            transaction_id,
+            service = %service_name,
+            code = %code,
+            trace_id = %trace_opt.map(|trace| trace.trace_id_hex()).unwrap_or_default(),
+            span_id = %trace_opt.map(|trace| trace.span_id_hex()).unwrap_or_default(),
+            "Respuesta de servicio recibida"
         );
+        metrics()
+            .service_responses
+            .inc(&[("service", service_name), ("code", &code)]);
 
         let mut opt_transaccion = self
             .curr_transaction
@@ -76,18 +166,23 @@ impl TransactionReceiver {
             return Err(TransactionError::WrongId);
         }
 
-        match transaction_code {
-            TransactionCode::Accept => {
+        match code_opt {
+            Some(TransactionCode::Accept) => {
                 transaction.accept(service_name.to_string(), None);
             }
-            TransactionCode::Abort => {
+            Some(TransactionCode::Abort) => {
                 transaction.abort(service_name.to_string(), None);
             }
-            TransactionCode::Commit => {
+            Some(TransactionCode::Commit) => {
                 transaction.commit(service_name.to_string(), None);
             }
-            TransactionCode::Prepare => {
-                println!("Codigo de transaccion no esperado: {}", transaction_code);
+            Some(TransactionCode::Prepare) => {
+                warn!(transaction_id, code = %code, "Codigo de transaccion no esperado");
+            }
+            // Una respuesta ilegible cuenta como voto de abortar
+            None => {
+                warn!(transaction_id, service = %service_name, "Respuesta de servicio invalida");
+                transaction.reject(service_name.to_string());
             }
         }
         self.curr_transaction.1.notify_all();
@@ -102,52 +197,143 @@ impl TransactionReceiver {
             .expect("[Transaction Receiver] Lock de transaccion envenenado");
         let new_transaction = TransactionLog::new_transaction(message);
         let repr = new_transaction.representation(true);
-        println!("[Transaction Receiver] Actualizacion: {}", repr);
+        debug!(
+            transaction_id = new_transaction.get_id(),
+            transaction = %repr,
+            "Log de transaccion recibido"
+        );
         *opt_transaccion = Some(Box::new(new_transaction));
         self.curr_transaction.1.notify_all();
     }
 
-    #[allow(clippy::mutex_atomic)]
-    fn process_retry(&mut self, message: &[u8]) {
+    fn reply_retry(&mut self, id: u64, reply: RetryReply, addr: &SocketAddr) {
+        if let Some(udp_sender) = &mut self.udp_sender_opt {
+            let mut message = TransactionRetryAck::build(id, reply);
+            TransactionInfo::add_padding(&mut message);
+            if let Err(err) = udp_sender.send_to(&message, addr) {
+                warn!(transaction_id = id, client = %addr, error = %err, "No se pudo responder el reintento");
+            }
+        }
+    }
+
+    fn send_to_replicas(&mut self, mut message: Vec<u8>) {
+        TransactionInfo::add_padding(&mut message);
+        if let Some(udp_sender) = &mut self.udp_sender_opt {
+            for addr in &self.replicas_addrs {
+                if let Err(err) = udp_sender.send_to(&message, addr) {
+                    warn!(replica = %addr, error = %err, "No se pudo replicar la cola de reintentos");
+                }
+            }
+        }
+    }
+
+    /// El lider encola el reintento, aunque este procesando otra
+    /// transaccion, y lo replica antes de concederlo
+    fn process_retry(&mut self, message: &[u8], addr: &SocketAddr) {
         let new_transaction = TransactionRetry::new_transaction(message);
         let repr = new_transaction.representation(false);
-
-        let mut ended = self
-            .ended
-            .0
-            .lock()
-            .expect("[Transaction Receiver] Lock de finilizacion envenenado");
-        if !*ended {
-            println!(
-                "[Transaction Receiver] Reintento {} DENEGADO: otra transaccion en ejecucion",
-                repr
-            );
+        let id = new_transaction.get_id();
+
+        let leading = *self
+            .leading
+            .read()
+            .expect("[Transaction Receiver] Lock de liderazgo envenenado");
+        if !leading {
+            debug!(transaction = %repr, "Reintento recibido por un nodo que no es lider");
+            self.reply_retry(id, RetryReply::Denied(DenyReason::NotLeader), addr);
             return;
         }
 
-        let mut opt_transaction = self
-            .curr_transaction
-            .0
-            .lock()
-            .expect("[Transaction Manager] Lock de transaccion envenenado");
-
-        if let Some(transaction) = opt_transaction.as_ref() {
-            let curr_id = transaction.get_id();
-            let new_id = new_transaction.get_id();
-            if curr_id >= new_id {
-                println!(
-                    "[Transaction Receiver] Reintento {} DENEGADO: ID bajo",
-                    repr
-                );
+        let retry = match self.retries.push(id, new_transaction.all_services(), *addr) {
+            Ok(value) => value,
+            Err(reason) => {
+                warn!(transaction = %repr, reason = %reason, "Reintento denegado");
+                metrics().retries.inc(&[("result", "denied")]);
+                self.reply_retry(id, RetryReply::Denied(reason), addr);
                 return;
             }
+        };
+        info!(
+            transaction = %repr,
+            seq = retry.seq,
+            pending = self.retries.len(),
+            "Reintento encolado"
+        );
+        metrics().retries.inc(&[("result", "granted")]);
+        self.send_to_replicas(TransactionRetryQueue::build_enqueue(&retry));
+        self.reply_retry(id, RetryReply::Granted, addr);
+    }
+
+    fn process_retry_queue(&mut self, message: &[u8]) {
+        if TransactionRetryQueue::is_done(message) {
+            if let Some(seq) = TransactionRetryQueue::parse_done(message) {
+                debug!(seq, "Reintento terminado por el lider");
+                self.retries.done(seq);
+            } else {
+                warn!("Mensaje de la cola de reintentos invalido descartado");
+            }
+            return;
         }
+        let retry = match TransactionRetryQueue::parse_enqueue(message) {
+            Some(value) => value,
+            None => {
+                warn!("Mensaje de la cola de reintentos invalido descartado");
+                return;
+            }
+        };
+        debug!(
+            transaction_id = retry.id,
+            seq = retry.seq,
+            "Reintento encolado por el lider"
+        );
+        self.retries.replicate(retry);
+    }
 
-        println!("[Transaction Receiver] Reintento {} CONCEDIDO", repr);
-        *opt_transaction = Some(Box::new(new_transaction));
+    fn process_handoff(&mut self, message: &[u8]) {
+        let (offset, decisions) = match TransactionHandoff::parse(message) {
+            Some(value) => value,
+            None => {
+                warn!(len = message.len(), "Traspaso invalido descartado");
+                return;
+            }
+        };
+        info!(
+            offset,
+            decisions = decisions.len(),
+            "Traspaso de liderazgo recibido"
+        );
+        let mut handoff = self
+            .handoff
+            .lock()
+            .expect("[Transaction Receiver] Lock de traspaso envenenado");
+        *handoff = Some((offset, decisions));
+    }
 
-        *ended = false;
-        self.ended.1.notify_all();
+    fn process_checkpoint(&mut self, message: &[u8]) {
+        let (id, commited, offset) = match TransactionCheckpoint::parse(message) {
+            Some(value) => value,
+            None => {
+                warn!("Progreso del lider invalido descartado");
+                return;
+            }
+        };
+        debug!(
+            transaction_id = id,
+            commited, offset, "Progreso del lider recibido"
+        );
+        let mut progress = self
+            .progress
+            .lock()
+            .expect("[Transaction Receiver] Lock de progreso envenenado");
+        progress.0 = offset;
+        progress.1.insert(id, commited, offset);
+        if commited && id < RETRY_ID_BASE {
+            self.retries.commited(id);
+        }
+        *self
+            .last_decided
+            .lock()
+            .expect("[Transaction Receiver] Lock de ultima decision envenenado") = Some(id);
     }
 
     /// # Errors
@@ -158,26 +344,45 @@ impl TransactionReceiver {
     /// `TransactionError::WrongId` => La transaccion
     /// recibida no es la transaccion siendo procesada
     ///
-    /// # Panics
-    ///
-    /// Esta funcion paniquea si
-    /// - Ocurrio un error irreversible en canal de lectura
+    /// Los datagramas que no se pudieron recibir (demasiado largos,
+    /// fragmentos mal formados, firmas invalidas) y los mensajes de tipo
+    /// desconocido o incompletos se registran y se descartan.
     pub fn recv(&mut self) -> Result<(), TransactionError> {
-        let result = self.udp_receiver.recv(TransactionInfo::size());
+        // El traspaso es el unico mensaje que puede superar el largo
+        // comun, segun la cantidad de decisiones
+        let result = self
+            .udp_receiver
+            .recv(max(TransactionInfo::size(), TransactionHandoff::max_size()));
         let (message, addr) = match result {
             Ok(value) => value,
-            Err(err) => match err {
-                SocketError::Timeout => return Ok(()),
-                _ => panic!("{}", err),
-            },
+            Err(SocketError::Timeout) => return Ok(()),
+            Err(err) => {
+                warn!(error = %err, "Datagrama descartado");
+                return Ok(());
+            }
         };
-        let info_type = message[0];
         let mut res: Result<(), TransactionError> = Ok(());
-        match info_type {
-            RESPONSE_BYTE => res = self.process_response(&message, &addr),
-            LOG_BYTE => self.process_log(&message),
-            RETRY_BYTE => self.process_retry(&message),
-            _ => panic!("Byte de informacion desconocido"),
+        match message.first().copied() {
+            Some(RESPONSE_BYTE) if message.len() >= TransactionResponse::size() => {
+                res = self.process_response(&message, &addr);
+            }
+            Some(LOG_BYTE) if message.len() >= TransactionLog::size() => {
+                self.process_log(&message);
+            }
+            Some(RETRY_BYTE) if message.len() >= TransactionRetry::size() => {
+                self.process_retry(&message, &addr);
+            }
+            Some(HANDOFF_BYTE) => self.process_handoff(&message),
+            Some(CHECKPOINT_BYTE) => self.process_checkpoint(&message),
+            Some(RETRY_QUEUE_BYTE) => self.process_retry_queue(&message),
+            info_type => {
+                warn!(
+                    from = %addr,
+                    info_type = ?info_type,
+                    len = message.len(),
+                    "Mensaje desconocido o incompleto descartado"
+                );
+            }
         };
         res
     }
@@ -188,24 +393,36 @@ mod tests {
     use super::*;
 
     use crate::{
-        alglobo::{transaction_state::TransactionState, transactionable::MockTransactionable},
+        alglobo::{
+            decisions::DECISIONS_CAPACITY, retry_queue::QueuedRetry,
+            transaction_state::TransactionState, transactionable::MockTransactionable,
+        },
         services::service_name::ServiceName,
-        sockets::udp_socket_receiver::MockUdpSocketReceiver,
+        sockets::{
+            constants::UDP_PACKET_SIZE, udp_socket_receiver::MockUdpSocketReceiver,
+            udp_socket_sender::MockUdpSocketSender, udp_socket_wrap::UdpSocketWrap,
+        },
         transaction_messages::{
-            transaction_code::TransactionCode, transaction_info::TransactionInfo,
+            transaction_checkpoint::TransactionCheckpoint, transaction_code::TransactionCode,
+            transaction_handoff::TransactionHandoff, transaction_info::TransactionInfo,
             transaction_log::TransactionLog, transaction_response::TransactionResponse,
-            transaction_retry::TransactionRetry,
+            transaction_retry::TransactionRetry, transaction_retry_queue::TransactionRetryQueue,
         },
     };
 
+    use ntest::timeout;
     use std::{
         collections::HashMap,
+        net::{SocketAddr, UdpSocket},
         sync::{Arc, Condvar, Mutex},
     };
 
     #[test]
     fn it_should_change_transaction_service_state_to_accepted_when_recv_accept_from_it() {
-        let airline = ("127.0.0.1:49156", ServiceName::Airline.string_name());
+        let airline = (
+            "127.0.0.1:49156".parse::<SocketAddr>().unwrap(),
+            ServiceName::Airline.string_name(),
+        );
         let mut airline_clone;
 
         airline_clone = airline.clone();
@@ -220,9 +437,9 @@ mod tests {
         let response_len = response.len();
         mock_socket
             .expect_recv()
-            .withf(move |n_bytes| n_bytes == &response_len)
+            .withf(move |n_bytes| n_bytes >= &response_len)
             .times(1)
-            .returning(move |_| Ok((response.clone(), airline_clone.0.to_string())));
+            .returning(move |_| Ok((response.clone(), airline_clone.0)));
 
         let mut mock_transaction = MockTransactionable::new();
         mock_transaction
@@ -245,9 +462,53 @@ mod tests {
         assert!(receiver.recv().is_ok());
     }
 
+    #[test]
+    fn it_should_reject_the_service_when_its_response_cannot_be_decoded() {
+        let airline = (
+            "127.0.0.1:49156".parse::<SocketAddr>().unwrap(),
+            ServiceName::Airline.string_name(),
+        );
+        let services_addrs = HashMap::from([airline.clone()]);
+
+        let transaction_id = 0;
+        let mut response = TransactionResponse::build(TransactionCode::Accept, transaction_id);
+        response[1] = b'?';
+        TransactionInfo::add_padding(&mut response);
+
+        let mut mock_socket = MockUdpSocketReceiver::new();
+        let airline_addr = airline.0;
+        mock_socket
+            .expect_recv()
+            .times(1)
+            .returning(move |_| Ok((response.clone(), airline_addr)));
+
+        let mut mock_transaction = MockTransactionable::new();
+        mock_transaction
+            .expect_get_id()
+            .times(1)
+            .returning(move || transaction_id);
+        mock_transaction
+            .expect_reject()
+            .withf(move |name| name == &airline.1)
+            .times(1)
+            .returning(|_| true);
+
+        let mut receiver = TransactionReceiver::new(
+            Box::new(mock_socket),
+            &services_addrs,
+            Arc::new((Mutex::new(Some(Box::new(mock_transaction))), Condvar::new())),
+            Arc::new((Mutex::new(false), Condvar::new())),
+        );
+
+        assert!(receiver.recv().is_ok());
+    }
+
     #[test]
     fn it_should_change_transaction_service_state_to_commited_when_recv_commit_from_it() {
-        let airline = ("127.0.0.1:49156", ServiceName::Airline.string_name());
+        let airline = (
+            "127.0.0.1:49156".parse::<SocketAddr>().unwrap(),
+            ServiceName::Airline.string_name(),
+        );
         let mut airline_clone;
 
         airline_clone = airline.clone();
@@ -262,9 +523,9 @@ mod tests {
         let response_len = response.len();
         mock_socket
             .expect_recv()
-            .withf(move |n_bytes| n_bytes == &response_len)
+            .withf(move |n_bytes| n_bytes >= &response_len)
             .times(1)
-            .returning(move |_| Ok((response.clone(), airline_clone.0.to_string())));
+            .returning(move |_| Ok((response.clone(), airline_clone.0)));
 
         let mut mock_transaction = MockTransactionable::new();
         mock_transaction
@@ -290,9 +551,18 @@ mod tests {
     #[test]
     fn it_should_update_the_whole_transaction_when_recv_log_message() {
         let services_addrs = HashMap::from([
-            ("127.0.0.1:49156", ServiceName::Airline.string_name()),
-            ("127.0.0.1:49157", ServiceName::Hotel.string_name()),
-            ("127.0.0.1:49158", ServiceName::Bank.string_name()),
+            (
+                "127.0.0.1:49156".parse::<SocketAddr>().unwrap(),
+                ServiceName::Airline.string_name(),
+            ),
+            (
+                "127.0.0.1:49157".parse::<SocketAddr>().unwrap(),
+                ServiceName::Hotel.string_name(),
+            ),
+            (
+                "127.0.0.1:49158".parse::<SocketAddr>().unwrap(),
+                ServiceName::Bank.string_name(),
+            ),
         ]);
 
         let transaction_id = 0;
@@ -308,9 +578,9 @@ mod tests {
         let msg_len = message.len();
         mock_socket
             .expect_recv()
-            .withf(move |n_bytes| n_bytes == &msg_len)
+            .withf(move |n_bytes| n_bytes >= &msg_len)
             .times(1)
-            .returning(move |_| Ok((message.clone(), "".to_string())));
+            .returning(move |_| Ok((message.clone(), "127.0.0.1:49152".parse().unwrap())));
 
         let curr_transaction: CurrentTransaction = Arc::new((Mutex::new(None), Condvar::new()));
         let mut receiver = TransactionReceiver::new(
@@ -343,169 +613,383 @@ mod tests {
         );
     }
 
+    fn retry_message(transaction_id: u64) -> Vec<u8> {
+        let mut message = TransactionRetry::build(transaction_id, 100.0, 200.0, 300.0);
+        TransactionInfo::add_padding(&mut message);
+        message
+    }
+
     #[test]
-    fn it_should_update_current_transaction_and_set_ended_false_if_retry_msg_and_ended() {
-        let services_addrs = HashMap::from([
-            ("127.0.0.1:49156", ServiceName::Airline.string_name()),
-            ("127.0.0.1:49157", ServiceName::Hotel.string_name()),
-            ("127.0.0.1:49158", ServiceName::Bank.string_name()),
-        ]);
+    fn it_should_queue_and_replicate_the_retry_while_a_transaction_runs() {
+        let client: SocketAddr = "127.0.0.1:49160".parse().unwrap();
+        let my_addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let replica: SocketAddr = "127.0.0.1:49153".parse().unwrap();
+        let transaction_id = 7;
 
-        let transaction_id = 0;
-        let services_info_vec = [
-            (ServiceName::Airline.string_name(), 100.0),
-            (ServiceName::Hotel.string_name(), 200.0),
-            (ServiceName::Bank.string_name(), 300.0),
-        ];
+        let mut mock_socket = MockUdpSocketReceiver::new();
+        let message = retry_message(transaction_id);
+        mock_socket
+            .expect_recv()
+            .times(1)
+            .returning(move |_| Ok((message.clone(), client)));
+
+        let mut granted = TransactionRetryAck::build(transaction_id, RetryReply::Granted);
+        TransactionInfo::add_padding(&mut granted);
+        let mut mock_sender = MockUdpSocketSender::new();
+        let mut seq = mockall::Sequence::new();
+        mock_sender
+            .expect_send_to()
+            .withf(move |buf, addr| {
+                TransactionRetryQueue::parse_enqueue(buf)
+                    .is_some_and(|retry| retry.id == transaction_id)
+                    && *addr == replica
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _| Ok(()));
+        mock_sender
+            .expect_send_to()
+            .withf(move |buf, addr| buf.to_vec() == granted && *addr == client)
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _| Ok(()));
+
+        // El manager esta procesando otra transaccion
+        let curr_transaction: CurrentTransaction = Arc::new((
+            Mutex::new(Some(Box::new(MockTransactionable::new()))),
+            Condvar::new(),
+        ));
+        let mut receiver = TransactionReceiver::new(
+            Box::new(mock_socket),
+            &HashMap::new(),
+            curr_transaction,
+            Arc::new((Mutex::new(false), Condvar::new())),
+        )
+        .with_replies(Box::new(mock_sender))
+        .with_replicas(my_addr, &[my_addr, replica]);
 
-        let mut message = TransactionRetry::build(
-            transaction_id,
-            services_info_vec[0].1,
-            services_info_vec[1].1,
-            services_info_vec[2].1,
+        *receiver.leading().write().unwrap() = true;
+        assert!(receiver.recv().is_ok());
+        let retry = receiver.retries().front().unwrap();
+        assert_eq!(
+            (retry.seq, retry.id, retry.client_opt),
+            (0, 7, Some(client))
         );
-        TransactionInfo::add_padding(&mut message);
+        assert_eq!(
+            retry.fees,
+            HashMap::from([
+                (ServiceName::Airline.string_name(), 100.0),
+                (ServiceName::Hotel.string_name(), 200.0),
+                (ServiceName::Bank.string_name(), 300.0),
+            ])
+        );
+    }
 
-        let mut mock_socket = MockUdpSocketReceiver::new();
+    #[test]
+    fn it_should_deny_the_retry_if_not_leading() {
+        let client: SocketAddr = "127.0.0.1:49160".parse().unwrap();
+        let transaction_id = 7;
 
-        let msg_len = message.len();
+        let mut mock_socket = MockUdpSocketReceiver::new();
+        let message = retry_message(transaction_id);
         mock_socket
             .expect_recv()
-            .withf(move |n_bytes| n_bytes == &msg_len)
             .times(1)
-            .returning(move |_| Ok((message.clone(), "".to_string())));
-
-        let curr_transaction = Arc::new((Mutex::new(None), Condvar::new()));
-        let curr_transaction_clone = curr_transaction.clone();
-
-        let ended = Arc::new((Mutex::new(true), Condvar::new()));
-        let ended_clone = ended.clone();
+            .returning(move |_| Ok((message.clone(), client)));
+
+        let mut denied =
+            TransactionRetryAck::build(transaction_id, RetryReply::Denied(DenyReason::NotLeader));
+        TransactionInfo::add_padding(&mut denied);
+        let mut mock_sender = MockUdpSocketSender::new();
+        mock_sender
+            .expect_send_to()
+            .withf(move |buf, addr| buf.to_vec() == denied && *addr == client)
+            .times(1)
+            .returning(|_, _| Ok(()));
 
         let mut receiver = TransactionReceiver::new(
             Box::new(mock_socket),
-            &services_addrs,
-            curr_transaction_clone,
-            ended_clone,
-        );
+            &HashMap::new(),
+            Arc::new((Mutex::new(None), Condvar::new())),
+            Arc::new((Mutex::new(true), Condvar::new())),
+        )
+        .with_replies(Box::new(mock_sender));
 
         assert!(receiver.recv().is_ok());
-        let opt_transaction = curr_transaction.0.lock().unwrap();
-        assert!(!opt_transaction.is_none());
-        let transaction = opt_transaction.as_ref().unwrap();
-        let services_info = transaction.all_services();
-        assert_eq!(services_info, HashMap::from(services_info_vec));
-        assert!(!*ended.0.lock().unwrap());
+        assert!(receiver.retries().is_empty());
     }
 
     #[test]
-    fn it_should_ignore_transaction_retry_if_not_ended() {
-        let services_addrs = HashMap::from([
-            ("127.0.0.1:49156", ServiceName::Airline.string_name()),
-            ("127.0.0.1:49157", ServiceName::Hotel.string_name()),
-            ("127.0.0.1:49158", ServiceName::Bank.string_name()),
-        ]);
-
-        let transaction_id = 0;
-        let services_info_vec = [
-            (ServiceName::Airline.string_name(), 100.0),
-            (ServiceName::Hotel.string_name(), 200.0),
-            (ServiceName::Bank.string_name(), 300.0),
+    fn it_should_deny_the_retry_of_a_transaction_commited_before_the_offset_advanced() {
+        let client: SocketAddr = "127.0.0.1:49160".parse().unwrap();
+        let leader: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let transaction_id = 7;
+
+        let mut messages = vec![
+            TransactionCheckpoint::build(transaction_id, true, 12),
+            TransactionCheckpoint::build(2, false, 20),
         ];
-
-        let mut message = TransactionRetry::build(
-            transaction_id,
-            services_info_vec[0].1,
-            services_info_vec[1].1,
-            services_info_vec[2].1,
-        );
-        TransactionInfo::add_padding(&mut message);
-
+        for message in &mut messages {
+            TransactionInfo::add_padding(message);
+        }
         let mut mock_socket = MockUdpSocketReceiver::new();
-
-        let msg_len = message.len();
+        let mut seq = mockall::Sequence::new();
+        for message in messages {
+            mock_socket
+                .expect_recv()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move |_| Ok((message.clone(), leader)));
+        }
+        let message = retry_message(transaction_id);
         mock_socket
             .expect_recv()
-            .withf(move |n_bytes| n_bytes == &msg_len)
             .times(1)
-            .returning(move |_| Ok((message.clone(), "".to_string())));
+            .in_sequence(&mut seq)
+            .returning(move |_| Ok((message.clone(), client)));
 
-        let curr_transaction = Arc::new((Mutex::new(None), Condvar::new()));
-        let curr_transaction_clone = curr_transaction.clone();
+        let mut denied = TransactionRetryAck::build(
+            transaction_id,
+            RetryReply::Denied(DenyReason::AlreadyCommited),
+        );
+        TransactionInfo::add_padding(&mut denied);
+        let mut mock_sender = MockUdpSocketSender::new();
+        mock_sender
+            .expect_send_to()
+            .withf(move |buf, addr| buf.to_vec() == denied && *addr == client)
+            .times(1)
+            .returning(|_, _| Ok(()));
 
-        let ended = Arc::new((Mutex::new(false), Condvar::new()));
-        let ended_clone = ended.clone();
+        let mut receiver = TransactionReceiver::new(
+            Box::new(mock_socket),
+            &HashMap::new(),
+            Arc::new((Mutex::new(None), Condvar::new())),
+            Arc::new((Mutex::new(true), Condvar::new())),
+        )
+        .with_replies(Box::new(mock_sender));
+
+        for _ in 0..2 {
+            assert!(receiver.recv().is_ok());
+        }
+        // Este nodo paso a ser lider
+        *receiver.leading().write().unwrap() = true;
+        assert!(!receiver
+            .progress()
+            .lock()
+            .unwrap()
+            .1
+            .contains(transaction_id));
+        assert!(receiver.recv().is_ok());
+        assert!(receiver.retries().is_empty());
+    }
+
+    #[test]
+    fn it_should_follow_the_retry_queue_replicated_by_the_leader() {
+        let queued = |seq, id| QueuedRetry {
+            seq,
+            id,
+            fees: HashMap::from([
+                (ServiceName::Airline.string_name(), 1.0),
+                (ServiceName::Hotel.string_name(), 2.0),
+                (ServiceName::Bank.string_name(), 3.0),
+            ]),
+            client_opt: None,
+        };
+        let mut messages = vec![
+            TransactionRetryQueue::build_enqueue(&queued(0, 4)),
+            TransactionRetryQueue::build_enqueue(&queued(1, 9)),
+            TransactionRetryQueue::build_done(0),
+        ];
+        for message in &mut messages {
+            TransactionInfo::add_padding(message);
+        }
+
+        let mut mock_socket = MockUdpSocketReceiver::new();
+        let mut seq = mockall::Sequence::new();
+        for message in messages {
+            mock_socket
+                .expect_recv()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move |_| Ok((message.clone(), "127.0.0.1:49152".parse().unwrap())));
+        }
 
         let mut receiver = TransactionReceiver::new(
             Box::new(mock_socket),
-            &services_addrs,
-            curr_transaction_clone,
-            ended_clone,
+            &HashMap::new(),
+            Arc::new((Mutex::new(None), Condvar::new())),
+            Arc::new((Mutex::new(false), Condvar::new())),
         );
+        let retries = receiver.retries();
 
         assert!(receiver.recv().is_ok());
-        let opt_transaction = curr_transaction.0.lock().unwrap();
-        assert!(opt_transaction.is_none());
-        assert!(!*ended.0.lock().unwrap());
+        assert!(receiver.recv().is_ok());
+        assert_eq!(retries.len(), 2);
+        assert!(receiver.recv().is_ok());
+        assert_eq!(retries.front(), Some(queued(1, 9)));
+        assert_eq!(retries.len(), 1);
     }
 
     #[test]
-    fn it_should_ignore_transaction_if_id_is_less_than_current() {
+    #[timeout(3000)]
+    fn it_should_set_handoff_after_recv_the_offset_and_decisions() {
         let services_addrs = HashMap::from([
-            ("127.0.0.1:49156", ServiceName::Airline.string_name()),
-            ("127.0.0.1:49157", ServiceName::Hotel.string_name()),
-            ("127.0.0.1:49158", ServiceName::Bank.string_name()),
+            (
+                "127.0.0.1:49156".parse::<SocketAddr>().unwrap(),
+                ServiceName::Airline.string_name(),
+            ),
+            (
+                "127.0.0.1:49157".parse::<SocketAddr>().unwrap(),
+                ServiceName::Hotel.string_name(),
+            ),
+            (
+                "127.0.0.1:49158".parse::<SocketAddr>().unwrap(),
+                ServiceName::Bank.string_name(),
+            ),
         ]);
 
-        let transaction_id = 0;
-        let services_info_vec = [
-            (ServiceName::Airline.string_name(), 100.0),
-            (ServiceName::Hotel.string_name(), 200.0),
-            (ServiceName::Bank.string_name(), 300.0),
-        ];
+        // Con todas las decisiones que se conservan, el traspaso no
+        // entra en un datagrama y se recibe fragmentado
+        let mut decisions = Decisions::new();
+        for id in 0..DECISIONS_CAPACITY as u64 {
+            decisions.insert(id, id % 2 == 0, 28);
+        }
+        let message = TransactionHandoff::build(28, &decisions);
+        assert!(message.len() > UDP_PACKET_SIZE);
 
-        let mut message = TransactionRetry::build(
-            transaction_id,
-            services_info_vec[0].1,
-            services_info_vec[1].1,
-            services_info_vec[2].1,
+        let receiver_addr: SocketAddr = "127.0.0.1:59214".parse().unwrap();
+        let socket = UdpSocketWrap::new_with_addr(None, receiver_addr).unwrap();
+        let mut receiver = TransactionReceiver::new(
+            Box::new(socket),
+            &services_addrs,
+            Arc::new((Mutex::new(None), Condvar::new())),
+            Arc::new((Mutex::new(false), Condvar::new())),
         );
-        TransactionInfo::add_padding(&mut message);
+        let handoff = receiver.handoff();
+
+        let mut sender = UdpSocketWrap::new(None);
+        sender.send_to(&message, &receiver_addr).unwrap();
+        assert!(receiver.recv().is_ok());
+        assert_eq!(*handoff.lock().unwrap(), Some((28, decisions)));
+    }
+
+    #[test]
+    fn it_should_track_the_progress_replicated_by_the_leader() {
+        let mut messages = vec![
+            TransactionCheckpoint::build(9, true, 12),
+            TransactionCheckpoint::build(2, false, 20),
+        ];
+        for message in &mut messages {
+            TransactionInfo::add_padding(message);
+        }
 
         let mut mock_socket = MockUdpSocketReceiver::new();
+        let mut seq = mockall::Sequence::new();
+        for message in messages {
+            mock_socket
+                .expect_recv()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move |_| Ok((message.clone(), "127.0.0.1:49152".parse().unwrap())));
+        }
 
-        let msg_len = message.len();
-        mock_socket
-            .expect_recv()
-            .withf(move |n_bytes| n_bytes == &msg_len)
-            .times(1)
-            .returning(move |_| Ok((message.clone(), "".to_string())));
+        let mut receiver = TransactionReceiver::new(
+            Box::new(mock_socket),
+            &HashMap::new(),
+            Arc::new((Mutex::new(None), Condvar::new())),
+            Arc::new((Mutex::new(false), Condvar::new())),
+        );
+        let progress = receiver.progress();
 
-        let curr_id = transaction_id + 1;
-        let mut mock_transaction = MockTransactionable::new();
-        mock_transaction
-            .expect_get_id()
-            .times(2)
-            .returning(move || curr_id);
+        assert!(receiver.recv().is_ok());
+        assert!(receiver.recv().is_ok());
+        // La decision tomada con el offset anterior se descarta
+        let mut decisions = Decisions::new();
+        decisions.insert(2, false, 20);
+        assert_eq!(*progress.lock().unwrap(), (20, decisions));
+        assert_eq!(*receiver.last_decided().lock().unwrap(), Some(2));
+        assert!(receiver.handoff().lock().unwrap().is_none());
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_drop_oversized_and_malformed_datagrams_and_keep_receiving() {
+        let receiver_addr: SocketAddr = "127.0.0.1:59207".parse().unwrap();
+        let peer_addr: SocketAddr = "127.0.0.1:59208".parse().unwrap();
+        let socket = UdpSocketWrap::new_with_addr(None, receiver_addr).unwrap();
+        let mut receiver = TransactionReceiver::new(
+            Box::new(socket),
+            &HashMap::new(),
+            Arc::new((Mutex::new(None), Condvar::new())),
+            Arc::new((Mutex::new(false), Condvar::new())),
+        );
 
-        let curr_transaction: CurrentTransaction =
-            Arc::new((Mutex::new(Some(Box::new(mock_transaction))), Condvar::new()));
-        let curr_transaction_clone = curr_transaction.clone();
+        let peer = UdpSocket::bind(peer_addr).unwrap();
+        peer.send_to(&[LOG_BYTE; UDP_PACKET_SIZE + 1], receiver_addr)
+            .unwrap();
+        peer.send_to(&[0xFF, b'F', b'R', b'G', 0], receiver_addr)
+            .unwrap();
+        let mut checkpoint = TransactionCheckpoint::build(9, true, 12);
+        TransactionInfo::add_padding(&mut checkpoint);
+        peer.send_to(&checkpoint, receiver_addr).unwrap();
+
+        for _ in 0..3 {
+            assert!(receiver.recv().is_ok());
+        }
+        assert_eq!(*receiver.last_decided().lock().unwrap(), Some(9));
+    }
+
+    #[test]
+    fn it_should_drop_messages_of_unknown_type_or_that_cannot_be_parsed() {
+        let mut decisions = Decisions::new();
+        decisions.insert(7, true, 28);
+        let mut unknown_decision = TransactionHandoff::build(28, &decisions);
+        let code_index = unknown_decision.len() - 1;
+        unknown_decision[code_index] = b'X';
+        let mut unknown_checkpoint = TransactionCheckpoint::build(9, true, 12);
+        unknown_checkpoint[9] = b'X';
+        let enqueue = TransactionRetryQueue::build_enqueue(&QueuedRetry {
+            seq: 0,
+            id: 5,
+            fees: HashMap::new(),
+            client_opt: None,
+        });
+        let messages = vec![
+            vec![],
+            vec![b'Z'; TransactionInfo::size()],
+            vec![LOG_BYTE, 0, 0],
+            unknown_decision,
+            TransactionHandoff::build(28, &decisions)[..6].to_vec(),
+            unknown_checkpoint,
+            enqueue[..enqueue.len() - 1].to_vec(),
+            TransactionRetryQueue::build_done(0)[..4].to_vec(),
+        ];
+        let n_messages = messages.len();
 
-        let ended = Arc::new((Mutex::new(true), Condvar::new()));
-        let ended_clone = ended.clone();
+        let mut mock_socket = MockUdpSocketReceiver::new();
+        let mut seq = mockall::Sequence::new();
+        for message in messages {
+            mock_socket
+                .expect_recv()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move |_| Ok((message.clone(), "127.0.0.1:49152".parse().unwrap())));
+        }
 
+        let curr_transaction = Arc::new((Mutex::new(None), Condvar::new()));
         let mut receiver = TransactionReceiver::new(
             Box::new(mock_socket),
-            &services_addrs,
-            curr_transaction_clone,
-            ended_clone,
+            &HashMap::new(),
+            curr_transaction.clone(),
+            Arc::new((Mutex::new(false), Condvar::new())),
         );
 
-        assert!(receiver.recv().is_ok());
-        let opt_transaction = curr_transaction.0.lock().unwrap();
-        assert!(!opt_transaction.is_none());
-        let transaction = opt_transaction.as_ref().unwrap();
-        assert_eq!(transaction.get_id(), curr_id);
-        assert!(*ended.0.lock().unwrap());
+        for _ in 0..n_messages {
+            assert!(receiver.recv().is_ok());
+        }
+        assert!(curr_transaction.0.lock().unwrap().is_none());
+        assert!(receiver.handoff().lock().unwrap().is_none());
+        assert_eq!(*receiver.last_decided().lock().unwrap(), None);
+        assert!(receiver.retries().is_empty());
     }
 }
@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use super::transaction_state::TransactionState;
+
 #[cfg(test)]
 use mockall::automock;
 
@@ -17,6 +19,13 @@ pub trait Transactionable {
 
     fn commit(&mut self, name: String, opt_fee: Option<f64>) -> bool;
 
+    /// Registra que la respuesta del servicio no se pudo decodificar,
+    /// por lo que se lo considera abortado
+    fn reject(&mut self, name: String) -> bool;
+
+    /// Servicios cuya respuesta no se pudo decodificar
+    fn rejected_services(&self) -> Vec<String>;
+
     fn waiting_services(&self) -> HashMap<String, f64>;
 
     fn not_aborted_services(&self) -> HashMap<String, f64>;
@@ -25,6 +34,8 @@ pub trait Transactionable {
 
     fn all_services(&self) -> HashMap<String, f64>;
 
+    fn service_states(&self) -> HashMap<String, TransactionState>;
+
     fn is_any_waiting(&self) -> bool;
 
     fn is_accepted(&self) -> bool;
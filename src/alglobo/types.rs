@@ -1,5 +1,19 @@
-use std::sync::{Arc, Condvar, Mutex};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
 
-use crate::alglobo::transactionable::Transactionable;
+use crate::alglobo::{decisions::Decisions, transactionable::Transactionable};
 
 pub type CurrentTransaction = Arc<(Mutex<Option<Box<dyn Transactionable + Send>>>, Condvar)>;
+
+/// Offset del archivo de transacciones y decisiones que conserva el
+/// lider (ver `Decisions`), traspasadas por un lider que renuncio
+pub type HandOff = Arc<Mutex<Option<(u64, Decisions)>>>;
+
+/// Progreso replicado por el lider: offset desde el que se retoma
+/// la lectura del archivo y decisiones que conserva el lider
+pub type Progress = Arc<Mutex<(u64, Decisions)>>;
+
+/// Indica si el nodo es el lider, para responder los reintentos
+pub type Leading = Arc<RwLock<bool>>;
+
+/// Id de la ultima transaccion decidida por el lider
+pub type LastDecided = Arc<Mutex<Option<u64>>>;
@@ -1,149 +1,267 @@
+use crate::admin::admin_server;
+use crate::admin::node_state::{KnownLeader, LeaderInfo, NodeState};
+use crate::alglobo::decisions::Decisions;
+use crate::alglobo::retry_queue::RetryQueue;
 use crate::alglobo::transaction_manager::TransactionManager;
 use crate::alglobo::transaction_receiver::TransactionReceiver;
-use crate::alglobo::types::CurrentTransaction;
-use crate::candidates::constants::{
-    ABORT_FILE, AIRLINE_ADDR, BANK_ADDR, DEFAULT_IP, EMPTY, HOTEL_ADDR, VEC_PORT_DATA,
-    VEC_PORT_INFO,
-};
+use crate::alglobo::types::{CurrentTransaction, HandOff, LastDecided, Leading, Progress};
 use crate::candidates::election_code::ElectionCode;
 use crate::candidates::election_message::ElectionMessage;
 use crate::candidates::leader::Leader;
-use crate::file_reader::file_iterator::FileIterator;
-use crate::services::service_name::ServiceName;
+use crate::candidates::step_down::StepDown;
+use crate::config::settings::Settings;
+use crate::config::topology::NodeAddrs;
+use crate::file_reader::transaction_source::SourceSpec;
+use crate::metrics::registry::metrics;
+use crate::runtime::event_loop::EventLoop;
+use crate::sockets::reliable_socket::maybe_polled_reliable;
+use crate::sockets::shared_socket_sender::SharedSocketSender;
+use crate::sockets::tcp_transport::route_tcp;
 use crate::sockets::udp_socket_receiver::UdpSocketReceiver;
 use crate::sockets::udp_socket_sender::UdpSocketSender;
-use crate::sockets::udp_socket_wrap::UdpSocketWrap;
-use std::collections::HashMap;
-use std::sync::{Arc, Condvar, Mutex};
+use crate::submit::{submit_queue::SubmitQueue, submit_server};
+use std::net::SocketAddr;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tracing::{info, info_span, warn};
 
-#[allow(dead_code)]
 pub struct Candidate {
-    udp_receiver: Box<dyn UdpSocketReceiver>,
-    udp_sender: Box<dyn UdpSocketSender>,
-    my_port: String,
-    possible_ports: Vec<String>,
-    leader_port: String,
-    leader_address: String,
+    udp_receiver: Box<dyn UdpSocketReceiver + Send>,
+    udp_sender: Box<dyn UdpSocketSender + Send>,
+    node_id: u64,
+    epoch: u64,
+    data_addr: SocketAddr,
+    info_addr: SocketAddr,
+    admin_addr_opt: Option<SocketAddr>,
+    submit_addr_opt: Option<SocketAddr>,
+    settings: Settings,
+    leader_opt: Option<ElectionMessage>,
+    known_leader: KnownLeader,
     im_the_leader: bool,
+    ended: Arc<(Mutex<bool>, Condvar)>,
+    step_down: StepDown,
+    handoff: HandOff,
+    progress: Progress,
+    last_decided: LastDecided,
+    leading: Leading,
+    retries: RetryQueue,
+    submissions: SubmitQueue,
+    event_loop: EventLoop,
 }
 
 impl Candidate {
+    /// El id de nodo define la prioridad en la eleccion:
+    /// el candidato con menor id es elegido lider. El socket de datos
+    /// del nodo y su receptor de transacciones se alojan en
+    /// `event_loop`, que pueden compartir varios candidatos.
     #[must_use]
     pub fn new(
-        udp_receiver: Box<dyn UdpSocketReceiver>,
-        udp_sender: Box<dyn UdpSocketSender>,
-        my_port: String,
-        possible_ports: Vec<String>,
-        leader_port: String,
-        leader_address: String,
+        udp_receiver: Box<dyn UdpSocketReceiver + Send>,
+        udp_sender: Box<dyn UdpSocketSender + Send>,
+        node: NodeAddrs,
+        settings: Settings,
+        event_loop: EventLoop,
     ) -> Self {
         let im_the_leader = false;
+        let ended = Arc::new((Mutex::new(false), Condvar::new()));
+        let step_down = StepDown::new(ended.clone());
+        let submissions = SubmitQueue::new(ended.clone());
+        let retries = RetryQueue::new(ended.clone());
         Candidate {
             udp_receiver,
             udp_sender,
-            my_port,
-            possible_ports,
-            leader_port,
-            leader_address,
+            node_id: node.id,
+            epoch: 0,
+            data_addr: node.data_addr,
+            info_addr: node.info_addr,
+            admin_addr_opt: node.admin_addr,
+            submit_addr_opt: node.submit_addr,
+            settings,
+            leader_opt: None,
+            known_leader: Arc::new(Mutex::new((0, None))),
             im_the_leader,
+            ended,
+            step_down,
+            handoff: Arc::new(Mutex::new(None)),
+            progress: Arc::new(Mutex::new((0, Decisions::new()))),
+            last_decided: Arc::new(Mutex::new(None)),
+            leading: Arc::new(RwLock::new(false)),
+            retries,
+            submissions,
+            event_loop,
+        }
+    }
+
+    /// Devuelve el pedido de renuncia compartido, con el que se
+    /// le indica al nodo que traspase el liderazgo a un sucesor
+    #[must_use]
+    pub fn step_down(&self) -> StepDown {
+        self.step_down.clone()
+    }
+
+    fn set_leading(&self, leading: bool) {
+        *self
+            .leading
+            .write()
+            .expect("[Candidate] Lock de liderazgo envenenado") = leading;
+    }
+
+    /// Publica la epoca y el lider conocido para el puerto de administracion
+    fn publish_leader(&self) {
+        let leader_opt = self.leader_opt.map(|leader| LeaderInfo {
+            node_id: leader.node_id,
+            epoch: leader.epoch,
+            data_addr: leader.data_addr,
+            info_addr: leader.info_addr,
+        });
+        *self
+            .known_leader
+            .lock()
+            .expect("[Candidate] Lock de lider conocido envenenado") = (self.epoch, leader_opt);
+    }
+
+    fn is_handed_off(&self) -> bool {
+        self.handoff
+            .lock()
+            .expect("[Candidate] Lock de traspaso envenenado")
+            .is_some()
+    }
+
+    fn take_over(&mut self) {
+        info!("Liderazgo recibido por traspaso");
+        self.im_the_leader = true;
+        self.communicate_new_leader();
+    }
+
+    fn message(&self, code: ElectionCode) -> Vec<u8> {
+        ElectionMessage::build(
+            code,
+            self.node_id,
+            self.epoch,
+            self.data_addr,
+            self.info_addr,
+        )
+    }
+
+    /// Espera hasta `timeout` un mensaje de eleccion valido; los
+    /// invalidos se descartan sin reiniciar la espera
+    fn recv_message(&mut self, timeout: Duration) -> Option<ElectionMessage> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            self.udp_receiver.set_timeout(Some(remaining));
+            let (message, addr) = self.udp_receiver.recv(ElectionMessage::size()).ok()?;
+            match ElectionMessage::parse(&message) {
+                Some(value) => return Some(value),
+                None => warn!(from = %addr, "Mensaje de eleccion invalido descartado"),
+            }
+        }
+    }
+
+    /// Registra al emisor de un mensaje LIDER, salvo que
+    /// pertenezca a una epoca anterior a la conocida
+    fn update_leader(&mut self, message: &ElectionMessage) {
+        if message.epoch < self.epoch {
+            warn!(
+                leader_id = message.node_id,
+                leader_epoch = message.epoch,
+                epoch = self.epoch,
+                "Lider de una epoca anterior ignorado"
+            );
+            return;
+        }
+        self.epoch = message.epoch;
+        self.leader_opt = Some(*message);
+        self.publish_leader();
+        metrics().leader_changes.inc(&[]);
+        info!(
+            leader_id = message.node_id,
+            epoch = message.epoch,
+            data_addr = %message.data_addr,
+            info_addr = %message.info_addr,
+            "Nuevo lider"
+        );
+    }
+
+    /// Espera mensajes LIDER hasta recibir un traspaso de liderazgo
+    fn follow_leader(&mut self) {
+        loop {
+            if self.is_handed_off() {
+                self.take_over();
+                return;
+            }
+            if let Some(response) = self.recv_message(Duration::from_millis(10000)) {
+                if response.code == ElectionCode::Leader {
+                    self.update_leader(&response);
+                }
+            }
         }
     }
 
     pub fn send_to(&mut self) {
-        if self.leader_port == EMPTY {
-            self.im_the_leader = self.start_election(&DEFAULT_IP.to_string());
+        if self.is_handed_off() {
+            self.take_over();
             return;
         }
-        let message = ElectionMessage::build(ElectionCode::Alive);
-        let _drop = self
-            .udp_sender
-            .send_to(message.as_slice(), &self.leader_address);
-        self.udp_receiver
-            .set_timeout(Some(Duration::from_millis(1000)));
-        if let Ok(value) = self.udp_receiver.recv(ElectionMessage::size()) {
-            match value.0[0] {
-                b'v' => {
-                    self.udp_receiver
-                        .set_timeout(Some(Duration::from_millis(10000)));
-                    if let Ok(response) = self.udp_receiver.recv(ElectionMessage::size()) {
-                        let his_address = response.1;
+        let leader_address = match self.leader_opt {
+            Some(leader) => leader.info_addr,
+            None => {
+                self.im_the_leader = self.start_election();
+                if self.im_the_leader {
+                    self.communicate_new_leader();
+                }
+                return;
+            }
+        };
+        let message = self.message(ElectionCode::Alive);
+        let _drop = self.udp_sender.send_to(message.as_slice(), &leader_address);
+        if let Some(value) = self.recv_message(Duration::from_millis(1000)) {
+            match value.code {
+                ElectionCode::Alive => {
+                    if let Some(response) = self.recv_message(Duration::from_millis(10000)) {
+                        let his_address = response.info_addr;
                         let _drop = self.udp_sender.send_to(message.as_slice(), &his_address);
-                        self.im_the_leader = self.start_election(&his_address);
+                        self.im_the_leader = self.start_election();
                         if self.im_the_leader {
                             //soy el lider
-                            self.communicate_new_leader(his_address);
+                            self.communicate_new_leader();
                         }
                     }
                 }
                 //contemplar que pasa cuando llega un mensaje de election y tengo que contestar OK, como se que no soy el lider?
-                b'e' => {
-                    let his_address = value.1;
+                ElectionCode::Election => {
+                    let his_address = value.info_addr;
                     let _drop = self.udp_sender.send_to(message.as_slice(), &his_address);
-                    self.im_the_leader = self.start_election(&his_address);
+                    self.im_the_leader = self.start_election();
                     if self.im_the_leader {
                         //soy el lider
-                        self.communicate_new_leader(his_address);
+                        self.communicate_new_leader();
                     } else {
-                        loop {
-                            self.udp_receiver
-                                .set_timeout(Some(Duration::from_millis(10000)));
-                            if let Ok(response) = self.udp_receiver.recv(ElectionMessage::size()) {
-                                if response.0[0] == b'l' {
-                                    let his_port_vect: Vec<&str> = response.1.split(':').collect();
-                                    self.leader_port = his_port_vect[1].to_string();
-                                    self.leader_address = response.1;
-                                }
-                            }
-                        }
+                        self.follow_leader();
                     }
                 }
-                b'l' => {
-                    let his_port_vect: Vec<&str> = value.1.split(':').collect();
-                    self.leader_port = his_port_vect[1].to_string();
-                    self.leader_address = value.1;
-                }
-                _ => {
-                    println!("No hay mas casos");
-                }
+                ElectionCode::Leader => self.update_leader(&value),
             }
         } else {
-            self.im_the_leader = self.start_election(&self.leader_address.to_string());
+            self.im_the_leader = self.start_election();
             if self.im_the_leader {
                 //soy el lider
-                if let Ok(parsed) = self.leader_address.parse() {
-                    self.communicate_new_leader(parsed);
-                }
+                self.communicate_new_leader();
             } else {
-                loop {
-                    self.udp_receiver
-                        .set_timeout(Some(Duration::from_millis(10000)));
-                    if let Ok(response) = self.udp_receiver.recv(ElectionMessage::size()) {
-                        if response.0[0] == b'l' {
-                            let his_port_vect: Vec<&str> = response.1.split(':').collect();
-                            self.leader_port = his_port_vect[1].to_string();
-                            self.leader_address = response.1;
-                        }
-                    }
-                }
+                self.follow_leader();
             }
         }
     }
 
-    fn start_election(&mut self, his_address: &str) -> bool {
+    fn start_election(&mut self) -> bool {
+        metrics().elections.inc(&[]);
         let mut im_the_leader = true;
-        for port in &self.possible_ports {
-            if port.parse::<i32>().unwrap() < self.my_port.parse::<i32>().unwrap() {
-                let message = ElectionMessage::build(ElectionCode::Election);
-                let his_address_vect: Vec<&str> = his_address.split(':').collect();
-                let address_to_send = his_address_vect[0].to_string() + ":" + port;
-                let _drop = self
-                    .udp_sender
-                    .send_to(message.as_slice(), &address_to_send);
-                self.udp_receiver
-                    .set_timeout(Some(Duration::from_millis(100)));
-                if let Ok(_response) = self.udp_receiver.recv(ElectionMessage::size()) {
+        for node in self.settings.topology.nodes.clone() {
+            if node.id < self.node_id {
+                let message = self.message(ElectionCode::Election);
+                let _drop = self.udp_sender.send_to(message.as_slice(), &node.info_addr);
+                if let Some(_response) = self.recv_message(Duration::from_millis(100)) {
                     //loggear que me respondieron
                     im_the_leader = false;
                 }
@@ -152,97 +270,195 @@ impl Candidate {
         im_the_leader
     }
 
-    fn communicate_new_leader(&mut self, his_address: String) {
-        for port in &self.possible_ports {
-            let message = ElectionMessage::build(ElectionCode::Leader);
-            let his_adr_vect: Vec<&str> = his_address.split(':').collect();
-            let adr_to_send = his_adr_vect[0].to_string() + port;
-            let _drop = self.udp_sender.send_to(message.as_slice(), &adr_to_send);
+    /// Inicia una nueva epoca y la anuncia a todos los candidatos
+    fn communicate_new_leader(&mut self) {
+        self.epoch += 1;
+        info!(epoch = self.epoch, "Asumiendo el liderazgo");
+        metrics().leaderships.inc(&[]);
+        let message = self.message(ElectionCode::Leader);
+        for node in &self.settings.topology.nodes {
+            let _drop = self.udp_sender.send_to(message.as_slice(), &node.info_addr);
+        }
+        self.leader_opt = Some(ElectionMessage {
+            code: ElectionCode::Leader,
+            node_id: self.node_id,
+            epoch: self.epoch,
+            data_addr: self.data_addr,
+            info_addr: self.info_addr,
+        });
+        self.publish_leader();
+    }
+
+    /// Espera el anuncio del sucesor luego de un traspaso. Si no
+    /// llega a tiempo, se olvida al lider para iniciar una eleccion
+    fn wait_new_leader(&mut self) {
+        self.leader_opt = None;
+        self.publish_leader();
+        while let Some(response) = self.recv_message(Duration::from_millis(10000)) {
+            if response.code == ElectionCode::Leader && response.node_id != self.node_id {
+                self.update_leader(&response);
+                return;
+            }
         }
-        self.leader_port = self.my_port.clone();
     }
 
     #[allow(clippy::mutex_atomic)]
     pub fn start_candidate(&mut self) {
-        let mut file_iter =
-            FileIterator::new("data/data.csv").expect("fallo la lectura del archivo de datos.csv");
-        let first_transaction = file_iter.next();
-        let true_first_transaction =
-            first_transaction.expect("hubo un problema iterando el archivo de data");
+        // Todos los eventos del nodo, incluidos los de sus hilos, llevan su id
+        let span = info_span!("node", node_id = self.node_id);
+        let _entered = span.enter();
+        // Las filas invalidas se registran recien cuando el lider las procesa
+        let source = SourceSpec {
+            rejects_file_opt: None,
+            ..self.settings.transaction_source()
+        };
+        let mut file_iter = source
+            .open()
+            .expect("fallo la lectura del archivo de transacciones");
+        // Siguiendo el archivo, la primera transaccion es la siguiente al
+        // checkpoint y puede que todavia no se haya escrito
+        if let Some(offset) = source.checkpoint_offset() {
+            if file_iter.seek(offset).is_err() {
+                let _drop = file_iter.seek(0);
+            }
+        }
+        let true_first_transaction = loop {
+            match (file_iter.next(), &source.follow_opt) {
+                (Some(transaction), _) => break transaction,
+                (None, Some(follow)) => thread::sleep(follow.poll),
+                (None, None) => panic!("hubo un problema iterando el archivo de data"),
+            }
+        };
         let first_trans_cond: CurrentTransaction = Arc::new((
             Mutex::new(Some(Box::new(true_first_transaction))),
             Condvar::new(),
         ));
-        let mut port_transaction = 0;
-        let mut socket_data_recv = UdpSocketWrap::new(None);
-        let mut socket_data_send = UdpSocketWrap::new(None);
-        for port in VEC_PORT_DATA.clone() {
-            let socket_info_data_new = UdpSocketWrap::new_with_addr(
-                Some(Duration::from_millis(1000)),
-                DEFAULT_IP.to_string() + port.to_string().as_str(),
-            );
-            if let Ok(socket_new_aux) = socket_info_data_new {
-                socket_data_recv = socket_new_aux;
-                if let Ok(socket_aux) = socket_data_recv.try_clone() {
-                    socket_data_send = socket_aux;
-                    port_transaction = port;
+        let (binding, (socket_data_send, socket_data_recv)) = self
+            .event_loop
+            .bind(self.data_addr)
+            .expect("[Candidate] No se pudo abrir el socket de datos");
+        // Lo que llega por TCP se entrega al receptor de transacciones
+        // por su inbox, debajo de la seguridad y del canal confiable
+        let (socket_data_send, tcp_receiver_opt) =
+            route_tcp(socket_data_send, self.settings.topology.tcp_addrs());
+        let (socket_data_send, socket_data_recv) =
+            self.settings
+                .security()
+                .secure(socket_data_send, socket_data_recv, self.data_addr);
+        let (socket_data_send, socket_data_recv) =
+            maybe_polled_reliable(socket_data_send, socket_data_recv, self.settings.reliable());
+        let socket_data_send = SharedSocketSender::new(socket_data_send);
+        let true_first_trans_cond = first_trans_cond.clone();
+        let services_addrs = self.settings.topology.services_addrs();
+        let transaction_receiver = TransactionReceiver::new(
+            socket_data_recv,
+            &services_addrs,
+            true_first_trans_cond,
+            self.ended.clone(),
+        )
+        .with_replies(Box::new(socket_data_send.clone()))
+        .with_replicas(self.data_addr, &self.settings.topology.replicas_addrs());
+        self.handoff = transaction_receiver.handoff();
+        self.progress = transaction_receiver.progress();
+        self.leading = transaction_receiver.leading();
+        self.retries = transaction_receiver.retries();
+        self.last_decided = transaction_receiver.last_decided();
+        let inbox = self.event_loop.start(binding, transaction_receiver);
+        if let Some(tcp_receiver) = tcp_receiver_opt {
+            inbox.forward(Box::new(tcp_receiver));
+        }
+        // Los nodos escuchan pedidos SUBMIT en su propia direccion, pero
+        // solo el lider los encola
+        if let Some(addr) = self.submit_addr_opt {
+            submit_server::serve(addr, self.submissions.clone())
+                .expect("[Candidate] No se pudo escuchar pedidos SUBMIT");
+        }
+        if let Some(addr) = self.admin_addr_opt {
+            let state = NodeState {
+                node_id: self.node_id,
+                known_leader: self.known_leader.clone(),
+                leading: self.leading.clone(),
+                curr_transaction: first_trans_cond.clone(),
+                retries: self.retries.clone(),
+                submissions: self.submissions.clone(),
+                last_decided: self.last_decided.clone(),
+            };
+            admin_server::serve(addr, state)
+                .expect("[Candidate] No se pudo escuchar pedidos de administracion");
+        }
+        loop {
+            loop {
+                self.send_to();
+
+                if self.im_the_leader {
                     break;
                 }
             }
-        }
-        let true_first_trans_cond = first_trans_cond.clone();
-        let ended_cvar = Arc::new((Mutex::new(false), Condvar::new()));
-        let ended_cvar_clone = ended_cvar.clone();
-        thread::spawn(move || {
-            let services_addrs_str_recv = &HashMap::from([
-                (AIRLINE_ADDR, ServiceName::Airline.string_name()),
-                (HOTEL_ADDR, ServiceName::Hotel.string_name()),
-                (BANK_ADDR, ServiceName::Bank.string_name()),
-            ]);
-            let mut transaction_receiver = TransactionReceiver::new(
-                Box::new(socket_data_recv),
-                services_addrs_str_recv,
-                true_first_trans_cond,
-                ended_cvar_clone,
-            );
 
-            loop {
-                let _drop = transaction_receiver.recv();
+            let mut leader = Leader::new(self.node_id, self.epoch, self.data_addr, self.info_addr);
+            let udp_sender = socket_data_send.clone();
+            let mut transaction_manager = TransactionManager::new(
+                self.data_addr,
+                Box::new(udp_sender),
+                first_trans_cond.clone(),
+                self.ended.clone(),
+                &services_addrs,
+                &self.settings.topology.replicas_addrs(),
+                self.settings.manager_timeout,
+                self.settings.sleep_manager,
+                self.settings.end_timeout,
+                Some(self.settings.abort_file.clone()),
+            );
+            let opt_handoff = self
+                .handoff
+                .lock()
+                .expect("[Candidate] Lock de traspaso envenenado")
+                .take();
+            // Sin traspaso, el lider anterior se cayo: se retoma desde
+            // el progreso que llego a replicar
+            let opt_resume = opt_handoff.or_else(|| {
+                let progress = self
+                    .progress
+                    .lock()
+                    .expect("[Candidate] Lock de progreso envenenado");
+                (!progress.1.is_empty()).then(|| progress.clone())
+            });
+            if let Some((offset, decisions)) = opt_resume {
+                transaction_manager.resume_from(offset, decisions);
             }
-        });
-        loop {
-            self.send_to();
-
-            if self.im_the_leader {
-                break;
+            if self.submit_addr_opt.is_some() {
+                self.submissions.open();
+                transaction_manager.accept_submissions(self.submissions.clone());
             }
-        }
+            transaction_manager.accept_retries(self.retries.clone());
+            transaction_manager.report_decisions(self.last_decided.clone());
+            self.set_leading(true);
+            // Los pedidos de renuncia previos al liderazgo se descartan
+            self.step_down.clear();
+            let mut transaction_manager = leader.start_leader(
+                transaction_manager,
+                &mut self.udp_receiver,
+                &mut self.udp_sender,
+                &self.settings.transaction_source(),
+                &self.step_down,
+            );
+            self.set_leading(false);
+            // Los pedidos pendientes se rechazan: el sucesor no los conoce
+            self.submissions.close();
+            // Si vuelve a ser elegido, conoce lo que decidio como lider
+            *self
+                .progress
+                .lock()
+                .expect("[Candidate] Lock de progreso envenenado") = transaction_manager.progress();
 
-        let mut leader = Leader::new(VEC_PORT_INFO.clone());
-        let services_addrs_str = &HashMap::from([
-            (AIRLINE_ADDR, ServiceName::Airline.string_name()),
-            (HOTEL_ADDR, ServiceName::Hotel.string_name()),
-            (BANK_ADDR, ServiceName::Bank.string_name()),
-        ]);
-        let mut vec_addr: Vec<String> = vec![DEFAULT_IP.to_string() + "49353"];
-        for port in VEC_PORT_DATA.clone() {
-            vec_addr.push(DEFAULT_IP.to_string() + port.to_string().as_str());
+            let successor = match self.step_down.successor() {
+                Some(value) => value,
+                None => break,
+            };
+            transaction_manager.hand_off(&successor);
+            self.step_down.clear();
+            self.im_the_leader = false;
+            self.wait_new_leader();
         }
-        let vec = &vec_addr;
-        let transaction_manager = TransactionManager::new(
-            port_transaction as u64,
-            Box::new(socket_data_send),
-            first_trans_cond.clone(),
-            ended_cvar,
-            services_addrs_str,
-            vec,
-            Duration::from_millis(10000),
-            Some(ABORT_FILE.to_string()),
-        );
-        leader.start_leader(
-            transaction_manager,
-            &mut self.udp_receiver,
-            &mut self.udp_sender,
-        );
     }
 }
@@ -1,39 +1,88 @@
+use std::{
+    convert::TryInto,
+    mem::size_of,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
 use super::election_code::ElectionCode;
 
 const ALIVE_BYTE: u8 = b'v';
 const ELECTION_BYTE: u8 = b'e';
 const LEADER_BYTE: u8 = b'l';
 
-pub struct ElectionMessage;
+const IPV4_BYTE: u8 = 4;
+const IPV6_BYTE: u8 = 6;
+// Familia + direccion IP (rellenada a 16 bytes) + puerto
+const ADDR_SIZE: usize = 1 + 16 + size_of::<u16>();
+
+/// Mensaje del algoritmo de eleccion. Ademas del codigo lleva la
+/// identidad del emisor: su id de nodo, la epoca que conoce y las
+/// direcciones de sus sockets de datos y de eleccion, de modo que
+/// el receptor no dependa de la direccion de origen del datagrama.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ElectionMessage {
+    pub code: ElectionCode,
+    pub node_id: u64,
+    pub epoch: u64,
+    pub data_addr: SocketAddr,
+    pub info_addr: SocketAddr,
+}
 
 impl ElectionMessage {
     #[must_use]
-    pub fn size() -> usize {
-        ElectionMessage::build(ElectionCode::Alive).len()
+    pub const fn size() -> usize {
+        1 + 2 * size_of::<u64>() + 2 * ADDR_SIZE
     }
 
     #[must_use]
-    pub fn build(code: ElectionCode) -> Vec<u8> {
-        let code = ElectionMessage::map_code(code);
-        let message = vec![code];
+    pub fn build(
+        code: ElectionCode,
+        node_id: u64,
+        epoch: u64,
+        data_addr: SocketAddr,
+        info_addr: SocketAddr,
+    ) -> Vec<u8> {
+        let mut message = vec![ElectionMessage::map_code(code)];
+        message.append(&mut node_id.to_be_bytes().to_vec());
+        message.append(&mut epoch.to_be_bytes().to_vec());
+        message.append(&mut ElectionMessage::addr_bytes(data_addr));
+        message.append(&mut ElectionMessage::addr_bytes(info_addr));
         message
     }
 
-    /// # Panics
-    ///
-    /// Esta funcion paniquea si:
-    /// - se recibio un codigo desconocido
+    /// Devuelve `None` si el mensaje es mas corto de lo esperado o
+    /// tiene un codigo o una familia de direcciones desconocidos
     #[must_use]
-    pub fn code(code: u8) -> ElectionCode {
-        let err_msg = format!(
-            "[Election message] Codigo de eleccion desconocido: {}",
-            code
-        );
+    pub fn parse(message: &[u8]) -> Option<ElectionMessage> {
+        if message.len() < ElectionMessage::size() {
+            return None;
+        }
+        let code = ElectionMessage::code(message[0])?;
+        let mut begin = 1;
+        let node_id = ElectionMessage::parse_u64(message, begin);
+        begin += size_of::<u64>();
+        let epoch = ElectionMessage::parse_u64(message, begin);
+        begin += size_of::<u64>();
+        let data_addr = ElectionMessage::parse_addr(&message[begin..begin + ADDR_SIZE])?;
+        begin += ADDR_SIZE;
+        let info_addr = ElectionMessage::parse_addr(&message[begin..begin + ADDR_SIZE])?;
+        Some(ElectionMessage {
+            code,
+            node_id,
+            epoch,
+            data_addr,
+            info_addr,
+        })
+    }
+
+    /// Devuelve `None` si el codigo es desconocido
+    #[must_use]
+    pub fn code(code: u8) -> Option<ElectionCode> {
         match code {
-            ALIVE_BYTE => ElectionCode::Alive,
-            ELECTION_BYTE => ElectionCode::Election,
-            LEADER_BYTE => ElectionCode::Leader,
-            _ => panic!("{}", err_msg),
+            ALIVE_BYTE => Some(ElectionCode::Alive),
+            ELECTION_BYTE => Some(ElectionCode::Election),
+            LEADER_BYTE => Some(ElectionCode::Leader),
+            _ => None,
         }
     }
 
@@ -44,4 +93,86 @@ impl ElectionMessage {
             ElectionCode::Leader => LEADER_BYTE,
         }
     }
+
+    fn addr_bytes(addr: SocketAddr) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(ADDR_SIZE);
+        match addr.ip() {
+            IpAddr::V4(ip) => {
+                bytes.push(IPV4_BYTE);
+                bytes.append(&mut ip.octets().to_vec());
+                bytes.append(&mut vec![0; 12]);
+            }
+            IpAddr::V6(ip) => {
+                bytes.push(IPV6_BYTE);
+                bytes.append(&mut ip.octets().to_vec());
+            }
+        }
+        bytes.append(&mut addr.port().to_be_bytes().to_vec());
+        bytes
+    }
+
+    fn parse_addr(bytes: &[u8]) -> Option<SocketAddr> {
+        let port_bytes: [u8; size_of::<u16>()] = bytes[17..ADDR_SIZE]
+            .try_into()
+            .expect("[Election message] Los puertos deberian ocupar 2 bytes");
+        let port = u16::from_be_bytes(port_bytes);
+        let ip = match bytes[0] {
+            IPV4_BYTE => IpAddr::V4(Ipv4Addr::new(bytes[1], bytes[2], bytes[3], bytes[4])),
+            IPV6_BYTE => {
+                let octets: [u8; 16] = bytes[1..17]
+                    .try_into()
+                    .expect("[Election message] Las direcciones IPv6 deberian ocupar 16 bytes");
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            _ => return None,
+        };
+        Some(SocketAddr::new(ip, port))
+    }
+
+    fn parse_u64(message: &[u8], begin: usize) -> u64 {
+        let bytes: [u8; size_of::<u64>()] = message[begin..begin + size_of::<u64>()]
+            .try_into()
+            .expect("[Election message] Los enteros deberian ocupar 8 bytes");
+        u64::from_be_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_should_return_the_fields_given_to_build() {
+        let data_addr: SocketAddr = "10.0.0.2:49152".parse().unwrap();
+        let info_addr: SocketAddr = "[::1]:49354".parse().unwrap();
+        let message = ElectionMessage::build(ElectionCode::Leader, 3, 7, data_addr, info_addr);
+
+        assert_eq!(message.len(), ElectionMessage::size());
+        assert_eq!(
+            ElectionMessage::parse(&message),
+            Some(ElectionMessage {
+                code: ElectionCode::Leader,
+                node_id: 3,
+                epoch: 7,
+                data_addr,
+                info_addr,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_should_return_none_for_invalid_messages() {
+        let addr: SocketAddr = "10.0.0.2:49152".parse().unwrap();
+        let message = ElectionMessage::build(ElectionCode::Alive, 3, 7, addr, addr);
+
+        let mut unknown_code = message.clone();
+        unknown_code[0] = b'?';
+        let mut unknown_family = message.clone();
+        unknown_family[1 + 2 * size_of::<u64>()] = 9;
+
+        assert_eq!(ElectionMessage::parse(&[]), None);
+        assert_eq!(ElectionMessage::parse(&message[..10]), None);
+        assert_eq!(ElectionMessage::parse(&unknown_code), None);
+        assert_eq!(ElectionMessage::parse(&unknown_family), None);
+    }
 }
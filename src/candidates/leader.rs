@@ -1,77 +1,106 @@
 use crate::alglobo::transaction_manager::TransactionManager;
 use crate::candidates::election_code::ElectionCode;
 use crate::candidates::election_message::ElectionMessage;
+use crate::file_reader::transaction_source::SourceSpec;
+use crate::metrics::registry::metrics;
 use crate::sockets::udp_socket_receiver::UdpSocketReceiver;
 use crate::sockets::udp_socket_sender::UdpSocketSender;
-use std::ops::Range;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::RwLock;
 use std::thread;
 use std::time::Duration;
+use tracing::{info_span, warn};
 
-use super::constants::TRANSACTION_FILE;
+use super::step_down::StepDown;
 
-#[allow(dead_code)]
 pub struct Leader {
-    /*udp_receiver: Box<dyn UdpSocketReceiver + Send>,
-    udp_sender: Box<dyn UdpSocketSender + Send>,*/
-    possible_ports: Range<i32>,
+    node_id: u64,
+    epoch: u64,
+    data_addr: SocketAddr,
+    info_addr: SocketAddr,
 }
 
 impl Leader {
     #[must_use]
-    pub fn new(
-        /* udp_receiver: Box<dyn UdpSocketReceiver + Send>,
-        udp_sender: Box<dyn UdpSocketSender + Send>,*/
-        possible_ports: Range<i32>,
-    ) -> Self {
+    pub fn new(node_id: u64, epoch: u64, data_addr: SocketAddr, info_addr: SocketAddr) -> Self {
         Leader {
-            /*   udp_receiver,
-            udp_sender,*/
-            possible_ports,
+            node_id,
+            epoch,
+            data_addr,
+            info_addr,
         }
     }
 
+    fn message(&self, code: ElectionCode) -> Vec<u8> {
+        ElectionMessage::build(
+            code,
+            self.node_id,
+            self.epoch,
+            self.data_addr,
+            self.info_addr,
+        )
+    }
+
     pub fn recv(
         &mut self,
-        recv: &mut Box<dyn UdpSocketReceiver>,
-        send: &mut Box<dyn UdpSocketSender>,
+        recv: &mut Box<dyn UdpSocketReceiver + Send>,
+        send: &mut Box<dyn UdpSocketSender + Send>,
     ) {
         recv.set_timeout(Some(Duration::from_millis(10000)));
         let result = recv.recv(ElectionMessage::size());
         if let Ok(response) = result.as_ref() {
-            match response.0[0] {
-                b'v' => {
-                    let message = ElectionMessage::build(ElectionCode::Alive);
-                    let his_address = response.1.clone();
+            let his_message = match ElectionMessage::parse(&response.0) {
+                Some(value) => value,
+                None => {
+                    warn!(from = %response.1, "Mensaje de eleccion invalido descartado");
+                    return;
+                }
+            };
+            let his_address = his_message.info_addr;
+            metrics()
+                .leader_messages
+                .inc(&[("code", &his_message.code.to_string())]);
+            match his_message.code {
+                ElectionCode::Alive => {
+                    let message = self.message(ElectionCode::Alive);
                     let _drop = send.send_to(message.as_slice(), &his_address);
                 }
-                b'e' => {
-                    let his_address = response.1.clone();
-                    for port in self.possible_ports.clone() {
-                        let message = ElectionMessage::build(ElectionCode::Leader);
-                        let his_address_vect: Vec<&str> = his_address.split(':').collect();
-                        let address_to_send =
-                            his_address_vect[0].to_string() + ":" + &port.to_string();
-                        let _drop = send.send_to(message.as_slice(), &address_to_send);
-                    }
+                ElectionCode::Election => {
+                    let message = self.message(ElectionCode::Leader);
+                    let _drop = send.send_to(message.as_slice(), &his_address);
                 }
-                _ => {}
+                ElectionCode::Leader => {}
             }
         }
     }
 
+    /// Ejecuta el `TransactionManager` hasta que termine de procesar
+    /// el origen de transacciones `source` o se pida la renuncia del lider,
+    /// y lo devuelve para que pueda traspasar su estado al sucesor
+    ///
+    /// # Panics
+    ///
+    /// Esta funcion paniquea si el hilo del `TransactionManager` paniqueo
     pub fn start_leader(
         &mut self,
         mut transaction_manager: TransactionManager,
-        recv: &mut Box<dyn UdpSocketReceiver>,
-        send: &mut Box<dyn UdpSocketSender>,
-    ) {
+        recv: &mut Box<dyn UdpSocketReceiver + Send>,
+        send: &mut Box<dyn UdpSocketSender + Send>,
+        source: &SourceSpec,
+        step_down: &StepDown,
+    ) -> TransactionManager {
         let boolean = false;
         let finish_lock = Arc::new(RwLock::new(boolean));
         let finish_lock_clone = finish_lock.clone();
+        let step_down_clone = step_down.clone();
+        let source = source.clone();
+        // Los eventos del Transaction Manager llevan la epoca del liderazgo
+        let span = info_span!("leader", epoch = self.epoch);
         let join_handle = thread::spawn(move || {
-            transaction_manager.run(TRANSACTION_FILE, &finish_lock_clone);
+            let _entered = span.enter();
+            transaction_manager.run(&source, &finish_lock_clone, &step_down_clone);
+            transaction_manager
         });
         loop {
             self.recv(recv, send);
@@ -80,48 +109,47 @@ impl Leader {
                 break;
             }
         }
-        let _ = join_handle.join();
+        join_handle
+            .join()
+            .expect("[Leader] El hilo del Transaction Manager no deberia paniquear")
     }
 }
-/*
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::candidates::candidate::Candidate;
     use crate::{
-        candidates::election_code::ElectionCode,
         sockets::udp_socket_receiver::MockUdpSocketReceiver,
         sockets::udp_socket_sender::MockUdpSocketSender,
     };
 
     #[test]
-    fn it_should_receive_alive_message(){
-        let address = "127.0.0.1:49156";
+    fn it_should_answer_election_with_leader_to_the_sender_info_address() {
+        let leader_data: SocketAddr = "10.0.0.1:49152".parse().unwrap();
+        let leader_info: SocketAddr = "10.0.0.1:49353".parse().unwrap();
+        let his_data: SocketAddr = "10.0.0.2:49153".parse().unwrap();
+        let his_info: SocketAddr = "10.0.0.2:49354".parse().unwrap();
+
+        let election = ElectionMessage::build(ElectionCode::Election, 49354, 1, his_data, his_info);
+        let expected =
+            ElectionMessage::build(ElectionCode::Leader, 49353, 2, leader_data, leader_info);
+
         let mut mock_receiver = MockUdpSocketReceiver::new();
-        let mut mock_sender = MockUdpSocketSender::new();
-        let mut mock_receiver_candidate = MockUdpSocketReceiver::new();
-        let mut mock_sender_leader = MockUdpSocketSender::new();
-        let message = ElectionMessage::build(ElectionCode::Alive);
-        let messages = [message.clone()];
+        mock_receiver.expect_set_timeout().returning(|_| ());
         mock_receiver
             .expect_recv()
             .withf(|n_bytes| n_bytes == &ElectionMessage::size())
             .times(1)
-            .returning(move |_| Ok((message.clone(),address.to_string())));
+            .returning(move |_| Ok((election.clone(), his_info)));
+        let mut mock_sender = MockUdpSocketSender::new();
         mock_sender
             .expect_send_to()
-            .withf(move |buf, addr| {
-                messages.contains(&buf.to_vec()) && addr == address
-            })
+            .withf(move |buf, addr| buf.to_vec() == expected && addr == &his_info)
             .times(1)
             .returning(|_, _| Ok(()));
-        let leader = Leader::new(Box::new(mock_receiver), Box::new(mock_sender_leader),vec!["".to_string()]);
-        let mut candidate = Candidate::new(Box::new(mock_receiver_candidate),
-                                           Box::new(mock_sender),
-                                           "49156".to_string(),
-                                           vec!["".to_string()],
-                                           "49156".to_string(), address.to_string());
-        candidate.send_to();
+
+        let mut leader = Leader::new(49353, 2, leader_data, leader_info);
+        let mut recv: Box<dyn UdpSocketReceiver + Send> = Box::new(mock_receiver);
+        let mut send: Box<dyn UdpSocketSender + Send> = Box::new(mock_sender);
+        leader.recv(&mut recv, &mut send);
     }
 }
- */
@@ -3,3 +3,4 @@ pub mod constants;
 pub mod election_code;
 pub mod election_message;
 pub mod leader;
+pub mod step_down;
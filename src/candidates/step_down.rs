@@ -0,0 +1,77 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+
+/// Pedido de renuncia del lider actual. Guarda la direccion de datos
+/// del sucesor elegido y despierta al `TransactionManager` si se
+/// encuentra esperando reintentos.
+#[derive(Clone)]
+pub struct StepDown {
+    successor: Arc<RwLock<Option<SocketAddr>>>,
+    ended: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl StepDown {
+    #[must_use]
+    pub fn new(ended: Arc<(Mutex<bool>, Condvar)>) -> Self {
+        StepDown {
+            successor: Arc::new(RwLock::new(None)),
+            ended,
+        }
+    }
+
+    pub fn request(&self, successor_addr: SocketAddr) {
+        {
+            let mut successor = self
+                .successor
+                .write()
+                .expect("[Step Down] Lock de sucesor envenenado");
+            *successor = Some(successor_addr);
+        }
+        let _ended = self
+            .ended
+            .0
+            .lock()
+            .expect("[Step Down] Lock de finalizacion envenenado");
+        self.ended.1.notify_all();
+    }
+
+    #[must_use]
+    pub fn requested(&self) -> bool {
+        self.successor().is_some()
+    }
+
+    #[must_use]
+    pub fn successor(&self) -> Option<SocketAddr> {
+        *self
+            .successor
+            .read()
+            .expect("[Step Down] Lock de sucesor envenenado")
+    }
+
+    pub fn clear(&self) {
+        let mut successor = self
+            .successor
+            .write()
+            .expect("[Step Down] Lock de sucesor envenenado");
+        *successor = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_keep_the_successor_until_cleared() {
+        let step_down = StepDown::new(Arc::new((Mutex::new(true), Condvar::new())));
+        assert!(!step_down.requested());
+
+        let successor: SocketAddr = "127.0.0.1:49153".parse().unwrap();
+        step_down.request(successor);
+        assert!(step_down.requested());
+        assert_eq!(step_down.successor(), Some(successor));
+
+        step_down.clear();
+        assert!(!step_down.requested());
+    }
+}
@@ -0,0 +1,30 @@
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    Io(String, String),
+    Parse(String),
+    InvalidValue(String, String),
+    Invalid(String),
+    UnknownVar(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => {
+                write!(
+                    f,
+                    "No se pudo leer el archivo de configuracion {}: {}",
+                    path, err
+                )
+            }
+            ConfigError::Parse(err) => write!(f, "Archivo de configuracion invalido: {}", err),
+            ConfigError::InvalidValue(key, value) => {
+                write!(f, "Valor invalido para {}: {}", key, value)
+            }
+            ConfigError::Invalid(err) => write!(f, "Configuracion invalida: {}", err),
+            ConfigError::UnknownVar(key) => write!(f, "Variable de entorno desconocida: {}", key),
+        }
+    }
+}
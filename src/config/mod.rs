@@ -0,0 +1,3 @@
+pub mod config_error;
+pub mod settings;
+pub mod topology;
@@ -0,0 +1,906 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs, iter,
+    net::SocketAddr,
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::candidates::constants::{
+    ABORT_FILE, CHECKPOINT_FILE, END_TIMEOUT, FOLLOW_POLL, REJECTS_FILE, SLEEP_MANAGER,
+    TRANSACTION_FILE,
+};
+use crate::file_reader::{
+    input_format::InputFormat,
+    transaction_source::{FollowSpec, SourceSpec},
+};
+use crate::logging::{log_format::LogFormat, logger::parse_filter};
+use crate::services::constants::PERCENTAGE_ERROR;
+use crate::services::service_name::ServiceName;
+use crate::sockets::link_security::LinkSecurity;
+use crate::sockets::reliable_socket::ReliableConfig;
+use crate::sockets::signed_socket::Keys;
+
+use super::{
+    config_error::ConfigError,
+    topology::{NodeAddrs, Topology},
+};
+
+/// Variable de entorno con la ruta del archivo de configuracion
+pub static CONFIG_ENV: &str = "ALGLOBO_CONFIG";
+static ENV_PREFIX: &str = "ALGLOBO_";
+static MANAGER_TIMEOUT: Duration = Duration::from_secs(10);
+static RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(200);
+static MAX_ATTEMPTS: u32 = 5;
+static REPLAY_WINDOW: Duration = Duration::from_secs(30);
+static LOG_LEVEL: &str = "info";
+
+/// Configuracion de un proceso de AlGlobo. Se parte de los valores
+/// por defecto, se pisan con los del archivo TOML y por ultimo con
+/// las variables de entorno `ALGLOBO_<CLAVE>`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Settings {
+    pub topology: Topology,
+    pub sleep_manager: Duration,
+    pub end_timeout: Duration,
+    pub manager_timeout: Duration,
+    pub percentage_error: u32,
+    pub transaction_file: String,
+    /// Formato del archivo de transacciones; si se omite se deduce
+    /// de la extension
+    pub input_format: Option<InputFormat>,
+    pub abort_file: String,
+    pub rejects_file: String,
+    /// Si el lider sigue leyendo el archivo de transacciones a medida
+    /// que se le agregan filas
+    pub follow: bool,
+    pub follow_poll: Duration,
+    pub checkpoint_file: String,
+    pub reliable_messaging: bool,
+    pub retransmit_timeout: Duration,
+    pub max_attempts: u32,
+    pub keys: Keys,
+    pub replay_window: Duration,
+    pub psks: Keys,
+    pub log_level: String,
+    pub log_format: LogFormat,
+    /// Direccion del endpoint HTTP `/metrics`, si se exponen metricas
+    pub metrics_addr: Option<SocketAddr>,
+    /// Archivo OTLP/JSON al que se exportan los spans, si se exportan
+    pub trace_file: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct SettingsFile {
+    sleep_manager_ms: Option<u64>,
+    end_timeout_ms: Option<u64>,
+    manager_timeout_ms: Option<u64>,
+    percentage_error: Option<u32>,
+    transaction_file: Option<String>,
+    input_format: Option<String>,
+    abort_file: Option<String>,
+    rejects_file: Option<String>,
+    follow: Option<bool>,
+    follow_poll_ms: Option<u64>,
+    checkpoint_file: Option<String>,
+    reliable_messaging: Option<bool>,
+    retransmit_timeout_ms: Option<u64>,
+    max_attempts: Option<u32>,
+    replay_window_ms: Option<u64>,
+    log_level: Option<String>,
+    log_format: Option<String>,
+    metrics_addr: Option<String>,
+    trace_file: Option<String>,
+    retry_addr: Option<String>,
+    services: Option<ServicesFile>,
+    nodes: Option<Vec<NodeFile>>,
+    keys: Option<HashMap<String, String>>,
+    psk: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ServicesFile {
+    airline: Option<String>,
+    hotel: Option<String>,
+    bank: Option<String>,
+    airline_transport: Option<String>,
+    hotel_transport: Option<String>,
+    bank_transport: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NodeFile {
+    id: u64,
+    info_addr: String,
+    data_addr: String,
+    admin_addr: Option<String>,
+    submit_addr: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            topology: Topology::default(),
+            sleep_manager: SLEEP_MANAGER,
+            end_timeout: END_TIMEOUT,
+            manager_timeout: MANAGER_TIMEOUT,
+            percentage_error: PERCENTAGE_ERROR,
+            transaction_file: TRANSACTION_FILE.to_string(),
+            input_format: None,
+            abort_file: ABORT_FILE.to_string(),
+            rejects_file: REJECTS_FILE.to_string(),
+            follow: false,
+            follow_poll: FOLLOW_POLL,
+            checkpoint_file: CHECKPOINT_FILE.to_string(),
+            reliable_messaging: false,
+            retransmit_timeout: RETRANSMIT_TIMEOUT,
+            max_attempts: MAX_ATTEMPTS,
+            keys: Keys::default(),
+            replay_window: REPLAY_WINDOW,
+            psks: Keys::default(),
+            log_level: LOG_LEVEL.to_string(),
+            log_format: LogFormat::Human,
+            metrics_addr: None,
+            trace_file: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Carga la configuracion del archivo `path_opt` o, si no se indica,
+    /// del archivo de `ALGLOBO_CONFIG` (si esta definida) y aplica las
+    /// variables de entorno.
+    ///
+    /// # Errors
+    ///
+    /// Devuelve un error si:
+    /// - no se pudo leer o parsear el archivo de configuracion
+    /// - alguna variable de entorno es desconocida o tiene un valor invalido
+    /// - la configuracion resultante no es valida
+    pub fn from_env(path_opt: Option<&str>) -> Result<Settings, ConfigError> {
+        let path_opt = path_opt
+            .map(str::to_string)
+            .or_else(|| env::var(CONFIG_ENV).ok());
+        let content = match path_opt {
+            Some(path) => Some(
+                fs::read_to_string(&path).map_err(|err| ConfigError::Io(path, err.to_string()))?,
+            ),
+            None => None,
+        };
+        let vars = env::vars().filter(|(key, _)| key.starts_with(ENV_PREFIX));
+        Settings::load(content.as_deref(), vars)
+    }
+
+    /// Arma la configuracion a partir del contenido de un archivo
+    /// TOML opcional y de pares `(clave, valor)` de entorno.
+    ///
+    /// # Errors
+    ///
+    /// Devuelve un error si:
+    /// - el contenido no es TOML valido o tiene claves desconocidas
+    /// - alguna variable es desconocida o algun valor no se puede parsear
+    /// - la configuracion resultante no es valida
+    pub fn load<I>(content: Option<&str>, vars: I) -> Result<Settings, ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let file: SettingsFile = match content {
+            Some(value) => {
+                toml::from_str(value).map_err(|err| ConfigError::Parse(err.to_string()))?
+            }
+            None => SettingsFile::default(),
+        };
+        let mut settings = Settings::default();
+        settings.apply_file(file)?;
+        for (key, value) in vars {
+            settings.apply_var(&key, &value)?;
+        }
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn apply_file(&mut self, file: SettingsFile) -> Result<(), ConfigError> {
+        if let Some(value) = file.sleep_manager_ms {
+            self.sleep_manager = Duration::from_millis(value);
+        }
+        if let Some(value) = file.end_timeout_ms {
+            self.end_timeout = Duration::from_millis(value);
+        }
+        if let Some(value) = file.manager_timeout_ms {
+            self.manager_timeout = Duration::from_millis(value);
+        }
+        if let Some(value) = file.percentage_error {
+            self.percentage_error = value;
+        }
+        if let Some(value) = file.transaction_file {
+            self.transaction_file = value;
+        }
+        if let Some(value) = file.input_format {
+            self.input_format = parse_input_format("input_format", &value)?;
+        }
+        if let Some(value) = file.abort_file {
+            self.abort_file = value;
+        }
+        if let Some(value) = file.rejects_file {
+            self.rejects_file = value;
+        }
+        if let Some(value) = file.follow {
+            self.follow = value;
+        }
+        if let Some(value) = file.follow_poll_ms {
+            self.follow_poll = Duration::from_millis(value);
+        }
+        if let Some(value) = file.checkpoint_file {
+            self.checkpoint_file = value;
+        }
+        if let Some(value) = file.reliable_messaging {
+            self.reliable_messaging = value;
+        }
+        if let Some(value) = file.retransmit_timeout_ms {
+            self.retransmit_timeout = Duration::from_millis(value);
+        }
+        if let Some(value) = file.max_attempts {
+            self.max_attempts = value;
+        }
+        if let Some(value) = file.replay_window_ms {
+            self.replay_window = Duration::from_millis(value);
+        }
+        if let Some(value) = file.log_level {
+            self.log_level = value;
+        }
+        if let Some(value) = file.log_format {
+            self.log_format = parse("log_format", &value)?;
+        }
+        if let Some(addr) = file.metrics_addr {
+            self.metrics_addr = Some(parse_addr("metrics_addr", &addr)?);
+        }
+        if let Some(path) = file.trace_file {
+            self.trace_file = Some(path);
+        }
+        if let Some(addr) = file.retry_addr {
+            self.topology.retry_addr = parse_addr("retry_addr", &addr)?;
+        }
+        if let Some(services) = file.services {
+            if let Some(addr) = services.airline {
+                self.topology.airline_addr = parse_addr("services.airline", &addr)?;
+            }
+            if let Some(addr) = services.hotel {
+                self.topology.hotel_addr = parse_addr("services.hotel", &addr)?;
+            }
+            if let Some(addr) = services.bank {
+                self.topology.bank_addr = parse_addr("services.bank", &addr)?;
+            }
+            let transports = [
+                (
+                    ServiceName::Airline,
+                    services.airline_transport,
+                    "services.airline_transport",
+                ),
+                (
+                    ServiceName::Hotel,
+                    services.hotel_transport,
+                    "services.hotel_transport",
+                ),
+                (
+                    ServiceName::Bank,
+                    services.bank_transport,
+                    "services.bank_transport",
+                ),
+            ];
+            for (service, transport_opt, key) in transports {
+                if let Some(transport) = transport_opt {
+                    self.set_transport(service, key, &transport)?;
+                }
+            }
+        }
+        if let Some(nodes) = file.nodes {
+            let mut parsed = Vec::with_capacity(nodes.len());
+            for node in nodes {
+                parsed.push(NodeAddrs {
+                    id: node.id,
+                    info_addr: parse_addr("nodes.info_addr", &node.info_addr)?,
+                    data_addr: parse_addr("nodes.data_addr", &node.data_addr)?,
+                    admin_addr: match node.admin_addr {
+                        Some(addr) => Some(parse_addr("nodes.admin_addr", &addr)?),
+                        None => None,
+                    },
+                    submit_addr: match node.submit_addr {
+                        Some(addr) => Some(parse_addr("nodes.submit_addr", &addr)?),
+                        None => None,
+                    },
+                });
+            }
+            self.topology.nodes = parsed;
+        }
+        if let Some(keys) = file.keys {
+            self.keys = parse_keys("keys", keys)?;
+        }
+        if let Some(psks) = file.psk {
+            self.psks = parse_keys("psk", psks)?;
+        }
+        Ok(())
+    }
+
+    fn apply_var(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key.trim_start_matches(ENV_PREFIX) {
+            "SLEEP_MANAGER_MS" => self.sleep_manager = Duration::from_millis(parse(key, value)?),
+            "END_TIMEOUT_MS" => self.end_timeout = Duration::from_millis(parse(key, value)?),
+            "MANAGER_TIMEOUT_MS" => {
+                self.manager_timeout = Duration::from_millis(parse(key, value)?);
+            }
+            "PERCENTAGE_ERROR" => self.percentage_error = parse(key, value)?,
+            "TRANSACTION_FILE" => self.transaction_file = value.to_string(),
+            "INPUT_FORMAT" => self.input_format = parse_input_format(key, value)?,
+            "ABORT_FILE" => self.abort_file = value.to_string(),
+            "REJECTS_FILE" => self.rejects_file = value.to_string(),
+            "FOLLOW" => self.follow = parse(key, value)?,
+            "FOLLOW_POLL_MS" => self.follow_poll = Duration::from_millis(parse(key, value)?),
+            "CHECKPOINT_FILE" => self.checkpoint_file = value.to_string(),
+            "RELIABLE_MESSAGING" => self.reliable_messaging = parse(key, value)?,
+            "RETRANSMIT_TIMEOUT_MS" => {
+                self.retransmit_timeout = Duration::from_millis(parse(key, value)?);
+            }
+            "MAX_ATTEMPTS" => self.max_attempts = parse(key, value)?,
+            "REPLAY_WINDOW_MS" => self.replay_window = Duration::from_millis(parse(key, value)?),
+            "LOG_LEVEL" => self.log_level = value.to_string(),
+            "LOG_FORMAT" => self.log_format = parse(key, value)?,
+            "METRICS_ADDR" => self.metrics_addr = Some(parse_addr(key, value)?),
+            "TRACE_FILE" => self.trace_file = Some(value.to_string()),
+            "RETRY_ADDR" => self.topology.retry_addr = parse_addr(key, value)?,
+            "AIRLINE_ADDR" => self.topology.airline_addr = parse_addr(key, value)?,
+            "HOTEL_ADDR" => self.topology.hotel_addr = parse_addr(key, value)?,
+            "BANK_ADDR" => self.topology.bank_addr = parse_addr(key, value)?,
+            "AIRLINE_TRANSPORT" => self.set_transport(ServiceName::Airline, key, value)?,
+            "HOTEL_TRANSPORT" => self.set_transport(ServiceName::Hotel, key, value)?,
+            "BANK_TRANSPORT" => self.set_transport(ServiceName::Bank, key, value)?,
+            // El archivo de configuracion ya se leyo en `from_env`
+            "CONFIG" => {}
+            _ => return Err(ConfigError::UnknownVar(key.to_string())),
+        }
+        Ok(())
+    }
+
+    fn set_transport(
+        &mut self,
+        service: ServiceName,
+        key: &str,
+        transport: &str,
+    ) -> Result<(), ConfigError> {
+        match transport {
+            "tcp" => self.topology.tcp_services.insert(service),
+            "udp" => self.topology.tcp_services.remove(&service),
+            _ => {
+                return Err(ConfigError::InvalidValue(
+                    key.to_string(),
+                    transport.to_string(),
+                ))
+            }
+        };
+        Ok(())
+    }
+
+    /// Parametros del canal confiable, si esta habilitado
+    #[must_use]
+    pub fn reliable(&self) -> Option<ReliableConfig> {
+        if !self.reliable_messaging {
+            return None;
+        }
+        Some(ReliableConfig {
+            retransmit_timeout: self.retransmit_timeout,
+            max_attempts: self.max_attempts,
+        })
+    }
+
+    /// Archivo de transacciones a procesar y donde registrar sus
+    /// filas invalidas
+    #[must_use]
+    pub fn transaction_source(&self) -> SourceSpec {
+        SourceSpec {
+            path: self.transaction_file.clone(),
+            format_opt: self.input_format,
+            rejects_file_opt: Some(self.rejects_file.clone()),
+            follow_opt: self.follow.then(|| FollowSpec {
+                poll: self.follow_poll,
+                checkpoint_file: self.checkpoint_file.clone(),
+            }),
+        }
+    }
+
+    /// Claves y ventana anti-replay para proteger los sockets
+    #[must_use]
+    pub fn security(&self) -> LinkSecurity {
+        LinkSecurity {
+            keys: self.keys.clone(),
+            replay_window: self.replay_window,
+            psks: self.psks.clone(),
+        }
+    }
+
+    /// Valida la configuracion. Se debe llamar nuevamente si se
+    /// modifican campos despues de cargarla.
+    ///
+    /// # Errors
+    ///
+    /// Devuelve un error describiendo el primer valor invalido
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.percentage_error > 100 {
+            return Err(ConfigError::Invalid(format!(
+                "percentage_error debe estar entre 0 y 100, se recibio {}",
+                self.percentage_error
+            )));
+        }
+        if self.end_timeout.is_zero() || self.manager_timeout.is_zero() {
+            return Err(ConfigError::Invalid(
+                "end_timeout_ms y manager_timeout_ms deben ser mayores a 0".to_string(),
+            ));
+        }
+        if self.reliable_messaging && (self.retransmit_timeout.is_zero() || self.max_attempts == 0)
+        {
+            return Err(ConfigError::Invalid(
+                "retransmit_timeout_ms y max_attempts deben ser mayores a 0".to_string(),
+            ));
+        }
+        if !self.keys.is_empty() && self.replay_window.is_zero() {
+            return Err(ConfigError::Invalid(
+                "replay_window_ms debe ser mayor a 0".to_string(),
+            ));
+        }
+        if let Err(err) = parse_filter(&self.log_level) {
+            return Err(ConfigError::Invalid(format!(
+                "log_level {} invalido: {}",
+                self.log_level, err
+            )));
+        }
+        if self.transaction_file.is_empty()
+            || self.abort_file.is_empty()
+            || self.rejects_file.is_empty()
+            || self.checkpoint_file.is_empty()
+        {
+            return Err(ConfigError::Invalid(
+                "transaction_file, abort_file, rejects_file y checkpoint_file no pueden estar vacios"
+                    .to_string(),
+            ));
+        }
+        if self.follow_poll.is_zero() {
+            return Err(ConfigError::Invalid(
+                "follow_poll_ms debe ser mayor a 0".to_string(),
+            ));
+        }
+        if self.topology.nodes.is_empty() {
+            return Err(ConfigError::Invalid(
+                "Debe haber al menos un nodo en la topologia".to_string(),
+            ));
+        }
+        let mut ids = HashSet::new();
+        let mut addrs = HashSet::new();
+        for node in &self.topology.nodes {
+            if !ids.insert(node.id) {
+                return Err(ConfigError::Invalid(format!(
+                    "El id de nodo {} esta repetido",
+                    node.id
+                )));
+            }
+            // Los pedidos de administracion y SUBMIT no se firman ni
+            // se cifran, por lo que solo se atienden en la maquina local
+            if let Some(addr) = node
+                .admin_addr
+                .into_iter()
+                .chain(node.submit_addr)
+                .find(|addr| !addr.ip().is_loopback())
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "La direccion {} del nodo {} no es local: admin_addr y submit_addr no se autentican",
+                    addr, node.id
+                )));
+            }
+        }
+        let all_addrs = self
+            .topology
+            .nodes
+            .iter()
+            .flat_map(|node| {
+                iter::once(node.info_addr)
+                    .chain(iter::once(node.data_addr))
+                    .chain(node.admin_addr)
+                    .chain(node.submit_addr)
+            })
+            .chain([
+                self.topology.airline_addr,
+                self.topology.hotel_addr,
+                self.topology.bank_addr,
+                self.topology.retry_addr,
+            ]);
+        for addr in all_addrs {
+            if !addrs.insert(addr) {
+                return Err(ConfigError::Invalid(format!(
+                    "La direccion {} esta asignada mas de una vez",
+                    addr
+                )));
+            }
+        }
+        // Un enlace solo tiene clave si la tienen sus dos extremos, por
+        // lo que una tabla no vacia debe cubrir todas las direcciones de
+        // nodos, servicios y `retry`
+        for (table, keys) in &[("keys", &self.keys), ("psk", &self.psks)] {
+            if keys.is_empty() {
+                continue;
+            }
+            let secured = self.topology.secured_addrs();
+            if let Some(addr) = secured.iter().find(|addr| !keys.contains(addr)) {
+                return Err(ConfigError::Invalid(format!(
+                    "La tabla [{}] no tiene clave para {}: debe cubrir todos los nodos, servicios y retry_addr",
+                    table, addr
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, ConfigError> {
+    value
+        .parse()
+        .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))
+}
+
+/// `auto` deja que el formato se deduzca de la extension del archivo
+fn parse_input_format(key: &str, value: &str) -> Result<Option<InputFormat>, ConfigError> {
+    if value == "auto" {
+        return Ok(None);
+    }
+    parse(key, value).map(Some)
+}
+
+fn parse_addr(key: &str, value: &str) -> Result<SocketAddr, ConfigError> {
+    parse(key, value)
+}
+
+fn parse_keys(table: &str, keys: HashMap<String, String>) -> Result<Keys, ConfigError> {
+    let mut parsed = HashMap::with_capacity(keys.len());
+    for (addr, key) in keys {
+        let addr = parse_addr(table, &addr)?;
+        // El valor no se incluye en el error para no filtrar la clave
+        match hex::decode(&key) {
+            Ok(bytes) if !bytes.is_empty() => parsed.insert(addr, bytes),
+            _ => {
+                return Err(ConfigError::Invalid(format!(
+                    "La clave de {} en [{}] debe ser hexadecimal y no vacia",
+                    addr, table
+                )))
+            }
+        };
+    }
+    Ok(Keys::new(parsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_vars() -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    #[test]
+    fn load_without_file_should_return_defaults() {
+        assert_eq!(Settings::load(None, no_vars()), Ok(Settings::default()));
+        assert_eq!(Settings::default().percentage_error, 20);
+    }
+
+    #[test]
+    fn the_example_config_should_load_with_the_default_error_rate() {
+        let settings = Settings::load(Some(include_str!("../../alglobo.toml")), no_vars()).unwrap();
+        assert_eq!(settings.percentage_error, PERCENTAGE_ERROR);
+    }
+
+    #[test]
+    fn load_should_override_defaults_with_file_and_then_env() {
+        let content = r#"
+            end_timeout_ms = 500
+            percentage_error = 10
+            abort_file = "abort.csv"
+            trace_file = "spans.json"
+
+            [services]
+            bank = "10.0.0.5:7000"
+            bank_transport = "tcp"
+
+            [[nodes]]
+            id = 1
+            info_addr = "10.0.0.1:6000"
+            data_addr = "10.0.0.1:6001"
+            admin_addr = "127.0.0.1:6002"
+            submit_addr = "127.0.0.1:6003"
+        "#;
+        let vars = vec![
+            ("ALGLOBO_PERCENTAGE_ERROR".to_string(), "0".to_string()),
+            (
+                "ALGLOBO_HOTEL_ADDR".to_string(),
+                "10.0.0.4:7000".to_string(),
+            ),
+        ];
+
+        let settings = Settings::load(Some(content), vars).unwrap();
+
+        assert_eq!(settings.end_timeout, Duration::from_millis(500));
+        assert_eq!(settings.percentage_error, 0);
+        assert_eq!(settings.abort_file, "abort.csv");
+        assert_eq!(settings.trace_file, Some("spans.json".to_string()));
+        assert_eq!(
+            settings.topology.bank_addr,
+            "10.0.0.5:7000".parse().unwrap()
+        );
+        assert_eq!(
+            settings.topology.hotel_addr,
+            "10.0.0.4:7000".parse().unwrap()
+        );
+        assert_eq!(
+            settings.topology.nodes,
+            vec![NodeAddrs {
+                id: 1,
+                info_addr: "10.0.0.1:6000".parse().unwrap(),
+                data_addr: "10.0.0.1:6001".parse().unwrap(),
+                admin_addr: Some("127.0.0.1:6002".parse().unwrap()),
+                submit_addr: Some("127.0.0.1:6003".parse().unwrap()),
+            }]
+        );
+    }
+
+    #[test]
+    fn load_should_reject_unknown_keys_and_invalid_values() {
+        assert!(matches!(
+            Settings::load(Some("timeout = 3"), no_vars()),
+            Err(ConfigError::Parse(_))
+        ));
+        assert_eq!(
+            Settings::load(
+                None,
+                vec![("ALGLOBO_END_TIMEOUT_MS".to_string(), "diez".to_string())]
+            ),
+            Err(ConfigError::InvalidValue(
+                "ALGLOBO_END_TIMEOUT_MS".to_string(),
+                "diez".to_string()
+            ))
+        );
+        assert!(matches!(
+            Settings::load(Some("percentage_error = 101"), no_vars()),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn load_should_reject_unknown_variables_except_the_config_file() {
+        let vars = vec![("ALGLOBO_CONFIG".to_string(), "alglobo.toml".to_string())];
+        assert_eq!(Settings::load(None, vars), Ok(Settings::default()));
+
+        let vars = vec![("ALGLOBO_END_TIMEOUT".to_string(), "10".to_string())];
+        assert_eq!(
+            Settings::load(None, vars),
+            Err(ConfigError::UnknownVar("ALGLOBO_END_TIMEOUT".to_string()))
+        );
+    }
+
+    #[test]
+    fn load_should_parse_log_settings() {
+        let vars = vec![("ALGLOBO_LOG_FORMAT".to_string(), "json".to_string())];
+
+        let settings = Settings::load(Some("log_level = \"debug\""), vars).unwrap();
+
+        assert_eq!(settings.log_level, "debug");
+        assert_eq!(settings.log_format, LogFormat::Json);
+        assert!(matches!(
+            Settings::load(Some("log_level = \"tp=ruidoso\""), no_vars()),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn load_should_parse_the_metrics_addr() {
+        assert_eq!(Settings::default().metrics_addr, None);
+
+        let vars = vec![(
+            "ALGLOBO_METRICS_ADDR".to_string(),
+            "127.0.0.1:9100".to_string(),
+        )];
+        let settings = Settings::load(Some("metrics_addr = \"127.0.0.1:9000\""), vars).unwrap();
+
+        assert_eq!(
+            settings.metrics_addr,
+            Some("127.0.0.1:9100".parse().unwrap())
+        );
+        assert!(matches!(
+            Settings::load(Some("metrics_addr = \"localhost\""), no_vars()),
+            Err(ConfigError::InvalidValue(_, _))
+        ));
+    }
+
+    #[test]
+    fn transaction_source_should_use_the_configured_format() {
+        let settings = Settings::load(
+            Some("transaction_file = \"data/reservas.jsonl\""),
+            no_vars(),
+        )
+        .unwrap();
+        assert_eq!(
+            settings.transaction_source().format(),
+            InputFormat::JsonLines
+        );
+
+        let vars = vec![("ALGLOBO_INPUT_FORMAT".to_string(), "csv".to_string())];
+        let settings = Settings::load(Some("input_format = \"jsonl\""), vars).unwrap();
+
+        assert_eq!(
+            settings.transaction_source(),
+            SourceSpec {
+                path: TRANSACTION_FILE.to_string(),
+                format_opt: Some(InputFormat::Csv),
+                rejects_file_opt: Some(REJECTS_FILE.to_string()),
+                follow_opt: None,
+            }
+        );
+
+        let vars = vec![("ALGLOBO_FOLLOW".to_string(), "true".to_string())];
+        let settings = Settings::load(Some("follow_poll_ms = 50"), vars).unwrap();
+
+        assert_eq!(
+            settings.transaction_source().follow_opt,
+            Some(FollowSpec {
+                poll: Duration::from_millis(50),
+                checkpoint_file: CHECKPOINT_FILE.to_string(),
+            })
+        );
+        assert!(matches!(
+            Settings::load(Some("input_format = \"xml\""), no_vars()),
+            Err(ConfigError::InvalidValue(_, _))
+        ));
+    }
+
+    #[test]
+    fn load_should_reject_a_submit_addr_shared_by_two_nodes() {
+        // Cada nodo escucha pedidos SUBMIT en su propia direccion
+        let content = r#"
+            [[nodes]]
+            id = 1
+            info_addr = "127.0.0.1:49353"
+            data_addr = "127.0.0.1:49152"
+            submit_addr = "127.0.0.1:9300"
+
+            [[nodes]]
+            id = 2
+            info_addr = "127.0.0.1:49354"
+            data_addr = "127.0.0.1:49153"
+            submit_addr = "127.0.0.1:9300"
+        "#;
+
+        assert!(matches!(
+            Settings::load(Some(content), no_vars()),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn reliable_should_be_enabled_only_with_reliable_messaging() {
+        assert_eq!(Settings::default().reliable(), None);
+
+        let settings = Settings::load(
+            Some("reliable_messaging = true\nmax_attempts = 2"),
+            no_vars(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            settings.reliable(),
+            Some(ReliableConfig {
+                retransmit_timeout: RETRANSMIT_TIMEOUT,
+                max_attempts: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn load_should_reject_repeated_addresses() {
+        let content = r#"
+            [services]
+            airline = "127.0.0.1:59354"
+        "#;
+
+        assert!(matches!(
+            Settings::load(Some(content), no_vars()),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn load_should_reject_admin_and_submit_addresses_that_are_not_local() {
+        for field in ["admin_addr", "submit_addr"] {
+            let content = format!(
+                "[[nodes]]\nid = 1\ninfo_addr = \"10.0.0.1:6000\"\ndata_addr = \"10.0.0.1:6001\"\n{} = \"10.0.0.1:6002\"\n",
+                field
+            );
+
+            assert!(matches!(
+                Settings::load(Some(&content), no_vars()),
+                Err(ConfigError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn load_should_take_the_retry_identity_from_file_and_env() {
+        let vars = vec![(
+            "ALGLOBO_RETRY_ADDR".to_string(),
+            "127.0.0.1:9200".to_string(),
+        )];
+        let settings = Settings::load(Some("retry_addr = \"127.0.0.1:9100\""), no_vars()).unwrap();
+        assert_eq!(
+            settings.topology.retry_addr,
+            "127.0.0.1:9100".parse().unwrap()
+        );
+
+        let settings = Settings::load(Some("retry_addr = \"127.0.0.1:9100\""), vars).unwrap();
+        assert_eq!(
+            settings.topology.retry_addr,
+            "127.0.0.1:9200".parse().unwrap()
+        );
+        assert!(matches!(
+            Settings::load(Some("retry_addr = \"127.0.0.1:59353\""), no_vars()),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    /// Tabla con la misma clave para todas las direcciones por defecto
+    fn key_table(table: &str, key: &str) -> String {
+        Topology::default()
+            .secured_addrs()
+            .iter()
+            .fold(format!("[{}]\n", table), |content, addr| {
+                format!("{}\"{}\" = \"{}\"\n", content, addr, key)
+            })
+    }
+
+    #[test]
+    fn load_should_parse_hex_keys_and_reject_invalid_ones() {
+        let content = format!(
+            "{}\n{}",
+            key_table("keys", "00ff10"),
+            key_table("psk", "0a0b")
+        );
+
+        let settings = Settings::load(Some(&content), no_vars()).unwrap();
+
+        let table = |key: Vec<u8>| {
+            Keys::new(
+                Topology::default()
+                    .secured_addrs()
+                    .into_iter()
+                    .map(|addr| (addr, key.clone()))
+                    .collect(),
+            )
+        };
+        assert_eq!(settings.keys, table(vec![0x00, 0xff, 0x10]));
+        assert_eq!(settings.psks, table(vec![0x0a, 0x0b]));
+        assert!(matches!(
+            Settings::load(Some(&key_table("keys", "xyz")), no_vars()),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn load_should_reject_key_tables_that_leave_addresses_without_key() {
+        // Como en el ejemplo anterior de alglobo.toml: el hotel y el
+        // banco quedaban sin clave y aceptaban mensajes sin firmar
+        let partial = "[keys]\n\"127.0.0.1:59353\" = \"00ff\"\n\"127.0.0.1:49152\" = \"00ff\"\n";
+        let without_bank = key_table("psk", "0a0b").replace("\"127.0.0.1:59355\" = \"0a0b\"\n", "");
+
+        for content in [partial.to_string(), without_bank].iter() {
+            assert!(matches!(
+                Settings::load(Some(content), no_vars()),
+                Err(ConfigError::Invalid(_))
+            ));
+        }
+    }
+}
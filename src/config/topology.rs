@@ -0,0 +1,169 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+};
+
+use crate::services::service_name::ServiceName;
+
+/// Direcciones de un nodo de AlGlobo: la del socket de eleccion
+/// (info), la del socket de transacciones (data) y, si se usan, la
+/// del puerto de administracion y la de pedidos SUBMIT
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct NodeAddrs {
+    pub id: u64,
+    pub info_addr: SocketAddr,
+    pub data_addr: SocketAddr,
+    pub admin_addr: Option<SocketAddr>,
+    pub submit_addr: Option<SocketAddr>,
+}
+
+/// Direcciones de todos los nodos y servicios del sistema.
+/// Ninguna direccion se deriva de otra, por lo que cada
+/// participante puede estar en una maquina distinta.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Topology {
+    pub nodes: Vec<NodeAddrs>,
+    pub airline_addr: SocketAddr,
+    pub hotel_addr: SocketAddr,
+    pub bank_addr: SocketAddr,
+    /// Identidad con la que firma y cifra el binario `retry`, que
+    /// envia desde un puerto efimero
+    pub retry_addr: SocketAddr,
+    /// Servicios a los que se accede por TCP en lugar de UDP
+    pub tcp_services: HashSet<ServiceName>,
+}
+
+impl Topology {
+    #[must_use]
+    pub fn node(&self, id: u64) -> Option<NodeAddrs> {
+        self.nodes.iter().find(|node| node.id == id).copied()
+    }
+
+    /// Direcciones de datos de todas las replicas
+    #[must_use]
+    pub fn replicas_addrs(&self) -> Vec<SocketAddr> {
+        self.nodes.iter().map(|node| node.data_addr).collect()
+    }
+
+    /// Direcciones de los sockets de nodos y servicios, cuyos mensajes
+    /// se firman y cifran con las tablas de claves
+    #[must_use]
+    pub fn secured_addrs(&self) -> Vec<SocketAddr> {
+        self.nodes
+            .iter()
+            .flat_map(|node| vec![node.info_addr, node.data_addr])
+            .chain(vec![
+                self.airline_addr,
+                self.hotel_addr,
+                self.bank_addr,
+                self.retry_addr,
+            ])
+            .collect()
+    }
+
+    #[must_use]
+    pub fn services_addrs(&self) -> HashMap<SocketAddr, String> {
+        HashMap::from([
+            (self.airline_addr, ServiceName::Airline.string_name()),
+            (self.hotel_addr, ServiceName::Hotel.string_name()),
+            (self.bank_addr, ServiceName::Bank.string_name()),
+        ])
+    }
+
+    #[must_use]
+    pub fn uses_tcp(&self, service: ServiceName) -> bool {
+        self.tcp_services.contains(&service)
+    }
+
+    /// Direcciones de los servicios a los que se accede por TCP
+    #[must_use]
+    pub fn tcp_addrs(&self) -> HashSet<SocketAddr> {
+        self.tcp_services
+            .iter()
+            .map(|service| self.service_addr(*service))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn service_addr(&self, service: ServiceName) -> SocketAddr {
+        match service {
+            ServiceName::Airline => self.airline_addr,
+            ServiceName::Hotel => self.hotel_addr,
+            ServiceName::Bank => self.bank_addr,
+        }
+    }
+}
+
+impl Default for Topology {
+    /// Tres nodos y los tres servicios en la maquina local
+    fn default() -> Self {
+        let addr = |port: u16| SocketAddr::from(([127, 0, 0, 1], port));
+        Topology {
+            nodes: vec![
+                NodeAddrs {
+                    id: 1,
+                    info_addr: addr(49353),
+                    data_addr: addr(49152),
+                    admin_addr: None,
+                    submit_addr: None,
+                },
+                NodeAddrs {
+                    id: 2,
+                    info_addr: addr(49354),
+                    data_addr: addr(49153),
+                    admin_addr: None,
+                    submit_addr: None,
+                },
+                NodeAddrs {
+                    id: 3,
+                    info_addr: addr(49355),
+                    data_addr: addr(49154),
+                    admin_addr: None,
+                    submit_addr: None,
+                },
+            ],
+            airline_addr: addr(59353),
+            hotel_addr: addr(59354),
+            bank_addr: addr(59355),
+            retry_addr: addr(59356),
+            tcp_services: HashSet::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn services_addrs_should_map_each_address_to_its_service_name() {
+        let topology = Topology::default();
+        let services_addrs = topology.services_addrs();
+
+        assert_eq!(
+            services_addrs.get(&topology.service_addr(ServiceName::Hotel)),
+            Some(&ServiceName::Hotel.string_name())
+        );
+        assert_eq!(services_addrs.len(), 3);
+    }
+
+    #[test]
+    fn tcp_addrs_should_contain_only_tcp_services() {
+        let mut topology = Topology::default();
+        topology.tcp_services.insert(ServiceName::Bank);
+
+        assert!(topology.uses_tcp(ServiceName::Bank));
+        assert_eq!(
+            topology.tcp_addrs(),
+            HashSet::from([topology.service_addr(ServiceName::Bank)])
+        );
+    }
+
+    #[test]
+    fn node_should_find_nodes_by_id() {
+        let topology = Topology::default();
+
+        assert_eq!(topology.node(2), Some(topology.nodes[1]));
+        assert_eq!(topology.node(4), None);
+    }
+}
@@ -0,0 +1,74 @@
+use std::fs;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+/// Posicion guardada del archivo de transacciones
+#[derive(Serialize, Deserialize)]
+struct Saved {
+    path: String,
+    offset: u64,
+}
+
+/// Archivo en el que el lider guarda hasta donde leyo el archivo de
+/// transacciones, para que la lectura se retome desde ahi aunque
+/// el lider se caiga o se reinicie el sistema
+#[derive(Debug, PartialEq, Clone)]
+pub struct Checkpoint {
+    path: String,
+}
+
+impl Checkpoint {
+    #[must_use]
+    pub fn new(path: &str) -> Self {
+        Checkpoint {
+            path: path.to_string(),
+        }
+    }
+
+    /// Offset guardado para el archivo de transacciones `source`. Se
+    /// ignora el checkpoint si corresponde a otro archivo.
+    #[must_use]
+    pub fn load(&self, source: &str) -> Option<u64> {
+        let content = fs::read_to_string(&self.path).ok()?;
+        let saved: Saved = serde_json::from_str(&content).ok()?;
+        if saved.path != source {
+            return None;
+        }
+        Some(saved.offset)
+    }
+
+    /// Guarda el offset en un archivo temporal y lo renombra, para que
+    /// una caida a mitad de la escritura no deje un checkpoint corrupto
+    /// # Errors:
+    /// Arroja error si no es posible escribir el checkpoint
+    pub fn save(&self, source: &str, offset: u64) -> io::Result<()> {
+        let saved = Saved {
+            path: source.to_string(),
+            offset,
+        };
+        let tmp_path = format!("{}.tmp", self.path);
+        fs::write(&tmp_path, serde_json::to_string(&saved)?)?;
+        fs::rename(tmp_path, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn it_should_load_the_offset_saved_for_the_same_file() {
+        let path = env::temp_dir().join(format!("alglobo_{}_checkpoint.json", std::process::id()));
+        let checkpoint = Checkpoint::new(&path.to_string_lossy());
+
+        assert_eq!(checkpoint.load("data/data.csv"), None);
+        checkpoint.save("data/data.csv", 10).unwrap();
+        checkpoint.save("data/data.csv", 25).unwrap();
+
+        assert_eq!(checkpoint.load("data/data.csv"), Some(25));
+        assert_eq!(checkpoint.load("data/otro.csv"), None);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,73 @@
+/// Separa un registro CSV en sus campos. Los campos pueden ir entre
+/// comillas dobles para incluir comas, saltos de linea o comillas
+/// (escritas como `""`). Devuelve `None` si queda una comilla sin
+/// cerrar, es decir, si el registro continua en la proxima linea.
+#[must_use]
+pub fn parse_record(record: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    fields.push(field);
+    Some(fields)
+}
+
+/// Escribe `field` como campo CSV, entre comillas si hace falta
+#[must_use]
+pub fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_split_quoted_and_unquoted_fields() {
+        assert_eq!(
+            parse_record("7,\"1,5\",\"dice \"\"hola\"\"\","),
+            Some(vec![
+                "7".to_string(),
+                "1,5".to_string(),
+                "dice \"hola\"".to_string(),
+                String::new(),
+            ])
+        );
+    }
+
+    #[test]
+    fn it_should_detect_an_unterminated_quote() {
+        assert_eq!(parse_record("7,\"sin cerrar"), None);
+        assert_eq!(
+            parse_record("7,\"dos\nlineas\""),
+            Some(vec!["7".to_string(), "dos\nlineas".to_string()])
+        );
+    }
+
+    #[test]
+    fn quote_should_round_trip_through_parse_record() {
+        let field = "motivo \"raro\", con coma";
+
+        assert_eq!(parse_record(&quote(field)), Some(vec![field.to_string()]));
+        assert_eq!(quote("simple"), "simple");
+    }
+}
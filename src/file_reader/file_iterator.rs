@@ -1,14 +1,106 @@
 use std::io::prelude::*;
+use std::io::SeekFrom;
 use std::iter::Iterator;
 
 use std::collections::HashMap;
 use std::{fs::File, io::BufReader};
 
+use tracing::warn;
+
+use super::csv_record::parse_record;
+use super::rejects::Rejects;
+use super::transaction_source::TransactionSource;
 use crate::alglobo::transaction::Transaction;
+use crate::services::service_name::ServiceName;
+
+const SERVICES: [ServiceName; 3] = [ServiceName::Airline, ServiceName::Hotel, ServiceName::Bank];
+
+/// Posicion de cada columna en los registros del archivo
+#[derive(Debug, PartialEq)]
+struct Columns {
+    id: usize,
+    fees: [usize; 3],
+    len: usize,
+}
+
+impl Columns {
+    /// Sin encabezado las columnas son id, aerolinea, hotel y banco
+    fn positional() -> Self {
+        Columns {
+            id: 0,
+            fees: [1, 2, 3],
+            len: 4,
+        }
+    }
+
+    /// Columnas de un encabezado con los nombres `id`, `airline`,
+    /// `hotel` y `bank` en cualquier orden. Las demas se ignoran.
+    fn from_header(fields: &[String]) -> Result<Self, String> {
+        let names: Vec<String> = fields.iter().map(|f| f.trim().to_lowercase()).collect();
+        let position = |name: &str| {
+            names
+                .iter()
+                .position(|other| other == name)
+                .ok_or(format!("encabezado sin la columna {}", name))
+        };
+        let mut fees = [0; 3];
+        for (fee, service) in fees.iter_mut().zip(SERVICES) {
+            *fee = position(&service.string_name().to_lowercase())?;
+        }
+        Ok(Columns {
+            id: position("id")?,
+            fees,
+            len: fields.len(),
+        })
+    }
+
+    fn transaction(&self, fields: &[String]) -> Result<Transaction, String> {
+        if fields.len() != self.len {
+            return Err(format!(
+                "se esperaban {} columnas y hay {}",
+                self.len,
+                fields.len()
+            ));
+        }
+        let id = fields[self.id].trim();
+        let id = id
+            .parse::<u64>()
+            .map_err(|_| format!("id invalido: {}", id))?;
+        let mut services_info: HashMap<String, f64> = HashMap::new();
+        for (index, service) in self.fees.iter().zip(SERVICES) {
+            let fee = fields[*index].trim();
+            match fee.parse::<f64>() {
+                Ok(value) if value.is_finite() => {
+                    services_info.insert(service.string_name(), value);
+                }
+                _ => {
+                    return Err(format!(
+                        "monto de {} invalido: {}",
+                        service.string_name(),
+                        fee
+                    ))
+                }
+            }
+        }
+        Ok(Transaction::new(id, &services_info))
+    }
+}
+
+/// Registro leido del archivo, con el numero de su primera linea
+struct Record {
+    line: u64,
+    content: String,
+    fields: Result<Vec<String>, String>,
+}
 
 pub struct FileIterator {
     reader: BufReader<File>,
     ended: bool,
+    offset: u64,
+    line: u64,
+    columns_opt: Option<Columns>,
+    rejects: Rejects,
+    follow: bool,
 }
 
 impl FileIterator {
@@ -22,48 +114,315 @@ impl FileIterator {
             return Ok(FileIterator {
                 reader: BufReader::new(file),
                 ended: false,
+                offset: 0,
+                line: 0,
+                columns_opt: None,
+                rejects: Rejects::default(),
+                follow: false,
             });
         }
         Err("[Sistema Error] Archivo de reservas no encontrado".to_string())
     }
 
-    /// La funcion devuelve el atributo ended
+    /// Agrega las filas invalidas al archivo CSV `path`, con el numero
+    /// de linea, el motivo y el contenido de cada una
+    /// # Errors:
+    /// Arroja error si no es posible abrir el archivo de rechazos
+    pub fn with_rejects(mut self, path: &str) -> Result<FileIterator, String> {
+        self.rejects = Rejects::open(path)?;
+        Ok(self)
+    }
+
+    /// Modo seguimiento: el archivo puede estar siendo escrito, por lo
+    /// que un registro sin salto de linea final no se consume hasta
+    /// que se complete
     #[must_use]
-    pub fn ended(&self) -> bool {
+    pub fn following(mut self) -> FileIterator {
+        self.follow = true;
+        self
+    }
+
+    /// Lee el proximo registro, que ocupa mas de una linea si tiene
+    /// un campo entre comillas con saltos de linea
+    fn read_record(&mut self) -> Option<Record> {
+        let mut bytes = Vec::new();
+        let (start, line) = (self.offset, self.line + 1);
+        loop {
+            let len = match self.reader.read_until(b'\n', &mut bytes) {
+                Ok(len) => len,
+                Err(err) => {
+                    warn!(error = %err, "No se pudo leer el archivo de reservas");
+                    0
+                }
+            };
+            if len == 0 && bytes.is_empty() {
+                self.ended = true;
+                return None;
+            }
+            if self.follow && (len == 0 || !bytes.ends_with(b"\n")) {
+                self.rewind(start, line - 1);
+                return None;
+            }
+            self.offset += len as u64;
+            if len > 0 {
+                self.line += 1;
+            }
+            let content = match String::from_utf8(bytes.clone()) {
+                Ok(value) => value,
+                Err(_) => {
+                    return Some(Record {
+                        line,
+                        content: String::from_utf8_lossy(&bytes).trim_end().to_string(),
+                        fields: Err("la linea no es UTF-8 valido".to_string()),
+                    })
+                }
+            };
+            let content = content.trim_end_matches(['\n', '\r']);
+            match parse_record(content) {
+                Some(fields) => {
+                    return Some(Record {
+                        line,
+                        content: content.to_string(),
+                        fields: Ok(fields),
+                    })
+                }
+                None if len == 0 => {
+                    return Some(Record {
+                        line,
+                        content: content.to_string(),
+                        fields: Err("comilla sin cerrar".to_string()),
+                    })
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Vuelve al comienzo de un registro incompleto
+    fn rewind(&mut self, offset: u64, line: u64) {
+        if let Err(err) = self.reader.seek(SeekFrom::Start(offset)) {
+            warn!(error = %err, "No se pudo posicionar el archivo de reservas");
+        }
+        self.offset = offset;
+        self.line = line;
+        self.ended = true;
+    }
+
+    /// Con el primer registro se decide si el archivo tiene
+    /// encabezado. Devuelve verdadero si `fields` era el encabezado.
+    fn detect_columns(&mut self, fields: &[String]) -> Result<bool, String> {
+        if self.columns_opt.is_some() {
+            return Ok(false);
+        }
+        if fields[0].trim().parse::<u64>().is_ok() {
+            self.columns_opt = Some(Columns::positional());
+            return Ok(false);
+        }
+        match Columns::from_header(fields) {
+            Ok(columns) => {
+                self.columns_opt = Some(columns);
+                Ok(true)
+            }
+            Err(reason) => {
+                self.columns_opt = Some(Columns::positional());
+                Err(reason)
+            }
+        }
+    }
+}
+
+impl TransactionSource for FileIterator {
+    fn ended(&self) -> bool {
         self.ended
     }
+
+    fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Se relee el archivo hasta `offset` para conocer el encabezado
+    /// y el numero de linea
+    fn seek(&mut self, offset: u64) -> Result<(), String> {
+        let err_msg = "[Sistema Error] No se pudo posicionar el archivo de reservas".to_string();
+        if self.reader.seek(SeekFrom::Start(0)).is_err() {
+            return Err(err_msg);
+        }
+        self.offset = 0;
+        self.line = 0;
+        self.columns_opt = None;
+        self.ended = false;
+        while self.offset < offset {
+            match self.read_record() {
+                Some(record) => {
+                    if let Ok(fields) = record.fields {
+                        let _drop = self.detect_columns(&fields);
+                    }
+                }
+                None => break,
+            }
+        }
+        if self.offset != offset {
+            return Err(err_msg);
+        }
+        Ok(())
+    }
 }
 
 impl Iterator for FileIterator {
     type Item = Transaction;
 
     /// Implementacion del metodo next de la interfaz Iterador, para la clase
-    /// `FileIterator`
-    /// #Errors
-    /// Arroja error si el archivo termino
+    /// `FileIterator`. Saltea las lineas vacias y el encabezado, y
+    /// rechaza las filas invalidas en lugar de cortar la lectura.
+    /// Devuelve `None` cuando el archivo termino
     fn next(&mut self) -> Option<Transaction> {
-        let mut line = String::new();
-        let len = self
-            .reader
-            .read_line(&mut line)
-            .expect("Read line should not fail");
-        if len == 0 {
-            self.ended = true;
-            return None;
+        loop {
+            let record = self.read_record()?;
+            let fields = match record.fields {
+                Ok(fields) => fields,
+                Err(reason) => {
+                    self.rejects.reject(record.line, &record.content, &reason);
+                    continue;
+                }
+            };
+            if fields.iter().all(|field| field.trim().is_empty()) {
+                continue;
+            }
+            match self.detect_columns(&fields) {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(reason) => {
+                    self.rejects.reject(record.line, &record.content, &reason);
+                    continue;
+                }
+            }
+            let result = self
+                .columns_opt
+                .as_ref()
+                .expect("[File Iterator] Las columnas deberian estar definidas")
+                .transaction(&fields);
+            match result {
+                Ok(transaction) => return Some(transaction),
+                Err(reason) => self.rejects.reject(record.line, &record.content, &reason),
+            }
         }
-        line = line.replace("\n", "");
-        let params: Vec<&str> = line.split(',').collect();
-        if params.len() < 4 {
-            return None;
-        }
-        let mut services_info: HashMap<String, f64> = HashMap::new();
-        services_info.insert("Airline".to_string(), params[1].parse::<f64>().unwrap());
-        services_info.insert("Hotel".to_string(), params[2].parse::<f64>().unwrap());
-        services_info.insert("Bank".to_string(), params[3].parse::<f64>().unwrap());
-
-        Some(Transaction::new(
-            params[0].parse::<u64>().unwrap(),
-            &services_info,
-        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alglobo::transactionable::Transactionable;
+    use std::fs::OpenOptions;
+    use std::{env, fs};
+
+    fn write_file(name: &str, content: &str) -> String {
+        let path = env::temp_dir().join(format!("alglobo_{}_{}", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    fn ids(iterator: FileIterator) -> Vec<u64> {
+        iterator.map(|transaction| transaction.get_id()).collect()
+    }
+
+    #[test]
+    fn it_should_read_a_header_with_named_columns_quoting_and_crlf() {
+        let path = write_file(
+            "header.csv",
+            "Bank,id,Hotel,Airline,nota\r\n\
+             1,7,\"2\",3,\"con, coma\"\r\n\
+             \r\n\
+             4,8,5,6,\"dos\nlineas\"\r\n",
+        );
+
+        let mut iterator = FileIterator::new(&path).unwrap();
+        let first = iterator.next().unwrap();
+
+        assert_eq!(first.get_id(), 7);
+        assert_eq!(
+            first.representation(false),
+            Transaction::new(
+                7,
+                &HashMap::from([
+                    ("Airline".to_string(), 3.0),
+                    ("Hotel".to_string(), 2.0),
+                    ("Bank".to_string(), 1.0),
+                ]),
+            )
+            .representation(false)
+        );
+        assert_eq!(ids(iterator), vec![8]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn it_should_report_malformed_rows_with_their_line_and_continue() {
+        let path = write_file(
+            "malformed.csv",
+            "0,1,2,3\n1,2,3\nx,1,2,3\n2,1,dos,3\n3,1,2,3\n4,\"1,2,3\n",
+        );
+        let rejects = write_file("malformed_rejects.csv", "");
+
+        let iterator = FileIterator::new(&path)
+            .unwrap()
+            .with_rejects(&rejects)
+            .unwrap();
+
+        assert_eq!(ids(iterator), vec![0, 3]);
+        assert_eq!(
+            fs::read_to_string(&rejects).unwrap(),
+            "2,se esperaban 4 columnas y hay 3,\"1,2,3\"\n\
+             3,id invalido: x,\"x,1,2,3\"\n\
+             4,monto de Hotel invalido: dos,\"2,1,dos,3\"\n\
+             6,comilla sin cerrar,\"4,\"\"1,2,3\"\n"
+        );
+        fs::remove_file(path).unwrap();
+        fs::remove_file(rejects).unwrap();
+    }
+
+    #[test]
+    fn following_should_wait_for_records_to_be_complete() {
+        let path = write_file(
+            "follow.csv",
+            "id,airline,hotel,bank,nota\n0,1,2,3,x\n1,1,2,3,\"dos\n",
+        );
+        let mut iterator = FileIterator::new(&path).unwrap().following();
+
+        assert_eq!(iterator.next().map(|t| t.get_id()), Some(0));
+        assert_eq!(iterator.next().map(|t| t.get_id()), None);
+        assert_eq!(iterator.offset(), 37);
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"lineas\"\n2,1,2").unwrap();
+        assert_eq!(iterator.next().map(|t| t.get_id()), Some(1));
+        assert_eq!(iterator.next().map(|t| t.get_id()), None);
+        file.write_all(b",3,x\n").unwrap();
+        assert_eq!(ids(iterator), vec![2]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn seek_should_keep_the_header_and_the_line_numbers() {
+        let path = write_file(
+            "seek.csv",
+            "id,airline,hotel,bank\n0,1,2,3\n1,1,2\n2,1,2,3\n",
+        );
+        let rejects = write_file("seek_rejects.csv", "");
+        let mut iterator = FileIterator::new(&path).unwrap();
+        iterator.next();
+        let offset = iterator.offset();
+
+        let mut resumed = FileIterator::new(&path)
+            .unwrap()
+            .with_rejects(&rejects)
+            .unwrap();
+        resumed.seek(offset).unwrap();
+
+        assert_eq!(ids(resumed), vec![2]);
+        assert!(fs::read_to_string(&rejects).unwrap().starts_with("3,"));
+        assert!(iterator.seek(offset + 1).is_err());
+        fs::remove_file(path).unwrap();
+        fs::remove_file(rejects).unwrap();
     }
 }
@@ -0,0 +1,59 @@
+use std::path::Path;
+use std::str::FromStr;
+
+/// Formato del archivo de transacciones
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum InputFormat {
+    /// CSV con id y montos, con encabezado opcional
+    Csv,
+    /// Un objeto JSON por linea
+    JsonLines,
+}
+
+impl InputFormat {
+    /// Formato segun la extension de `path`: `.jsonl` y `.ndjson`
+    /// son JSON Lines y cualquier otra se lee como CSV
+    #[must_use]
+    pub fn from_path(path: &str) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("jsonl") | Some("ndjson") => InputFormat::JsonLines,
+            _ => InputFormat::Csv,
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "csv" => Ok(InputFormat::Csv),
+            "jsonl" | "ndjson" => Ok(InputFormat::JsonLines),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_pick_the_format_by_extension_or_name() {
+        assert_eq!(
+            InputFormat::from_path("data/reservas.jsonl"),
+            InputFormat::JsonLines
+        );
+        assert_eq!(
+            InputFormat::from_path("data/reservas.NDJSON"),
+            InputFormat::JsonLines
+        );
+        assert_eq!(InputFormat::from_path("data/data.csv"), InputFormat::Csv);
+        assert_eq!("jsonl".parse(), Ok(InputFormat::JsonLines));
+        assert_eq!("xml".parse::<InputFormat>(), Err(()));
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::{fs::File, io::BufReader};
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tracing::warn;
+
+use super::rejects::Rejects;
+use super::transaction_source::TransactionSource;
+use crate::alglobo::{booking::Booking, transaction::Transaction};
+use crate::services::service_name::ServiceName;
+
+const SERVICES: [ServiceName; 3] = [ServiceName::Airline, ServiceName::Hotel, ServiceName::Bank];
+
+/// Una reserva por linea, por ejemplo
+/// `{"id":7,"fees":{"airline":3,"hotel":2,"bank":1},"currency":"ARS"}`.
+/// Los campos desconocidos se ignoran.
+#[derive(Deserialize)]
+struct JsonBooking {
+    id: u64,
+    fees: HashMap<String, f64>,
+    #[serde(default)]
+    currency: Option<String>,
+    #[serde(default)]
+    customer: Option<String>,
+    #[serde(default)]
+    metadata: Map<String, Value>,
+}
+
+impl JsonBooking {
+    fn transaction(self) -> Result<Transaction, String> {
+        let mut services_info: HashMap<String, f64> = HashMap::new();
+        for (name, fee) in &self.fees {
+            let service = SERVICES
+                .iter()
+                .find(|service| service.string_name().eq_ignore_ascii_case(name))
+                .ok_or(format!("servicio desconocido: {}", name))?;
+            if !fee.is_finite() {
+                return Err(format!(
+                    "monto de {} invalido: {}",
+                    service.string_name(),
+                    fee
+                ));
+            }
+            services_info.insert(service.string_name(), *fee);
+        }
+        if let Some(service) = SERVICES
+            .iter()
+            .find(|service| !services_info.contains_key(&service.string_name()))
+        {
+            return Err(format!("falta el monto de {}", service.string_name()));
+        }
+        if let Some(currency) = &self.currency {
+            if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+                return Err(format!("moneda invalida: {}", currency));
+            }
+        }
+        let booking = Booking {
+            currency: self.currency,
+            customer: self.customer,
+            metadata: self.metadata,
+        };
+        Ok(Transaction::new(self.id, &services_info).with_booking(booking))
+    }
+}
+
+/// Interpreta una reserva en formato JSON, la misma que ocupa una
+/// linea de un archivo JSON Lines
+/// # Errors:
+/// Devuelve el motivo si la reserva es invalida
+pub fn parse_booking(content: &str) -> Result<Transaction, String> {
+    serde_json::from_str::<JsonBooking>(content)
+        .map_err(|err| format!("JSON invalido: {}", err))
+        .and_then(JsonBooking::transaction)
+}
+
+pub struct JsonLinesIterator {
+    reader: BufReader<File>,
+    ended: bool,
+    offset: u64,
+    line: u64,
+    rejects: Rejects,
+    follow: bool,
+}
+
+impl JsonLinesIterator {
+    /// # Errors:
+    /// Arroja error si el archivo no existe
+    pub fn new(path: &str) -> Result<JsonLinesIterator, String> {
+        let file = File::open(path)
+            .map_err(|_| "[Sistema Error] Archivo de reservas no encontrado".to_string())?;
+        Ok(JsonLinesIterator {
+            reader: BufReader::new(file),
+            ended: false,
+            offset: 0,
+            line: 0,
+            rejects: Rejects::default(),
+            follow: false,
+        })
+    }
+
+    /// Agrega las lineas invalidas al archivo CSV `path`, con el mismo
+    /// formato que usa el lector de CSV
+    /// # Errors:
+    /// Arroja error si no es posible abrir el archivo de rechazos
+    pub fn with_rejects(mut self, path: &str) -> Result<JsonLinesIterator, String> {
+        self.rejects = Rejects::open(path)?;
+        Ok(self)
+    }
+
+    /// Modo seguimiento: una linea sin salto de linea final todavia
+    /// se esta escribiendo y no se consume hasta que se complete
+    #[must_use]
+    pub fn following(mut self) -> JsonLinesIterator {
+        self.follow = true;
+        self
+    }
+
+    /// Lee la proxima linea sin el salto de linea final
+    fn read_line(&mut self) -> Option<Result<String, String>> {
+        let mut bytes = Vec::new();
+        let len = match self.reader.read_until(b'\n', &mut bytes) {
+            Ok(len) => len,
+            Err(err) => {
+                warn!(error = %err, "No se pudo leer el archivo de reservas");
+                0
+            }
+        };
+        if len == 0 {
+            self.ended = true;
+            return None;
+        }
+        if self.follow && !bytes.ends_with(b"\n") {
+            if let Err(err) = self.reader.seek(SeekFrom::Start(self.offset)) {
+                warn!(error = %err, "No se pudo posicionar el archivo de reservas");
+            }
+            self.ended = true;
+            return None;
+        }
+        self.offset += len as u64;
+        self.line += 1;
+        Some(
+            String::from_utf8(bytes)
+                .map(|line| line.trim_end_matches(['\n', '\r']).to_string())
+                .map_err(|err| {
+                    String::from_utf8_lossy(err.as_bytes())
+                        .trim_end()
+                        .to_string()
+                }),
+        )
+    }
+}
+
+impl TransactionSource for JsonLinesIterator {
+    fn ended(&self) -> bool {
+        self.ended
+    }
+
+    fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Se relee el archivo hasta `offset` para conocer el numero de linea
+    fn seek(&mut self, offset: u64) -> Result<(), String> {
+        let err_msg = "[Sistema Error] No se pudo posicionar el archivo de reservas".to_string();
+        if self.reader.seek(SeekFrom::Start(0)).is_err() {
+            return Err(err_msg);
+        }
+        self.offset = 0;
+        self.line = 0;
+        self.ended = false;
+        while self.offset < offset {
+            if self.read_line().is_none() {
+                break;
+            }
+        }
+        if self.offset != offset {
+            return Err(err_msg);
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for JsonLinesIterator {
+    type Item = Transaction;
+
+    /// Saltea las lineas vacias y rechaza las invalidas en lugar de
+    /// cortar la lectura. Devuelve `None` cuando el archivo termino
+    fn next(&mut self) -> Option<Transaction> {
+        loop {
+            let line = self.line + 1;
+            let content = match self.read_line()? {
+                Ok(content) => content,
+                Err(content) => {
+                    self.rejects
+                        .reject(line, &content, "la linea no es UTF-8 valido");
+                    continue;
+                }
+            };
+            if content.trim().is_empty() {
+                continue;
+            }
+            match parse_booking(&content) {
+                Ok(transaction) => return Some(transaction),
+                Err(reason) => self.rejects.reject(line, &content, &reason),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alglobo::transactionable::Transactionable;
+    use std::{env, fs};
+
+    fn write_file(name: &str, content: &str) -> String {
+        let path = env::temp_dir().join(format!("alglobo_{}_{}", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn it_should_read_bookings_with_their_details() {
+        let path = write_file(
+            "bookings.jsonl",
+            "{\"id\":7,\"fees\":{\"airline\":3,\"Hotel\":2.5,\"bank\":1},\"currency\":\"ARS\",\
+             \"customer\":\"c-1\",\"metadata\":{\"canal\":\"web\"},\"extra\":true}\r\n\
+             \n\
+             {\"id\":8,\"fees\":{\"airline\":1,\"hotel\":1,\"bank\":1}}\n",
+        );
+
+        let mut iterator = JsonLinesIterator::new(&path).unwrap();
+        let first = iterator.next().unwrap();
+
+        assert_eq!(
+            first.representation(false),
+            Transaction::new(
+                7,
+                &HashMap::from([
+                    ("Airline".to_string(), 3.0),
+                    ("Hotel".to_string(), 2.5),
+                    ("Bank".to_string(), 1.0),
+                ]),
+            )
+            .representation(false)
+        );
+        let booking = first.booking().unwrap();
+        assert_eq!(booking.currency.as_deref(), Some("ARS"));
+        assert_eq!(booking.customer.as_deref(), Some("c-1"));
+        assert_eq!(booking.metadata["canal"], "web");
+        let second = iterator.next().unwrap();
+        assert_eq!(second.get_id(), 8);
+        assert_eq!(second.booking(), Some(&Booking::default()));
+        assert!(iterator.next().is_none());
+        assert!(iterator.ended());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn it_should_reject_invalid_lines_and_resume_from_an_offset() {
+        let path = write_file(
+            "invalid.jsonl",
+            "{\"id\":0,\"fees\":{\"airline\":1,\"hotel\":1,\"bank\":1}}\n\
+             {\"id\":1,\"fees\":{\"airline\":1,\"hotel\":1}}\n\
+             {\"id\":2,\"fees\":{\"airline\":1,\"hotel\":1,\"bank\":1,\"tren\":1}}\n\
+             {\"id\":3,\"fees\":{\"airline\":1,\"hotel\":1,\"bank\":1},\"currency\":\"pesos\"}\n\
+             {\"id\":\"x\"\n\
+             {\"id\":5,\"fees\":{\"airline\":1,\"hotel\":1,\"bank\":1}}\n",
+        );
+        let rejects = write_file("invalid_rejects.csv", "");
+        let mut iterator = JsonLinesIterator::new(&path)
+            .unwrap()
+            .with_rejects(&rejects)
+            .unwrap();
+        iterator.next();
+        let offset = iterator.offset();
+
+        let ids: Vec<u64> = iterator.map(|transaction| transaction.get_id()).collect();
+        let rows = fs::read_to_string(&rejects).unwrap();
+        let reasons: Vec<&str> = rows
+            .lines()
+            .map(|row| row.split(',').take(2).last().unwrap())
+            .collect();
+
+        assert_eq!(ids, vec![5]);
+        assert_eq!(
+            reasons[..3],
+            [
+                "falta el monto de Bank",
+                "servicio desconocido: tren",
+                "moneda invalida: pesos"
+            ]
+        );
+        assert!(rows
+            .lines()
+            .nth(3)
+            .unwrap()
+            .starts_with("5,\"JSON invalido"));
+        let mut resumed = JsonLinesIterator::new(&path).unwrap();
+        resumed.seek(offset).unwrap();
+        assert!(resumed.seek(offset + 1).is_err());
+        fs::remove_file(path).unwrap();
+        fs::remove_file(rejects).unwrap();
+    }
+}
@@ -1 +1,7 @@
+pub mod checkpoint;
+pub mod csv_record;
 pub mod file_iterator;
+pub mod input_format;
+pub mod json_lines_iterator;
+pub mod rejects;
+pub mod transaction_source;
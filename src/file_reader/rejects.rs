@@ -0,0 +1,39 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+use tracing::warn;
+
+use super::csv_record::quote;
+
+/// Registro de las filas invalidas de un archivo de transacciones.
+/// Cada rechazo se agrega como una fila CSV con el numero de linea,
+/// el motivo y el contenido original.
+#[derive(Default)]
+pub struct Rejects {
+    file_opt: Option<File>,
+}
+
+impl Rejects {
+    /// # Errors:
+    /// Arroja error si no es posible abrir el archivo de rechazos
+    pub fn open(path: &str) -> Result<Rejects, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|_| "[Sistema Error] No se pudo abrir el archivo de rechazos".to_string())?;
+        Ok(Rejects {
+            file_opt: Some(file),
+        })
+    }
+
+    pub fn reject(&mut self, line: u64, content: &str, reason: &str) {
+        warn!(line, reason, content, "Fila de transaccion rechazada");
+        if let Some(file) = &mut self.file_opt {
+            let row = format!("{},{},{}\n", line, quote(reason), quote(content));
+            if let Err(err) = file.write_all(row.as_bytes()) {
+                warn!(error = %err, "No se pudo registrar la fila rechazada");
+            }
+        }
+    }
+}
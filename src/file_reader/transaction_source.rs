@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use crate::alglobo::transaction::Transaction;
+
+use super::{
+    checkpoint::Checkpoint, file_iterator::FileIterator, input_format::InputFormat,
+    json_lines_iterator::JsonLinesIterator,
+};
+
+/// Origen de las transacciones que procesa el lider. Los offsets son
+/// posiciones en bytes del comienzo de un registro, que se traspasan
+/// al sucesor para que retome la lectura.
+pub trait TransactionSource: Iterator<Item = Transaction> {
+    /// Devuelve verdadero cuando ya no quedan registros por leer
+    fn ended(&self) -> bool;
+
+    /// Cantidad de bytes leidos, es decir, el comienzo del proximo registro
+    fn offset(&self) -> u64;
+
+    /// Posiciona el origen en el registro que empieza en `offset`
+    /// # Errors:
+    /// Arroja error si `offset` no es el comienzo de un registro
+    fn seek(&mut self, offset: u64) -> Result<(), String>;
+}
+
+/// Seguimiento de un archivo de transacciones que sigue creciendo,
+/// como `tail -f`
+#[derive(Debug, PartialEq, Clone)]
+pub struct FollowSpec {
+    /// Espera entre lecturas cuando no hay registros nuevos
+    pub poll: Duration,
+    /// Archivo donde se guarda el offset de lectura
+    pub checkpoint_file: String,
+}
+
+/// Archivo de transacciones a leer: ruta, formato (si se omite se
+/// deduce de la extension), archivo de filas rechazadas y, si se lo
+/// sigue mientras crece, como hacerlo
+#[derive(Debug, PartialEq, Clone)]
+pub struct SourceSpec {
+    pub path: String,
+    pub format_opt: Option<InputFormat>,
+    pub rejects_file_opt: Option<String>,
+    pub follow_opt: Option<FollowSpec>,
+}
+
+impl SourceSpec {
+    #[must_use]
+    pub fn format(&self) -> InputFormat {
+        self.format_opt
+            .unwrap_or_else(|| InputFormat::from_path(&self.path))
+    }
+
+    /// Checkpoint del archivo, solo si se lo sigue
+    #[must_use]
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        self.follow_opt
+            .as_ref()
+            .map(|follow| Checkpoint::new(&follow.checkpoint_file))
+    }
+
+    /// Offset desde el que se retoma la lectura segun el checkpoint
+    #[must_use]
+    pub fn checkpoint_offset(&self) -> Option<u64> {
+        self.checkpoint()?.load(&self.path)
+    }
+
+    /// # Errors:
+    /// Arroja error si no es posible abrir el archivo de transacciones
+    /// o el de rechazos
+    pub fn open(&self) -> Result<Box<dyn TransactionSource + Send>, String> {
+        let rejects_opt = self.rejects_file_opt.as_deref();
+        let follow = self.follow_opt.is_some();
+        Ok(match self.format() {
+            InputFormat::Csv => {
+                let mut source = FileIterator::new(&self.path)?;
+                if let Some(rejects) = rejects_opt {
+                    source = source.with_rejects(rejects)?;
+                }
+                if follow {
+                    source = source.following();
+                }
+                Box::new(source)
+            }
+            InputFormat::JsonLines => {
+                let mut source = JsonLinesIterator::new(&self.path)?;
+                if let Some(rejects) = rejects_opt {
+                    source = source.with_rejects(rejects)?;
+                }
+                if follow {
+                    source = source.following();
+                }
+                Box::new(source)
+            }
+        })
+    }
+}
@@ -1,9 +1,16 @@
+pub mod admin;
 pub mod alglobo;
 pub mod candidates;
+pub mod config;
 pub mod file_reader;
+pub mod logging;
+pub mod metrics;
 pub mod retry;
+pub mod runtime;
 pub mod services;
 pub mod sockets;
+pub mod submit;
+pub mod telemetry;
 pub mod transaction_messages;
 
 // Uncomment these lines when they are ready to be reviewed
@@ -0,0 +1,34 @@
+use std::str::FromStr;
+
+/// Formato de salida de los logs
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LogFormat {
+    /// Una linea legible por evento
+    Human,
+    /// Un objeto JSON por linea, para ingerir los logs de varias replicas
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "human" => Ok(LogFormat::Human),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_the_known_formats() {
+        assert_eq!("human".parse(), Ok(LogFormat::Human));
+        assert_eq!("json".parse(), Ok(LogFormat::Json));
+        assert_eq!("xml".parse::<LogFormat>(), Err(()));
+    }
+}
@@ -0,0 +1,33 @@
+use tracing_subscriber::EnvFilter;
+
+use super::log_format::LogFormat;
+
+/// Valida un filtro de niveles, por ejemplo `info` o
+/// `info,tp::sockets=debug`
+///
+/// # Errors
+///
+/// Devuelve la descripcion del error si el filtro es invalido
+pub fn parse_filter(level: &str) -> Result<EnvFilter, String> {
+    EnvFilter::try_new(level).map_err(|err| err.to_string())
+}
+
+/// Instala el logger global del proceso. Los eventos se escriben por
+/// la salida estandar con sus campos y los de los spans activos
+/// (por ejemplo el id de nodo y la epoca).
+///
+/// # Errors
+///
+/// Devuelve error si el filtro es invalido o ya habia un logger
+pub fn init(level: &str, format: LogFormat) -> Result<(), String> {
+    let builder = tracing_subscriber::fmt().with_env_filter(parse_filter(level)?);
+    let result = match format {
+        LogFormat::Human => builder.try_init(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(false)
+            .with_span_list(true)
+            .try_init(),
+    };
+    result.map_err(|err| err.to_string())
+}
@@ -0,0 +1,2 @@
+pub mod log_format;
+pub mod logger;
@@ -1,111 +1,551 @@
-use input_reader::get_input;
+use clap::{Parser, Subcommand, ValueEnum};
+use input_reader::read_commands;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::process;
+use std::thread;
 use std::time::Duration;
+use tp::admin::admin_server::query_status;
 use tp::candidates::candidate::Candidate;
-use tp::candidates::constants::{
-    AIRLINE_ADDR, BANK_ADDR, DEFAULT_IP, EMPTY, HOTEL_ADDR, VEC_PORT_INFO,
-};
+use tp::config::settings::Settings;
+use tp::config::topology::NodeAddrs;
+use tp::file_reader::input_format::InputFormat;
+use tp::logging::logger;
+use tp::metrics::{registry::metrics, server::serve};
+use tp::runtime::event_loop::EventLoop;
 use tp::services::airline_client::Airline;
 use tp::services::bank_client::Bank;
 use tp::services::common_client::CommonClient;
 use tp::services::hotel_client::Hotel;
-use tp::sockets::udp_socket_wrap::UdpSocketWrap;
+use tp::services::service_name::ServiceName;
+use tp::sockets::reliable_socket::{maybe_polled_reliable, maybe_reliable};
+use tp::sockets::tcp_transport::TcpTransport;
+use tp::telemetry::span_exporter;
+
+/// Nodos y servicios del sistema de pagos de AlGlobo
+#[derive(Parser)]
+#[command(name = "alglobo")]
+struct Cli {
+    /// Archivo de configuracion TOML (por defecto se usa ALGLOBO_CONFIG)
+    #[arg(long, global = true)]
+    config: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Levanta un nodo candidato a lider
+    Candidate {
+        /// Id del nodo en la topologia. Si se omite se usa el primero
+        /// cuya direccion de eleccion se pueda bindear
+        #[arg(long)]
+        id: Option<u64>,
+        /// Archivo de transacciones a procesar
+        #[arg(long)]
+        data: Option<String>,
+        /// Formato del archivo de transacciones
+        #[arg(long, value_enum)]
+        format: Option<FormatArg>,
+        /// Sigue leyendo el archivo de transacciones a medida que crece
+        #[arg(long)]
+        follow: bool,
+        /// Direccion en la que el nodo recibe reservas por SUBMIT, pisa a submit_addr
+        #[arg(long, requires = "id")]
+        submit: Option<SocketAddr>,
+        /// Direccion del endpoint HTTP /metrics
+        #[arg(long)]
+        metrics: Option<SocketAddr>,
+        /// Direccion del puerto de administracion del nodo, pisa a admin_addr
+        #[arg(long, requires = "id")]
+        admin: Option<SocketAddr>,
+    },
+    /// Levanta varios nodos candidatos en un mismo proceso. Los
+    /// sockets y receptores de todos los nodos comparten un event loop
+    Candidates {
+        /// Ids de los nodos a levantar, separados por coma. Si se omite
+        /// se levantan todos los nodos de la topologia
+        #[arg(long, value_delimiter = ',')]
+        ids: Vec<u64>,
+        /// Archivo de transacciones a procesar
+        #[arg(long)]
+        data: Option<String>,
+        /// Formato del archivo de transacciones
+        #[arg(long, value_enum)]
+        format: Option<FormatArg>,
+        /// Sigue leyendo el archivo de transacciones a medida que crece
+        #[arg(long)]
+        follow: bool,
+        /// Direccion del endpoint HTTP /metrics
+        #[arg(long)]
+        metrics: Option<SocketAddr>,
+    },
+    /// Consulta nodos por su puerto de administracion
+    Admin {
+        #[command(subcommand)]
+        command: AdminCommand,
+    },
+    /// Levanta un servicio externo
+    Service {
+        #[arg(value_enum)]
+        name: ServiceArg,
+        /// Direccion en la que escucha el servicio
+        #[arg(long)]
+        bind: Option<SocketAddr>,
+        /// Porcentaje (0 a 100) de pedidos de preparacion que se abortan
+        #[arg(long)]
+        fail_rate: Option<u32>,
+        /// Direccion del endpoint HTTP /metrics
+        #[arg(long)]
+        metrics: Option<SocketAddr>,
+    },
+    /// Levanta los tres servicios en un mismo proceso con un event loop
+    Services {
+        /// Porcentaje (0 a 100) de pedidos de preparacion que se abortan
+        #[arg(long)]
+        fail_rate: Option<u32>,
+        /// Direccion del endpoint HTTP /metrics
+        #[arg(long)]
+        metrics: Option<SocketAddr>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AdminCommand {
+    /// Muestra rol, lider, epoca, transaccion actual, colas y ultima
+    /// decision. Sin --node ni --addr consulta todos los nodos de la
+    /// topologia que tienen admin_addr
+    Status {
+        /// Id del nodo a consultar
+        #[arg(long, conflicts_with = "addr")]
+        node: Option<u64>,
+        /// Direccion del puerto de administracion a consultar
+        #[arg(long)]
+        addr: Option<SocketAddr>,
+        /// Muestra la respuesta JSON tal como la envia el nodo
+        #[arg(long)]
+        json: bool,
+        /// Espera maxima de cada respuesta
+        #[arg(long, default_value_t = 2000)]
+        timeout_ms: u64,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ServiceArg {
+    Airline,
+    Hotel,
+    Bank,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FormatArg {
+    /// Se deduce de la extension: .jsonl y .ndjson son JSON Lines
+    Auto,
+    Csv,
+    Jsonl,
+}
+
+impl From<FormatArg> for Option<InputFormat> {
+    fn from(format: FormatArg) -> Self {
+        match format {
+            FormatArg::Auto => None,
+            FormatArg::Csv => Some(InputFormat::Csv),
+            FormatArg::Jsonl => Some(InputFormat::JsonLines),
+        }
+    }
+}
+
+impl From<ServiceArg> for ServiceName {
+    fn from(service: ServiceArg) -> Self {
+        match service {
+            ServiceArg::Airline => ServiceName::Airline,
+            ServiceArg::Hotel => ServiceName::Hotel,
+            ServiceArg::Bank => ServiceName::Bank,
+        }
+    }
+}
 
 fn main() {
-    let input = get_input();
-    if input.is_err() {
-        println!("Error. Utilizar cargo run + c: crear candidato. a: servicio aerolinea, b: servicio banco o h: servicio hotel");
-        return;
+    let cli = Cli::parse();
+    let mut settings = match Settings::from_env(cli.config.as_deref()) {
+        Ok(value) => value,
+        Err(err) => exit_with_error(err),
+    };
+    if let Err(err) = logger::init(&settings.log_level, settings.log_format) {
+        exit_with_error(err);
     }
-    if input.clone().expect("Error. Utilizar cargo run + c: crear candidato. a: servicio aerolinea, b: servicio banco o h: servicio hotel ") == "c" {
-        let mut socket_data_recv = UdpSocketWrap::new(None);
-        let mut socket_data_send = UdpSocketWrap::new(None);
-        let mut port_candidate: i32 = 0;
-        let mut vec_addr: Vec<String> = vec!["49353".to_string()];
-        for port in VEC_PORT_INFO.clone() {
-            vec_addr.push(port.to_string().clone());
+    if let Some(path) = &settings.trace_file {
+        if let Err(err) = span_exporter::init(path) {
+            exit_with_error(format!(
+                "No se pudo abrir el archivo de spans {}: {}",
+                path, err
+            ));
         }
-        for port in VEC_PORT_INFO.clone() {
-            let socket_info_data_new = UdpSocketWrap::new_with_addr(
-                None,
-                DEFAULT_IP.to_string() + port.to_string().as_str(),
-            );
-            if let Ok(socket_new_aux) = socket_info_data_new {
-                socket_data_recv = socket_new_aux;
-                if let Ok(socket_aux) = socket_data_recv.try_clone() {
-                    socket_data_send = socket_aux;
-                    port_candidate = port;
-                    break;
+    }
+    match cli.command {
+        Command::Candidate {
+            id,
+            data,
+            format,
+            follow,
+            submit,
+            metrics,
+            admin,
+        } => {
+            apply_source_args(&mut settings, data, format, follow);
+            settings.metrics_addr = metrics.or(settings.metrics_addr);
+            if let Some(value) = id {
+                match settings
+                    .topology
+                    .nodes
+                    .iter_mut()
+                    .find(|node| node.id == value)
+                {
+                    Some(node) => {
+                        node.admin_addr = admin.or(node.admin_addr);
+                        node.submit_addr = submit.or(node.submit_addr);
+                    }
+                    None => {
+                        exit_with_error(format!("No hay un nodo con id {} en la topologia", value))
+                    }
                 }
             }
+            if let Err(err) = settings.validate() {
+                exit_with_error(err);
+            }
+            if let Err(err) = serve_metrics(&settings) {
+                exit_with_error(err);
+            }
+            start_candidate(settings, id);
+        }
+        Command::Candidates {
+            ids,
+            data,
+            format,
+            follow,
+            metrics,
+        } => {
+            apply_source_args(&mut settings, data, format, follow);
+            settings.metrics_addr = metrics.or(settings.metrics_addr);
+            if let Err(err) = settings.validate() {
+                exit_with_error(err);
+            }
+            if let Err(err) = serve_metrics(&settings) {
+                exit_with_error(err);
+            }
+            if let Err(err) = start_candidates(&settings, &ids) {
+                exit_with_error(err);
+            }
         }
-        let mut candidate = Candidate::new(
-            Box::new(socket_data_recv),
-            Box::new(socket_data_send),
-            port_candidate.to_string(),
-            vec_addr,
-            EMPTY.to_string(),
-            "".to_string(),
-        );
-        candidate.start_candidate();
-    } else if input.as_ref().expect("Input incorrecto") == "a" {
-        let socket_send_airline = UdpSocketWrap::new_with_addr(
-            Some(Duration::from_millis(100_000)),
-            AIRLINE_ADDR.to_string(),
-        )
-        .expect("No pude crear el socket del servicio de la aerolinea");
-        let socket_recv_airline = socket_send_airline
-            .try_clone()
-            .expect("No pude copiar el socket del servicio de la aerolinea");
-        let mut airline_service = Airline::new(
-            Box::new(socket_send_airline),
-            Box::new(socket_recv_airline),
-            AIRLINE_ADDR.to_string(),
-        );
-        airline_service.start_client();
-    } else if input.as_ref().expect("Input incorrecto") == "b" {
-        let socket_send_bank = UdpSocketWrap::new_with_addr(
-            Some(Duration::from_millis(100_000)),
-            BANK_ADDR.to_string(),
-        )
-        .expect("No pude crear el socket del servicio de la aerolinea");
-        let socket_recv_bank = socket_send_bank
-            .try_clone()
-            .expect("No pude copiar el socket del servicio de la aerolinea");
-        let mut bank_service = Bank::new(
-            Box::new(socket_send_bank),
-            Box::new(socket_recv_bank),
-            BANK_ADDR.to_string(),
-        );
-        bank_service.start_client();
-    } else if input.as_ref().expect("Input incorrecto") == "h" {
-        let socket_send_hotel = UdpSocketWrap::new_with_addr(
-            Some(Duration::from_millis(100_000)),
-            HOTEL_ADDR.to_string(),
-        )
-        .expect("No pude crear el socket del servicio del hotel");
-        let socket_recv_hotel = socket_send_hotel
-            .try_clone()
-            .expect("No pude copiar el socket del servicio del hotel");
-        let mut hotel_service = Hotel::new(
-            Box::new(socket_recv_hotel),
-            Box::new(socket_send_hotel),
-            HOTEL_ADDR.to_string(),
-        );
-        hotel_service.start_client();
+        Command::Admin {
+            command:
+                AdminCommand::Status {
+                    node,
+                    addr,
+                    json,
+                    timeout_ms,
+                },
+        } => admin_status(
+            &settings,
+            node,
+            addr,
+            json,
+            Duration::from_millis(timeout_ms),
+        ),
+        Command::Service {
+            name,
+            bind,
+            fail_rate,
+            metrics,
+        } => {
+            let service = ServiceName::from(name);
+            if let Some(addr) = bind {
+                match service {
+                    ServiceName::Airline => settings.topology.airline_addr = addr,
+                    ServiceName::Hotel => settings.topology.hotel_addr = addr,
+                    ServiceName::Bank => settings.topology.bank_addr = addr,
+                }
+            }
+            if let Some(value) = fail_rate {
+                settings.percentage_error = value;
+            }
+            settings.metrics_addr = metrics.or(settings.metrics_addr);
+            if let Err(err) = settings.validate() {
+                exit_with_error(err);
+            }
+            if let Err(err) = serve_metrics(&settings) {
+                exit_with_error(err);
+            }
+            start_service(&settings, service);
+        }
+        Command::Services { fail_rate, metrics } => {
+            if let Some(value) = fail_rate {
+                settings.percentage_error = value;
+            }
+            settings.metrics_addr = metrics.or(settings.metrics_addr);
+            if let Err(err) = settings.validate() {
+                exit_with_error(err);
+            }
+            if let Err(err) = serve_metrics(&settings) {
+                exit_with_error(err);
+            }
+            if let Err(err) = start_services(&settings) {
+                exit_with_error(err);
+            }
+        }
+    }
+}
+
+/// Informa un error de configuracion o de arranque y termina el proceso
+/// con un codigo de error
+fn exit_with_error(err: impl Display) -> ! {
+    eprintln!("{}", err);
+    process::exit(2)
+}
+
+/// Aplica al archivo de transacciones las opciones de la linea de comandos
+fn apply_source_args(
+    settings: &mut Settings,
+    data: Option<String>,
+    format: Option<FormatArg>,
+    follow: bool,
+) {
+    if let Some(path) = data {
+        settings.transaction_file = path;
+    }
+    if let Some(format) = format {
+        settings.input_format = format.into();
+    }
+    settings.follow |= follow;
+}
+
+/// Expone las metricas del proceso si se configuro `metrics_addr`
+fn serve_metrics(settings: &Settings) -> Result<(), String> {
+    if let Some(addr) = settings.metrics_addr {
+        serve(addr, metrics())
+            .map_err(|err| format!("No se pudo exponer las metricas en {}: {}", addr, err))?;
+    }
+    Ok(())
+}
+
+/// Muestra el estado de los nodos elegidos
+fn admin_status(
+    settings: &Settings,
+    node: Option<u64>,
+    addr: Option<SocketAddr>,
+    json: bool,
+    timeout: Duration,
+) {
+    let targets: Vec<(String, SocketAddr)> = match (node, addr) {
+        (_, Some(addr)) => vec![(addr.to_string(), addr)],
+        (Some(id), None) => match settings.topology.node(id).and_then(|node| node.admin_addr) {
+            Some(addr) => vec![(format!("Nodo {}", id), addr)],
+            None => {
+                println!("El nodo {} no tiene admin_addr en la topologia", id);
+                return;
+            }
+        },
+        (None, None) => settings
+            .topology
+            .nodes
+            .iter()
+            .filter_map(|node| {
+                node.admin_addr
+                    .map(|addr| (format!("Nodo {}", node.id), addr))
+            })
+            .collect(),
+    };
+    if targets.is_empty() {
+        println!("Ningun nodo de la topologia tiene admin_addr");
+    }
+    for (name, addr) in targets {
+        match query_status(&addr, timeout) {
+            Ok(status) if json => match serde_json::to_string(&status) {
+                Ok(line) => println!("{}", line),
+                Err(err) => println!("{} ({}): {}", name, addr, err),
+            },
+            Ok(status) => println!("{}\n", status.report()),
+            Err(err) => println!("{} ({}): {}\n", name, addr, err),
+        }
+    }
+}
+
+fn start_candidate(settings: Settings, id: Option<u64>) {
+    let nodes = match id {
+        Some(value) => settings.topology.node(value).into_iter().collect(),
+        // El nodo local es el primero cuya direccion de eleccion se puede bindear
+        None => settings.topology.nodes.clone(),
+    };
+    let event_loop = EventLoop::new().unwrap_or_else(|err| exit_with_error(err));
+    let mut candidate = nodes
+        .iter()
+        .find_map(|node| attach_candidate(&event_loop, &settings, *node).ok())
+        .expect("Ninguna direccion de la topologia pertenece a esta maquina o estan todas en uso");
+    let step_down = candidate.step_down();
+    thread::spawn(move || read_commands(&[step_down]));
+    candidate.start_candidate();
+}
+
+/// Levanta en este proceso los nodos `ids`, o todos los de la
+/// topologia si esta vacio. Cada nodo corre su eleccion en su propio
+/// hilo y sus sockets en el event loop compartido.
+fn start_candidates(settings: &Settings, ids: &[u64]) -> Result<(), String> {
+    let nodes: Vec<NodeAddrs> = if ids.is_empty() {
+        settings.topology.nodes.clone()
     } else {
-        println!("Error. Utilizar cargo run + c: crear candidato. a: servicio aerolinea, b: servicio banco o h: servicio hotel");
+        ids.iter()
+            .map(|id| {
+                settings
+                    .topology
+                    .node(*id)
+                    .ok_or_else(|| format!("No hay un nodo con id {} en la topologia", id))
+            })
+            .collect::<Result<_, _>>()?
+    };
+    let event_loop = EventLoop::new()?;
+    let mut step_downs = Vec::new();
+    let mut handles = Vec::new();
+    for node in nodes {
+        let mut candidate = attach_candidate(&event_loop, settings, node)?;
+        step_downs.push(candidate.step_down());
+        handles.push(thread::spawn(move || candidate.start_candidate()));
+    }
+    thread::spawn(move || read_commands(&step_downs));
+    for handle in handles {
+        let _drop = handle.join();
     }
+    Ok(())
+}
+
+/// Crea el candidato de `node` con su socket de eleccion en `event_loop`
+fn attach_candidate(
+    event_loop: &EventLoop,
+    settings: &Settings,
+    node: NodeAddrs,
+) -> Result<Candidate, String> {
+    let security = settings.security();
+    let reliable = settings.reliable();
+    let (socket_info_send, socket_info_recv) =
+        event_loop.attach(node.info_addr, |sender, receiver| {
+            let (sender, receiver) = security.secure(sender, receiver, node.info_addr);
+            maybe_polled_reliable(sender, receiver, reliable)
+        })?;
+    Ok(Candidate::new(
+        socket_info_recv,
+        socket_info_send,
+        node,
+        settings.clone(),
+        event_loop.clone(),
+    ))
+}
+
+fn start_service(settings: &Settings, service: ServiceName) {
+    if !settings.topology.uses_tcp(service) {
+        let hosted = EventLoop::new().and_then(|event_loop| {
+            host_service(&event_loop, settings, service)?;
+            event_loop.run();
+            Ok(())
+        });
+        if let Err(err) = hosted {
+            exit_with_error(err);
+        }
+        return;
+    }
+    // El transporte TCP es bloqueante: el servicio usa sus propios hilos
+    let addr = settings.topology.service_addr(service);
+    let (transport, receiver) = TcpTransport::listen(addr).unwrap_or_else(|err| panic!("{}", err));
+    let (socket_send, socket_recv) =
+        settings
+            .security()
+            .secure(Box::new(transport), Box::new(receiver), addr);
+    let (socket_send, socket_recv) = maybe_reliable(socket_send, socket_recv, settings.reliable());
+    match service {
+        ServiceName::Airline => {
+            Airline::new(socket_recv, socket_send, addr, settings.percentage_error).start_client();
+        }
+        ServiceName::Hotel => {
+            Hotel::new(socket_recv, socket_send, addr, settings.percentage_error).start_client();
+        }
+        ServiceName::Bank => {
+            Bank::new(socket_recv, socket_send, addr, settings.percentage_error).start_client();
+        }
+    }
+}
+
+/// Aloja en `event_loop` al servicio UDP `service`
+fn host_service(
+    event_loop: &EventLoop,
+    settings: &Settings,
+    service: ServiceName,
+) -> Result<(), String> {
+    let addr = settings.topology.service_addr(service);
+    let percentage_error = settings.percentage_error;
+    let reliable = settings.reliable();
+    let security = settings.security();
+    match service {
+        ServiceName::Airline => event_loop.host(addr, |receiver, sender| {
+            let (sender, receiver) = security.secure(sender, receiver, addr);
+            let (sender, receiver) = maybe_polled_reliable(sender, receiver, reliable);
+            Airline::new(receiver, sender, addr, percentage_error)
+        }),
+        ServiceName::Hotel => event_loop.host(addr, |receiver, sender| {
+            let (sender, receiver) = security.secure(sender, receiver, addr);
+            let (sender, receiver) = maybe_polled_reliable(sender, receiver, reliable);
+            Hotel::new(receiver, sender, addr, percentage_error)
+        }),
+        ServiceName::Bank => event_loop.host(addr, |receiver, sender| {
+            let (sender, receiver) = security.secure(sender, receiver, addr);
+            let (sender, receiver) = maybe_polled_reliable(sender, receiver, reliable);
+            Bank::new(receiver, sender, addr, percentage_error)
+        }),
+    }?;
+    Ok(())
+}
+
+fn start_services(settings: &Settings) -> Result<(), String> {
+    let event_loop = EventLoop::new()?;
+    // El event loop solo atiende sockets UDP: los servicios TCP usan sus propios hilos
+    for service in [ServiceName::Airline, ServiceName::Hotel, ServiceName::Bank] {
+        if settings.topology.uses_tcp(service) {
+            let settings = settings.clone();
+            thread::spawn(move || start_service(&settings, service));
+        } else {
+            host_service(&event_loop, settings, service)?;
+        }
+    }
+    event_loop.run();
+    Ok(())
 }
 
 mod input_reader {
-    use std::env;
+    use std::io::{self, BufRead};
+    use std::net::SocketAddr;
+    use tp::candidates::step_down::StepDown;
+    use tracing::info;
 
-    pub fn get_input() -> Result<String, i64> {
-        let args: Vec<String> = env::args().collect();
-        if args.len() < 2 {
-            return Err(1);
+    static COMMANDS_MSG: &str = "Comandos: step-down <direccion de datos del sucesor>";
+
+    /// Lee comandos de operador desde la entrada estandar.
+    /// `step-down <addr>` pide al lider que traspase el
+    /// liderazgo al nodo cuyo socket de datos es `addr`. Con varios
+    /// nodos en el proceso el pedido llega a todos y lo atiende el
+    /// que sea lider.
+    pub fn read_commands(step_downs: &[StepDown]) {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            let params: Vec<&str> = line.split_whitespace().collect();
+            match params.as_slice() {
+                ["step-down", successor] => match successor.parse::<SocketAddr>() {
+                    Ok(addr) => {
+                        info!(successor = %addr, "Renuncia solicitada");
+                        for step_down in step_downs {
+                            step_down.request(addr);
+                        }
+                    }
+                    Err(_) => println!("[Candidate] Direccion de sucesor invalida: {}", successor),
+                },
+                _ => println!("{}", COMMANDS_MSG),
+            }
         }
-        let filename = &args[1];
-        Ok(filename.to_string())
     }
 }
@@ -1,60 +1,204 @@
-use std::{collections::HashMap, env};
+use clap::{Parser, ValueEnum};
+use std::{collections::HashMap, process, thread, time::Duration};
 
 use tp::{
-    alglobo::{transaction::Transaction, transaction_error::TransactionError},
-    candidates::constants::{DEFAULT_IP, VEC_PORT_DATA},
-    retry::transaction_retrier::TransactionRetrier,
+    alglobo::{aborts::AbortReason, transaction::Transaction},
+    config::settings::Settings,
+    logging::logger,
+    retry::{
+        batch::{self, BatchFilter},
+        transaction_retrier::TransactionRetrier,
+    },
     services::service_name::ServiceName,
-    sockets::udp_socket_wrap::UdpSocketWrap,
+    sockets::{
+        reliable_socket::reliable_pair, udp_socket_receiver::UdpSocketReceiver,
+        udp_socket_wrap::UdpSocketWrap,
+    },
+    transaction_messages::transaction_retry_ack::RetryReply,
 };
 
-static ERR_MSG: &str = "
-    Uso:
-        cargo run --bin retry -- <id:u64> <pago_aerolinea:f64> <pago_hotel:f64> <pago_banco:f64>
-    ";
+// Codigos de salida; clap sale con 2 si los argumentos son invalidos
+const EXIT_COMMITED: i32 = 0;
+const EXIT_ABORTED: i32 = 1;
+const EXIT_DENIED: i32 = 3;
+const EXIT_UNANSWERED: i32 = 4;
+const EXIT_ERROR: i32 = 5;
 
-fn parse_args() -> Result<Transaction, TransactionError> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 5 {
-        println!("{}", ERR_MSG);
-        return Err(TransactionError::None);
+fn exit_code(reply_opt: Option<RetryReply>) -> i32 {
+    match reply_opt {
+        Some(RetryReply::Commited) => EXIT_COMMITED,
+        Some(RetryReply::Aborted) => EXIT_ABORTED,
+        Some(RetryReply::Denied(_)) => EXIT_DENIED,
+        Some(RetryReply::Granted) | None => EXIT_UNANSWERED,
     }
-    let id = if let Ok(value) = args[1].parse::<u64>() {
-        value
-    } else {
-        println!("{}", ERR_MSG);
-        return Err(TransactionError::None);
-    };
-    let services_names = [
-        (ServiceName::Airline.string_name()),
-        (ServiceName::Hotel.string_name()),
-        (ServiceName::Bank.string_name()),
-    ];
-    let mut services_info = HashMap::new();
-    for (idx, name) in services_names.iter().enumerate() {
-        let fee = if let Ok(value) = args[2 + idx].parse::<f64>() {
-            value
-        } else {
-            println!("{}", ERR_MSG);
-            return Err(TransactionError::None);
+}
+
+/// Reintenta una transaccion abortada o, con --from, las de un archivo de abortos
+#[derive(Parser)]
+#[command(name = "retry")]
+struct Cli {
+    /// Id de la transaccion
+    #[arg(required_unless_present = "from", requires_all = ["airline", "hotel", "bank"])]
+    id: Option<u64>,
+    /// Pago a la aerolinea
+    airline: Option<f64>,
+    /// Pago al hotel
+    hotel: Option<f64>,
+    /// Pago al banco
+    bank: Option<f64>,
+    /// Archivo de abortos a reintentar (JSON Lines o el CSV anterior)
+    #[arg(long, conflicts_with = "id")]
+    from: Option<String>,
+    /// Menor id a reintentar
+    #[arg(long, requires = "from")]
+    min_id: Option<u64>,
+    /// Mayor id a reintentar
+    #[arg(long, requires = "from")]
+    max_id: Option<u64>,
+    /// Motivos de aborto a reintentar; por defecto, todos
+    #[arg(long, value_enum, requires = "from")]
+    reason: Vec<ReasonArg>,
+    /// Reintentos enviados por segundo
+    #[arg(long, default_value_t = 1.0, requires = "from")]
+    rate: f64,
+    /// Espera maxima de la respuesta del lider a cada reintento
+    #[arg(long, default_value_t = 10000)]
+    timeout_ms: u64,
+    /// Espera maxima del resultado de cada reintento concedido
+    #[arg(long, default_value_t = 60000)]
+    result_timeout_ms: u64,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReasonArg {
+    Vote,
+    Timeout,
+    DecodeError,
+}
+
+impl From<ReasonArg> for AbortReason {
+    fn from(reason: ReasonArg) -> Self {
+        match reason {
+            ReasonArg::Vote => AbortReason::Vote,
+            ReasonArg::Timeout => AbortReason::Timeout,
+            ReasonArg::DecodeError => AbortReason::DecodeError,
+        }
+    }
+}
+
+impl Cli {
+    fn transaction(&self) -> Option<Transaction> {
+        let services_info = HashMap::from([
+            (ServiceName::Airline.string_name(), self.airline?),
+            (ServiceName::Hotel.string_name(), self.hotel?),
+            (ServiceName::Bank.string_name(), self.bank?),
+        ]);
+        Some(Transaction::new(self.id?, &services_info))
+    }
+
+    /// Devuelve el codigo de salida: el del resultado del reintento o,
+    /// en un lote, el peor de todos
+    fn retry(&self, retrier: &mut TransactionRetrier, replies: &mut dyn UdpSocketReceiver) -> i32 {
+        let path = match &self.from {
+            Some(value) => value,
+            None => {
+                let transaction = self
+                    .transaction()
+                    .expect("[Retry] Los argumentos de la transaccion son obligatorios");
+                let reply_opt = retrier.retry(&transaction, replies);
+                match reply_opt {
+                    Some(RetryReply::Granted) => println!("CONCEDIDO, RESULTADO PENDIENTE"),
+                    Some(reply) => println!("{}", reply),
+                    None => println!("SIN RESPUESTA"),
+                }
+                return exit_code(reply_opt);
+            }
+        };
+        let aborted = match batch::load(path) {
+            Ok(value) => value,
+            Err(err) => {
+                println!("{}", err);
+                return EXIT_ERROR;
+            }
+        };
+        let filter = BatchFilter {
+            min_id_opt: self.min_id,
+            max_id_opt: self.max_id,
+            reasons: self.reason.iter().map(|reason| (*reason).into()).collect(),
         };
-        services_info.insert(name.clone(), fee);
+        let interval = Duration::from_secs_f64(1.0 / self.rate);
+        let summary = batch::retry_batch(retrier, replies, &aborted, &filter, interval);
+        println!("{}", summary.report());
+        summary
+            .outcomes
+            .iter()
+            .map(|(_, reply_opt)| exit_code(*reply_opt))
+            .max()
+            .unwrap_or(EXIT_COMMITED)
     }
-    Ok(Transaction::new(id, &services_info))
 }
 
 fn main() {
-    let transaction = match parse_args() {
+    let cli = Cli::parse();
+    if !(cli.rate.is_finite() && cli.rate > 0.0) {
+        println!("--rate debe ser mayor a 0");
+        process::exit(EXIT_ERROR);
+    }
+
+    let settings = match Settings::from_env(None) {
         Ok(value) => value,
-        Err(_) => return,
+        Err(err) => {
+            println!("{}", err);
+            process::exit(EXIT_ERROR);
+        }
     };
-
-    let udp_sender = UdpSocketWrap::new(None);
-    let mut resplicas_addrs: Vec<String> = Vec::new();
-    for port in VEC_PORT_DATA.clone() {
-        resplicas_addrs.push(DEFAULT_IP.to_string() + port.to_string().as_str());
+    if let Err(err) = logger::init(&settings.log_level, settings.log_format) {
+        println!("{}", err);
+        process::exit(EXIT_ERROR);
     }
-    let mut retrier = TransactionRetrier::new(resplicas_addrs, Box::new(udp_sender));
+    let reply_timeout = Duration::from_millis(cli.timeout_ms);
+    let result_timeout = Duration::from_millis(cli.result_timeout_ms);
 
-    retrier.process(&transaction);
+    let replicas_addrs = settings.topology.replicas_addrs();
+    let code = match settings.reliable() {
+        Some(config) => {
+            let socket = UdpSocketWrap::new(Some(config.retransmit_timeout));
+            let socket_clone = socket
+                .try_clone()
+                .expect("No pude copiar el socket del reintento");
+            // El socket usa un puerto efimero: se firma con `retry_addr`
+            let (socket, socket_clone) = settings.security().secure(
+                Box::new(socket),
+                Box::new(socket_clone),
+                settings.topology.retry_addr,
+            );
+            let (udp_sender, mut udp_receiver) = reliable_pair(socket, socket_clone, config);
+            let mut retrier = TransactionRetrier::new(replicas_addrs, Box::new(udp_sender.clone()))
+                .with_reply_timeout(reply_timeout)
+                .with_result_timeout(result_timeout);
+            let code = cli.retry(&mut retrier, &mut udp_receiver);
+            // El canal procesa los acks por su cuenta: se espera a que
+            // lleguen o se agoten los intentos para no cortar las retransmisiones
+            while udp_sender.pending() > 0 {
+                thread::sleep(config.retransmit_timeout / 2);
+            }
+            code
+        }
+        None => {
+            let socket = UdpSocketWrap::new(None);
+            let socket_clone = socket
+                .try_clone()
+                .expect("No pude copiar el socket del reintento");
+            let (udp_sender, mut udp_receiver) = settings.security().secure(
+                Box::new(socket),
+                Box::new(socket_clone),
+                settings.topology.retry_addr,
+            );
+            let mut retrier = TransactionRetrier::new(replicas_addrs, udp_sender)
+                .with_reply_timeout(reply_timeout)
+                .with_result_timeout(result_timeout);
+            cli.retry(&mut retrier, udp_receiver.as_mut())
+        }
+    };
+    process::exit(code);
 }
@@ -0,0 +1,79 @@
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex};
+
+use super::labels::{render_labels, series};
+
+/// Contador monotonico con una serie por combinacion de etiquetas
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    values: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Counter {
+    #[must_use]
+    pub fn new(name: &'static str, help: &'static str) -> Self {
+        Counter {
+            name,
+            help,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn inc(&self, labels: &[(&str, &str)]) {
+        self.inc_by(labels, 1);
+    }
+
+    pub fn inc_by(&self, labels: &[(&str, &str)], amount: u64) {
+        let mut values = self
+            .values
+            .lock()
+            .expect("[Metrics] Lock de contador envenenado");
+        *values.entry(render_labels(labels)).or_insert(0) += amount;
+    }
+
+    #[must_use]
+    pub fn get(&self, labels: &[(&str, &str)]) -> u64 {
+        let values = self
+            .values
+            .lock()
+            .expect("[Metrics] Lock de contador envenenado");
+        values.get(&render_labels(labels)).copied().unwrap_or(0)
+    }
+
+    /// Agrega a `out` el contador en formato de exposicion de texto
+    pub fn render(&self, out: &mut String) {
+        let values = self
+            .values
+            .lock()
+            .expect("[Metrics] Lock de contador envenenado");
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} counter", self.name);
+        for (labels, value) in values.iter() {
+            let _ = writeln!(out, "{} {}", series(self.name, labels), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_count_each_label_set_separately() {
+        let counter = Counter::new("alglobo_test_total", "Prueba");
+
+        counter.inc(&[("outcome", "commit")]);
+        counter.inc(&[("outcome", "commit")]);
+        counter.inc_by(&[("outcome", "abort")], 3);
+
+        let mut out = String::new();
+        counter.render(&mut out);
+        assert_eq!(
+            out,
+            "# HELP alglobo_test_total Prueba\n\
+             # TYPE alglobo_test_total counter\n\
+             alglobo_test_total{outcome=\"abort\"} 3\n\
+             alglobo_test_total{outcome=\"commit\"} 2\n"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+use super::labels::{render_labels, series};
+
+/// Limites por defecto de los buckets, en segundos
+pub const DEFAULT_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0];
+
+#[derive(Clone)]
+struct Observations {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+/// Histograma de duraciones con buckets fijos y una serie por
+/// combinacion de etiquetas
+pub struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    bounds: &'static [f64],
+    values: Mutex<BTreeMap<String, Observations>>,
+}
+
+impl Histogram {
+    #[must_use]
+    pub fn new(name: &'static str, help: &'static str, bounds: &'static [f64]) -> Self {
+        Histogram {
+            name,
+            help,
+            bounds,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn observe(&self, labels: &[(&str, &str)], duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let mut values = self
+            .values
+            .lock()
+            .expect("[Metrics] Lock de histograma envenenado");
+        let observations = values
+            .entry(render_labels(labels))
+            .or_insert_with(|| Observations {
+                buckets: vec![0; self.bounds.len()],
+                sum: 0.0,
+                count: 0,
+            });
+        for (bound, bucket) in self.bounds.iter().zip(observations.buckets.iter_mut()) {
+            if seconds <= *bound {
+                *bucket += 1;
+            }
+        }
+        observations.sum += seconds;
+        observations.count += 1;
+    }
+
+    /// Agrega a `out` el histograma en formato de exposicion de texto.
+    /// Los buckets son acumulativos, como los espera Prometheus.
+    pub fn render(&self, out: &mut String) {
+        let values = self
+            .values
+            .lock()
+            .expect("[Metrics] Lock de histograma envenenado");
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} histogram", self.name);
+        let bucket_name = format!("{}_bucket", self.name);
+        for (labels, observations) in values.iter() {
+            let separator = if labels.is_empty() { "" } else { "," };
+            for (bound, bucket) in self.bounds.iter().zip(&observations.buckets) {
+                let le = format!("{}{}le=\"{}\"", labels, separator, bound);
+                let _ = writeln!(out, "{} {}", series(&bucket_name, &le), bucket);
+            }
+            let le = format!("{}{}le=\"+Inf\"", labels, separator);
+            let _ = writeln!(out, "{} {}", series(&bucket_name, &le), observations.count);
+            let _ = writeln!(
+                out,
+                "{} {}",
+                series(&format!("{}_sum", self.name), labels),
+                observations.sum
+            );
+            let _ = writeln!(
+                out,
+                "{} {}",
+                series(&format!("{}_count", self.name), labels),
+                observations.count
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_render_cumulative_buckets_sum_and_count() {
+        let histogram = Histogram::new("alglobo_test_seconds", "Prueba", &[0.1, 1.0]);
+
+        histogram.observe(&[("service", "Bank")], Duration::from_millis(50));
+        histogram.observe(&[("service", "Bank")], Duration::from_millis(500));
+        histogram.observe(&[("service", "Bank")], Duration::from_secs(2));
+
+        let mut out = String::new();
+        histogram.render(&mut out);
+        assert_eq!(
+            out,
+            "# HELP alglobo_test_seconds Prueba\n\
+             # TYPE alglobo_test_seconds histogram\n\
+             alglobo_test_seconds_bucket{service=\"Bank\",le=\"0.1\"} 1\n\
+             alglobo_test_seconds_bucket{service=\"Bank\",le=\"1\"} 2\n\
+             alglobo_test_seconds_bucket{service=\"Bank\",le=\"+Inf\"} 3\n\
+             alglobo_test_seconds_sum{service=\"Bank\"} 2.55\n\
+             alglobo_test_seconds_count{service=\"Bank\"} 3\n"
+        );
+    }
+}
@@ -0,0 +1,40 @@
+/// Arma el conjunto de etiquetas `clave="valor",...` en el formato de
+/// exposicion de Prometheus, escapando los valores
+#[must_use]
+pub fn render_labels(labels: &[(&str, &str)]) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", key, value)
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Nombre de una serie con sus etiquetas ya armadas
+#[must_use]
+pub fn series(name: &str, labels: &str) -> String {
+    if labels.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}{{{}}}", name, labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_escape_label_values() {
+        let labels = render_labels(&[("service", "Airline"), ("reason", "id \"bajo\"")]);
+
+        assert_eq!(labels, "service=\"Airline\",reason=\"id \\\"bajo\\\"\"");
+        assert_eq!(series("total", &labels), format!("total{{{}}}", labels));
+        assert_eq!(series("total", ""), "total");
+    }
+}
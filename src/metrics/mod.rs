@@ -0,0 +1,5 @@
+pub mod counter;
+pub mod histogram;
+pub mod labels;
+pub mod registry;
+pub mod server;
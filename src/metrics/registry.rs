@@ -0,0 +1,126 @@
+use std::sync::OnceLock;
+
+use super::{
+    counter::Counter,
+    histogram::{Histogram, DEFAULT_BUCKETS},
+};
+
+/// Metricas del proceso, compartidas por el coordinador, los
+/// candidatos, los servicios y los sockets
+pub struct Metrics {
+    pub transactions: Counter,
+    pub transaction_duration: Histogram,
+    pub prepare_duration: Histogram,
+    pub service_responses: Counter,
+    pub retries: Counter,
+    pub submissions: Counter,
+    pub retransmissions: Counter,
+    pub dropped_messages: Counter,
+    pub elections: Counter,
+    pub leaderships: Counter,
+    pub leader_changes: Counter,
+    pub leader_messages: Counter,
+    pub service_requests: Counter,
+    pub service_request_duration: Histogram,
+}
+
+impl Metrics {
+    #[must_use]
+    pub fn new() -> Self {
+        Metrics {
+            transactions: Counter::new(
+                "alglobo_transactions_total",
+                "Transacciones decididas por el lider, por resultado",
+            ),
+            transaction_duration: Histogram::new(
+                "alglobo_transaction_duration_seconds",
+                "Duracion de la transaccion desde el PREPARAR hasta su fin",
+                DEFAULT_BUCKETS,
+            ),
+            prepare_duration: Histogram::new(
+                "alglobo_prepare_duration_seconds",
+                "Espera de las respuestas al PREPARAR",
+                DEFAULT_BUCKETS,
+            ),
+            service_responses: Counter::new(
+                "alglobo_service_responses_total",
+                "Respuestas de los servicios recibidas por el coordinador",
+            ),
+            retries: Counter::new(
+                "alglobo_retries_total",
+                "Pedidos de reintento recibidos, por resultado",
+            ),
+            submissions: Counter::new(
+                "alglobo_submissions_total",
+                "Reservas recibidas por la API SUBMIT, por resultado",
+            ),
+            retransmissions: Counter::new(
+                "alglobo_retransmissions_total",
+                "Mensajes retransmitidos por el canal confiable",
+            ),
+            dropped_messages: Counter::new(
+                "alglobo_dropped_messages_total",
+                "Mensajes descartados por el canal confiable sin recibir ack",
+            ),
+            elections: Counter::new(
+                "alglobo_elections_total",
+                "Elecciones iniciadas por el nodo",
+            ),
+            leaderships: Counter::new(
+                "alglobo_leaderships_total",
+                "Veces que el nodo asumio el liderazgo",
+            ),
+            leader_changes: Counter::new(
+                "alglobo_leader_changes_total",
+                "Nuevos lideres anunciados por otros nodos",
+            ),
+            leader_messages: Counter::new(
+                "alglobo_leader_messages_total",
+                "Mensajes de eleccion atendidos por el lider, por codigo",
+            ),
+            service_requests: Counter::new(
+                "alglobo_service_requests_total",
+                "Pedidos atendidos por los servicios, por servicio y codigo",
+            ),
+            service_request_duration: Histogram::new(
+                "alglobo_service_request_duration_seconds",
+                "Tiempo de atencion de un pedido por servicio",
+                DEFAULT_BUCKETS,
+            ),
+        }
+    }
+
+    /// Devuelve todas las metricas en el formato de exposicion de
+    /// texto de Prometheus
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.transactions.render(&mut out);
+        self.transaction_duration.render(&mut out);
+        self.prepare_duration.render(&mut out);
+        self.service_responses.render(&mut out);
+        self.retries.render(&mut out);
+        self.submissions.render(&mut out);
+        self.retransmissions.render(&mut out);
+        self.dropped_messages.render(&mut out);
+        self.elections.render(&mut out);
+        self.leaderships.render(&mut out);
+        self.leader_changes.render(&mut out);
+        self.leader_messages.render(&mut out);
+        self.service_requests.render(&mut out);
+        self.service_request_duration.render(&mut out);
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+/// Metricas globales del proceso
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
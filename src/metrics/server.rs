@@ -0,0 +1,88 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread::{self, JoinHandle},
+};
+
+use tracing::{info, warn};
+
+use super::registry::Metrics;
+
+static CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Atiende `GET /metrics` en `addr` con las metricas de `metrics` en
+/// formato de texto de Prometheus. Cualquier otra ruta responde 404.
+///
+/// # Errors
+///
+/// Devuelve error si no se pudo escuchar en `addr`
+pub fn serve(addr: SocketAddr, metrics: &'static Metrics) -> io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr)?;
+    info!(addr = %addr, "Exponiendo metricas en /metrics");
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(err) = answer(stream, metrics) {
+                warn!(error = %err, "Pedido de metricas fallido");
+            }
+        }
+    }))
+}
+
+fn answer(stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Se consumen los encabezados, que no se usan
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let path = request_line.split_whitespace().nth(1).unwrap_or("");
+    let (status, body) = if request_line.starts_with("GET ") && path == "/metrics" {
+        ("200 OK", metrics.render())
+    } else {
+        ("404 Not Found", String::new())
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        CONTENT_TYPE,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ntest::timeout;
+    use std::{io::Read, sync::OnceLock};
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_serve_the_metrics_in_text_format() {
+        static METRICS: OnceLock<Metrics> = OnceLock::new();
+        let metrics = METRICS.get_or_init(Metrics::new);
+        let addr: SocketAddr = "127.0.0.1:59202".parse().unwrap();
+        serve(addr, metrics).unwrap();
+
+        metrics.transactions.inc(&[("outcome", "commit")]);
+
+        let response = get(addr, "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+        assert!(response.contains("alglobo_transactions_total{outcome=\"commit\"} 1\n"));
+        assert!(get(addr, "/otra").starts_with("HTTP/1.1 404 Not Found"));
+    }
+}
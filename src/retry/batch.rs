@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::fs;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use super::transaction_retrier::TransactionRetrier;
+use crate::alglobo::{
+    aborts::{AbortReason, AbortRecord},
+    transaction::Transaction,
+};
+use crate::services::service_name::ServiceName;
+use crate::sockets::udp_socket_receiver::UdpSocketReceiver;
+use crate::transaction_messages::transaction_retry_ack::RetryReply;
+
+const SERVICES: [ServiceName; 3] = [ServiceName::Airline, ServiceName::Hotel, ServiceName::Bank];
+
+/// Transaccion leida de un archivo de abortos
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbortedTransaction {
+    pub id: u64,
+    pub fees: HashMap<String, f64>,
+    /// Motivo del aborto; las filas del formato CSV anterior no lo tienen
+    pub reason_opt: Option<AbortReason>,
+}
+
+impl AbortedTransaction {
+    #[must_use]
+    pub fn transaction(&self) -> Transaction {
+        Transaction::new(self.id, &self.fees)
+    }
+
+    fn from_record(record: AbortRecord) -> Result<Self, String> {
+        let fees: HashMap<String, f64> = record
+            .services
+            .into_iter()
+            .map(|(name, vote)| (name, vote.fee))
+            .collect();
+        if let Some(service) = SERVICES
+            .iter()
+            .find(|service| !fees.contains_key(&service.string_name()))
+        {
+            return Err(format!("falta el monto de {}", service.string_name()));
+        }
+        Ok(AbortedTransaction {
+            id: record.id,
+            fees,
+            reason_opt: Some(record.reason),
+        })
+    }
+
+    /// Fila `id,aerolinea,hotel,banco` del formato CSV anterior
+    fn from_legacy_row(row: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = row.split(',').map(str::trim).collect();
+        if fields.len() != 1 + SERVICES.len() {
+            return Err("cantidad de campos invalida".to_string());
+        }
+        let id = fields[0]
+            .parse()
+            .map_err(|_| format!("id invalido: {}", fields[0]))?;
+        let mut fees = HashMap::new();
+        for (service, field) in SERVICES.iter().zip(&fields[1..]) {
+            let fee = field
+                .parse()
+                .map_err(|_| format!("monto de {} invalido: {}", service.string_name(), field))?;
+            fees.insert(service.string_name(), fee);
+        }
+        Ok(AbortedTransaction {
+            id,
+            fees,
+            reason_opt: None,
+        })
+    }
+}
+
+/// Lee las transacciones abortadas de `path`, sea el registro JSON Lines
+/// o el CSV anterior. Si una transaccion se aborto varias veces queda su
+/// ultimo aborto, en la posicion del primero.
+/// # Errors:
+/// Arroja error si no es posible leer el archivo
+pub fn load(path: &str) -> Result<Vec<AbortedTransaction>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("No se pudo leer el archivo de abortos: {}", err))?;
+    let mut aborted: Vec<AbortedTransaction> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parsed = if line.starts_with('{') {
+            serde_json::from_str(line)
+                .map_err(|err| format!("JSON invalido: {}", err))
+                .and_then(AbortedTransaction::from_record)
+        } else {
+            AbortedTransaction::from_legacy_row(line)
+        };
+        match parsed {
+            Ok(transaction) => match aborted.iter_mut().find(|prev| prev.id == transaction.id) {
+                Some(prev) => *prev = transaction,
+                None => aborted.push(transaction),
+            },
+            Err(reason) => warn!(line = index + 1, reason = %reason, "Aborto ignorado"),
+        }
+    }
+    Ok(aborted)
+}
+
+/// Criterio para elegir que abortos reintentar. Sin motivos se
+/// aceptan todos, incluidas las filas sin motivo.
+#[derive(Debug, Default, Clone)]
+pub struct BatchFilter {
+    pub min_id_opt: Option<u64>,
+    pub max_id_opt: Option<u64>,
+    pub reasons: Vec<AbortReason>,
+}
+
+impl BatchFilter {
+    #[must_use]
+    pub fn matches(&self, aborted: &AbortedTransaction) -> bool {
+        self.min_id_opt.is_none_or(|min_id| aborted.id >= min_id)
+            && self.max_id_opt.is_none_or(|max_id| aborted.id <= max_id)
+            && (self.reasons.is_empty()
+                || aborted
+                    .reason_opt
+                    .is_some_and(|reason| self.reasons.contains(&reason)))
+    }
+}
+
+/// Resultado de un reintento por lotes
+#[derive(Debug, Default, PartialEq)]
+pub struct BatchSummary {
+    /// Respuesta a cada reintento, `None` si no la hubo y
+    /// `RetryReply::Granted` si se concedio pero su resultado no llego
+    pub outcomes: Vec<(u64, Option<RetryReply>)>,
+    pub skipped: usize,
+}
+
+impl BatchSummary {
+    fn ids(&self, matches: fn(&Option<RetryReply>) -> bool) -> Vec<u64> {
+        self.outcomes
+            .iter()
+            .filter(|(_, reply_opt)| matches(reply_opt))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    #[must_use]
+    pub fn report(&self) -> String {
+        let commited = self.ids(|reply_opt| *reply_opt == Some(RetryReply::Commited));
+        let aborted = self.ids(|reply_opt| *reply_opt == Some(RetryReply::Aborted));
+        let pending = self.ids(|reply_opt| *reply_opt == Some(RetryReply::Granted));
+        let unanswered = self.ids(Option::is_none);
+        let denied: Vec<String> = self
+            .outcomes
+            .iter()
+            .filter_map(|(id, reply_opt)| match reply_opt {
+                Some(RetryReply::Denied(reason)) => Some(format!("{} ({})", id, reason)),
+                _ => None,
+            })
+            .collect();
+        format!(
+            "Commiteados: {} {:?}\nAbortados: {} {:?}\nDenegados: {} {:?}\nConcedidos con resultado pendiente: {} {:?}\nSin respuesta: {} {:?}\nAbortos filtrados: {}",
+            commited.len(),
+            commited,
+            aborted.len(),
+            aborted,
+            denied.len(),
+            denied,
+            pending.len(),
+            pending,
+            unanswered.len(),
+            unanswered,
+            self.skipped
+        )
+    }
+}
+
+/// Reintenta de a uno los abortos que cumplen `filter`, esperando cada
+/// resultado por `replies` y al menos `interval` entre envios, para no
+/// llenar la cola de reintentos del lider.
+pub fn retry_batch(
+    retrier: &mut TransactionRetrier,
+    replies: &mut dyn UdpSocketReceiver,
+    aborted: &[AbortedTransaction],
+    filter: &BatchFilter,
+    interval: Duration,
+) -> BatchSummary {
+    let mut summary = BatchSummary::default();
+    let selected: Vec<&AbortedTransaction> = aborted
+        .iter()
+        .filter(|transaction| filter.matches(transaction))
+        .collect();
+    summary.skipped = aborted.len() - selected.len();
+    info!(
+        selected = selected.len(),
+        skipped = summary.skipped,
+        "Reintentando abortos"
+    );
+    let mut last_opt: Option<Instant> = None;
+    for transaction in selected {
+        if let Some(last) = last_opt {
+            sleep(interval.saturating_sub(last.elapsed()));
+        }
+        last_opt = Some(Instant::now());
+        let reply_opt = retrier.retry(&transaction.transaction(), replies);
+        summary.outcomes.push((transaction.id, reply_opt));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sockets::{
+        socket_error::SocketError, udp_socket_receiver::MockUdpSocketReceiver,
+        udp_socket_sender::MockUdpSocketSender,
+    };
+    use crate::transaction_messages::{
+        transaction_info::TransactionInfo, transaction_retry::TransactionRetry,
+        transaction_retry_ack::TransactionRetryAck,
+    };
+    use std::{env, net::SocketAddr};
+
+    #[test]
+    fn it_should_retry_the_selected_aborts_of_both_formats() {
+        let path = env::temp_dir().join(format!("alglobo_{}_batch.jsonl", std::process::id()));
+        fs::write(
+            &path,
+            "1,100,200,300\n\
+             no es un aborto\n\
+             {\"id\":2,\"timestamp_ms\":0,\"reason\":\"vote\",\"attempts\":1,\"services\":{\
+             \"Airline\":{\"state\":\"aborted\",\"fee\":1.0},\
+             \"Hotel\":{\"state\":\"accepted\",\"fee\":2.0},\
+             \"Bank\":{\"state\":\"accepted\",\"fee\":3.0}}}\n\
+             {\"id\":3,\"timestamp_ms\":0,\"reason\":\"timeout\",\"attempts\":1,\"services\":{\
+             \"Airline\":{\"state\":\"waiting\",\"fee\":1.0},\
+             \"Hotel\":{\"state\":\"accepted\",\"fee\":2.0},\
+             \"Bank\":{\"state\":\"accepted\",\"fee\":3.0}}}\n\
+             {\"id\":2,\"timestamp_ms\":1,\"reason\":\"timeout\",\"attempts\":2,\"services\":{\
+             \"Airline\":{\"state\":\"waiting\",\"fee\":4.0},\
+             \"Hotel\":{\"state\":\"accepted\",\"fee\":2.0},\
+             \"Bank\":{\"state\":\"accepted\",\"fee\":3.0}}}\n",
+        )
+        .unwrap();
+        let aborted = load(&path.to_string_lossy()).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let ids: Vec<u64> = aborted.iter().map(|transaction| transaction.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(aborted[0].reason_opt, None);
+        assert_eq!(aborted[1].reason_opt, Some(AbortReason::Timeout));
+
+        let replica: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let mut mock_sender = MockUdpSocketSender::new();
+        let mut retried = TransactionRetry::build(2, 4.0, 2.0, 3.0);
+        TransactionInfo::add_padding(&mut retried);
+        mock_sender
+            .expect_send_to()
+            .withf(move |buf, _| buf.to_vec() == retried)
+            .times(1)
+            .returning(|_, _| Ok(()));
+        mock_sender
+            .expect_send_to()
+            .times(1)
+            .returning(|_, _| Err(SocketError::ZeroBytes));
+        let mut retrier = TransactionRetrier::new(vec![replica], Box::new(mock_sender));
+        let mut mock_receiver = MockUdpSocketReceiver::new();
+        mock_receiver.expect_set_timeout().returning(|_| ());
+        mock_receiver.expect_recv().times(1).returning(move |_| {
+            let mut reply = TransactionRetryAck::build(2, RetryReply::Commited);
+            TransactionInfo::add_padding(&mut reply);
+            Ok((reply, replica))
+        });
+        let filter = BatchFilter {
+            min_id_opt: Some(2),
+            max_id_opt: None,
+            reasons: vec![AbortReason::Timeout],
+        };
+
+        let summary = retry_batch(
+            &mut retrier,
+            &mut mock_receiver,
+            &aborted,
+            &filter,
+            Duration::from_millis(1),
+        );
+
+        assert_eq!(
+            summary,
+            BatchSummary {
+                outcomes: vec![(2, Some(RetryReply::Commited)), (3, None)],
+                skipped: 1
+            }
+        );
+        assert!(summary.report().starts_with("Commiteados: 1 [2]"));
+    }
+
+    #[test]
+    fn report_should_list_the_granted_retries_whose_result_is_pending() {
+        let summary = BatchSummary {
+            outcomes: vec![(4, Some(RetryReply::Granted)), (5, None)],
+            skipped: 0,
+        };
+
+        let report = summary.report();
+        assert!(report.contains("Concedidos con resultado pendiente: 1 [4]"));
+        assert!(report.contains("Sin respuesta: 1 [5]"));
+    }
+}
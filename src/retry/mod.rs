@@ -1 +1,2 @@
+pub mod batch;
 pub mod transaction_retrier;
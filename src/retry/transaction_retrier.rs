@@ -1,32 +1,142 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info, warn};
+
 use crate::{
-    alglobo::transactionable::Transactionable, sockets::udp_socket_sender::UdpSocketSender,
+    alglobo::transactionable::Transactionable,
+    sockets::{
+        socket_error::SocketError, udp_socket_receiver::UdpSocketReceiver,
+        udp_socket_sender::UdpSocketSender,
+    },
+    transaction_messages::{
+        transaction_info::TransactionInfo,
+        transaction_retry_ack::{DenyReason, RetryReply, TransactionRetryAck},
+    },
 };
 
+/// Tiempo maximo de espera de la respuesta a un reintento
+pub const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Tiempo maximo de espera del resultado de un reintento concedido,
+/// que el lider puede tener encolado detras de otros
+pub const RESULT_TIMEOUT: Duration = Duration::from_secs(60);
+
 pub struct TransactionRetrier {
-    replicas_addrs: Vec<String>,
+    replicas_addrs: Vec<SocketAddr>,
     udp_sender: Box<dyn UdpSocketSender>,
+    reply_timeout: Duration,
+    result_timeout: Duration,
 }
 
 impl TransactionRetrier {
     #[must_use]
-    pub fn new(replicas_addrs: Vec<String>, udp_sender: Box<dyn UdpSocketSender>) -> Self {
+    pub fn new(replicas_addrs: Vec<SocketAddr>, udp_sender: Box<dyn UdpSocketSender>) -> Self {
         TransactionRetrier {
             replicas_addrs,
             udp_sender,
+            reply_timeout: REPLY_TIMEOUT,
+            result_timeout: RESULT_TIMEOUT,
         }
     }
 
-    pub fn process(&mut self, transaction: &dyn Transactionable) {
+    #[must_use]
+    pub fn with_reply_timeout(mut self, reply_timeout: Duration) -> Self {
+        self.reply_timeout = reply_timeout;
+        self
+    }
+
+    /// Espera del resultado una vez concedido el reintento
+    #[must_use]
+    pub fn with_result_timeout(mut self, result_timeout: Duration) -> Self {
+        self.result_timeout = result_timeout;
+        self
+    }
+
+    /// Envia el reintento y espera la respuesta por `replies`: la
+    /// denegacion o, si el lider lo concede, el resultado de la
+    /// transaccion. Devuelve `RetryReply::Granted` si se concedio pero
+    /// el resultado no llego a tiempo, y `None` si no se pudo enviar o
+    /// no hubo respuesta a tiempo.
+    pub fn retry(
+        &mut self,
+        transaction: &dyn Transactionable,
+        replies: &mut dyn UdpSocketReceiver,
+    ) -> Option<RetryReply> {
+        let id = transaction.get_id();
+        if let Err(err) = self.process(transaction) {
+            warn!(transaction_id = id, error = %err, "No se pudo enviar el reintento");
+            return None;
+        }
+        let reply_opt = self.wait_reply(id, replies);
+        match reply_opt {
+            Some(RetryReply::Granted) => warn!(
+                transaction_id = id,
+                "Reintento concedido, pero su resultado sigue pendiente"
+            ),
+            Some(reply) => info!(transaction_id = id, reply = %reply, "Resultado del reintento"),
+            None => warn!(transaction_id = id, "Reintento sin respuesta"),
+        }
+        reply_opt
+    }
+
+    /// Todas las replicas deben contestar que no son lider para
+    /// darlo por denegado: el lider responde por su cuenta. Una vez
+    /// concedido, el resultado se espera hasta `result_timeout`.
+    fn wait_reply(&self, id: u64, replies: &mut dyn UdpSocketReceiver) -> Option<RetryReply> {
+        let mut deadline = Instant::now() + self.reply_timeout;
+        let mut granted = false;
+        let mut not_leaders = HashSet::new();
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return granted.then_some(RetryReply::Granted);
+            }
+            replies.set_timeout(Some(remaining));
+            let (message, addr) = match replies.recv(TransactionInfo::size()) {
+                Ok(value) => value,
+                Err(SocketError::Timeout) => return granted.then_some(RetryReply::Granted),
+                Err(err) => {
+                    debug!(error = %err, "Respuesta de reintento descartada");
+                    continue;
+                }
+            };
+            match TransactionRetryAck::parse(&message) {
+                Some((reply_id, reply)) if reply_id == id => match reply {
+                    RetryReply::Granted if !granted => {
+                        info!(transaction_id = id, replica = %addr, "Reintento concedido");
+                        granted = true;
+                        deadline = Instant::now() + self.result_timeout;
+                    }
+                    RetryReply::Granted => {}
+                    RetryReply::Denied(DenyReason::NotLeader) => {
+                        not_leaders.insert(addr);
+                        if not_leaders.len() >= self.replicas_addrs.len() {
+                            return Some(reply);
+                        }
+                    }
+                    _ => return Some(reply),
+                },
+                _ => debug!(replica = %addr, "Mensaje ajeno al reintento descartado"),
+            }
+        }
+    }
+
+    /// Envia el reintento a todas las replicas
+    /// # Errors:
+    /// Devuelve el error del primer envio fallido
+    pub fn process(&mut self, transaction: &dyn Transactionable) -> Result<(), SocketError> {
         let msg = transaction.retry();
         for addr in &self.replicas_addrs {
-            println!(
-                "[Transaction Retrier] Enviando reintento de transaccion a {}",
-                addr
+            info!(
+                transaction_id = transaction.get_id(),
+                replica = %addr,
+                "Enviando reintento de transaccion"
             );
-            self.udp_sender
-                .send_to(&msg, addr)
-                .expect("[Transaction Retrier] Enviar reintento no deberia fallar");
+            self.udp_sender.send_to(&msg, addr)?;
         }
+        Ok(())
     }
 }
 
@@ -36,18 +146,18 @@ mod tests {
 
     use crate::{
         alglobo::transactionable::MockTransactionable,
-        sockets::udp_socket_sender::MockUdpSocketSender,
-        transaction_messages::{
-            transaction_info::TransactionInfo, transaction_retry::TransactionRetry,
+        sockets::{
+            udp_socket_receiver::MockUdpSocketReceiver, udp_socket_sender::MockUdpSocketSender,
         },
+        transaction_messages::transaction_retry::TransactionRetry,
     };
 
     #[test]
     fn it_should_send_message_transaction_retry_to_all_alglobo_replicas() {
-        let replicas_addrs = vec![
-            "127.0.0.1:49152".to_string(),
-            "127.0.0.1:49353".to_string(),
-            "127.0.0.1:49354".to_string(),
+        let replicas_addrs: Vec<SocketAddr> = vec![
+            "127.0.0.1:49152".parse().unwrap(),
+            "127.0.0.1:49353".parse().unwrap(),
+            "127.0.0.1:49354".parse().unwrap(),
         ];
 
         let mut message = TransactionRetry::build(0, 100.0, 200.0, 300.0);
@@ -59,7 +169,7 @@ mod tests {
         mock_sender
             .expect_send_to()
             .withf(move |buf, addr| {
-                buf.to_vec() == message_clone && replicas_addrs_clone.contains(&addr.to_string())
+                buf.to_vec() == message_clone && replicas_addrs_clone.contains(addr)
             })
             .times(3)
             .returning(|_, _| Ok(()));
@@ -72,7 +182,109 @@ mod tests {
             .expect_retry()
             .times(1)
             .returning(move || message.clone());
+        mock_transaction.expect_get_id().returning(|| 0);
+
+        assert!(retrier.process(&mock_transaction).is_ok());
+    }
+
+    #[test]
+    fn retry_should_wait_for_the_result_of_the_leader() {
+        let leader: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let follower: SocketAddr = "127.0.0.1:49353".parse().unwrap();
+        let mut mock_sender = MockUdpSocketSender::new();
+        mock_sender
+            .expect_send_to()
+            .times(2)
+            .returning(|_, _| Ok(()));
+        let mut retrier = TransactionRetrier::new(vec![leader, follower], Box::new(mock_sender));
+
+        let mut replies = vec![
+            (TransactionRetryAck::build(7, RetryReply::Commited), leader),
+            (TransactionRetryAck::build(6, RetryReply::Aborted), leader),
+            (TransactionRetryAck::build(7, RetryReply::Granted), leader),
+            (
+                TransactionRetryAck::build(7, RetryReply::Denied(DenyReason::NotLeader)),
+                follower,
+            ),
+        ];
+        let mut mock_receiver = MockUdpSocketReceiver::new();
+        mock_receiver.expect_set_timeout().returning(|_| ());
+        mock_receiver
+            .expect_recv()
+            .times(4)
+            .returning(move |_| Ok(replies.pop().unwrap()));
+
+        let mut mock_transaction = MockTransactionable::new();
+        mock_transaction
+            .expect_retry()
+            .returning(|| TransactionRetry::build(7, 1.0, 1.0, 1.0));
+        mock_transaction.expect_get_id().returning(|| 7);
+
+        assert_eq!(
+            retrier.retry(&mock_transaction, &mut mock_receiver),
+            Some(RetryReply::Commited)
+        );
+    }
+
+    fn granted_retrier() -> (TransactionRetrier, MockTransactionable) {
+        let mut mock_sender = MockUdpSocketSender::new();
+        mock_sender.expect_send_to().returning(|_, _| Ok(()));
+        let retrier = TransactionRetrier::new(
+            vec!["127.0.0.1:49152".parse().unwrap()],
+            Box::new(mock_sender),
+        )
+        .with_reply_timeout(Duration::from_millis(50))
+        .with_result_timeout(Duration::from_millis(500));
+        let mut mock_transaction = MockTransactionable::new();
+        mock_transaction
+            .expect_retry()
+            .returning(|| TransactionRetry::build(7, 1.0, 1.0, 1.0));
+        mock_transaction.expect_get_id().returning(|| 7);
+        (retrier, mock_transaction)
+    }
+
+    #[test]
+    fn retry_should_wait_the_result_timeout_once_granted() {
+        let (mut retrier, mock_transaction) = granted_retrier();
+        let leader: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let mut replies = vec![
+            TransactionRetryAck::build(7, RetryReply::Commited),
+            TransactionRetryAck::build(7, RetryReply::Granted),
+        ];
+        let mut mock_receiver = MockUdpSocketReceiver::new();
+        mock_receiver.expect_set_timeout().returning(|_| ());
+        mock_receiver.expect_recv().times(2).returning(move |_| {
+            let reply = replies.pop().unwrap();
+            // El resultado llega despues de la espera de la respuesta
+            if replies.is_empty() {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Ok((reply, leader))
+        });
+
+        assert_eq!(
+            retrier.retry(&mock_transaction, &mut mock_receiver),
+            Some(RetryReply::Commited)
+        );
+    }
+
+    #[test]
+    fn retry_should_return_granted_if_the_result_is_still_pending() {
+        let (mut retrier, mock_transaction) = granted_retrier();
+        let leader: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let mut granted = Some(TransactionRetryAck::build(7, RetryReply::Granted));
+        let mut mock_receiver = MockUdpSocketReceiver::new();
+        mock_receiver.expect_set_timeout().returning(|_| ());
+        mock_receiver
+            .expect_recv()
+            .returning(move |_| match granted.take() {
+                Some(reply) => Ok((reply, leader)),
+                None => Err(SocketError::Timeout),
+            });
 
-        retrier.process(&mock_transaction);
+        assert_eq!(
+            retrier.retry(&mock_transaction, &mut mock_receiver),
+            Some(RetryReply::Granted)
+        );
     }
 }
@@ -0,0 +1,442 @@
+use std::{
+    net::SocketAddr,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use tokio::{
+    runtime::Runtime,
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+};
+use tracing::Span;
+
+use crate::sockets::{
+    async_udp_socket_receiver::AsyncUdpSocketReceiver,
+    async_udp_socket_sender::AsyncUdpSocketSender,
+    async_udp_socket_wrap::AsyncUdpSocketWrap,
+    channel_socket::{ChannelSocketReceiver, ChannelSocketSender, Datagram},
+    fragment::MAX_MESSAGE_SIZE,
+    socket_error::SocketError,
+    udp_socket_receiver::UdpSocketReceiver,
+    udp_socket_sender::UdpSocketSender,
+};
+
+use super::participant::Participant;
+
+/// Cada cuanto se despierta a los participantes aunque no lleguen
+/// datagramas, para que hagan sus tareas periodicas (por ejemplo
+/// retransmitir los mensajes confiables sin ack)
+pub const TICK: Duration = Duration::from_millis(50);
+
+/// Event loop que atiende a varios participantes desde un mismo
+/// proceso. Cada participante tiene su propio socket asincronico y se
+/// comunica con el a traves de un `ChannelSocketReceiver` y un
+/// `ChannelSocketSender`, sin necesitar un hilo dedicado. Sus pasos
+/// son sincronicos, por lo que se ejecutan en el pool de tareas
+/// bloqueantes del runtime y no en sus workers.
+///
+/// Es un handle: los clones comparten el runtime, por lo que varios
+/// nodos pueden alojar sus sockets en el mismo event loop. En los
+/// nodos candidatos corren en el event loop los sockets, el receptor
+/// de transacciones y el canal confiable; la eleccion y el
+/// coordinador siguen en un hilo por nodo porque esperan respuestas
+/// con timeouts y variables de condicion.
+#[derive(Clone)]
+pub struct EventLoop {
+    runtime: Arc<Runtime>,
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+/// Emisor y receptor sincronicos de un socket del event loop
+pub type Endpoints = (
+    Box<dyn UdpSocketSender + Send>,
+    Box<dyn UdpSocketReceiver + Send>,
+);
+
+/// Socket bindeado en el event loop que todavia no tiene participante
+pub struct Binding {
+    socket: AsyncUdpSocketWrap,
+    incoming: mpsc::Sender<Datagram>,
+    outgoing: UnboundedReceiver<Datagram>,
+}
+
+/// Entrada de un participante para los datagramas que no llegan por
+/// su socket, como los de conexiones TCP
+#[derive(Clone)]
+pub struct Inbox {
+    incoming: mpsc::Sender<Datagram>,
+    wake: UnboundedSender<()>,
+}
+
+impl Inbox {
+    /// Entrega `datagram` al participante y lo despierta para que lo
+    /// procese
+    ///
+    /// # Errors
+    ///
+    /// Devuelve error si el participante ya termino
+    pub fn deliver(&self, datagram: Datagram) -> Result<(), SocketError> {
+        self.incoming
+            .send(datagram)
+            .map_err(|_| SocketError::ZeroBytes)?;
+        self.wake.send(()).map_err(|_| SocketError::ZeroBytes)
+    }
+
+    /// Entrega al participante todo lo que llegue por `receiver` hasta
+    /// que se cierre. Los transportes bloqueantes no corren en el
+    /// event loop, por lo que se lee desde un hilo propio.
+    pub fn forward(self, mut receiver: Box<dyn UdpSocketReceiver + Send>) {
+        thread::spawn(move || loop {
+            match receiver.recv(MAX_MESSAGE_SIZE) {
+                Ok(datagram) => {
+                    if self.deliver(datagram).is_err() {
+                        break;
+                    }
+                }
+                Err(SocketError::ZeroBytes) => break,
+                Err(_) => {}
+            }
+        });
+    }
+}
+
+/// Participante que pasa a un canal lo que llega por su receptor, para
+/// usuarios que esperan mensajes con timeouts desde su propio hilo
+struct Relay {
+    receiver: Box<dyn UdpSocketReceiver + Send>,
+    delivered: mpsc::Sender<Datagram>,
+}
+
+impl Participant for Relay {
+    fn step(&mut self) {
+        loop {
+            match self.receiver.recv(MAX_MESSAGE_SIZE) {
+                Ok(datagram) => {
+                    // Sin usuario el mensaje se descarta
+                    if self.delivered.send(datagram).is_err() {
+                        break;
+                    }
+                }
+                Err(SocketError::Timeout | SocketError::ZeroBytes) => break,
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+impl EventLoop {
+    /// # Errors
+    ///
+    /// Devuelve error si no fue posible crear el runtime
+    pub fn new() -> Result<Self, String> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .map_err(|_| "[Event Loop] No se pudo crear el runtime".to_string())?;
+        Ok(EventLoop {
+            runtime: Arc::new(runtime),
+            tasks: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    /// Bindea un socket en `addr` y devuelve, junto al socket, el
+    /// emisor y el receptor no bloqueante con los que se construye su
+    /// participante
+    ///
+    /// # Errors
+    ///
+    /// Devuelve error si no fue posible bindear el socket
+    pub fn bind(&self, addr: SocketAddr) -> Result<(Binding, Endpoints), String> {
+        let socket = self
+            .runtime
+            .block_on(AsyncUdpSocketWrap::new_with_addr(None, addr))?;
+        let (incoming, incoming_receiver) = mpsc::channel();
+        let (outgoing_sender, outgoing) = unbounded_channel();
+        Ok((
+            Binding {
+                socket,
+                incoming,
+                outgoing,
+            },
+            (
+                Box::new(ChannelSocketSender::new(outgoing_sender)),
+                Box::new(ChannelSocketReceiver::non_blocking(incoming_receiver)),
+            ),
+        ))
+    }
+
+    /// Pone a correr a `participant` sobre el socket de `binding`. Sus
+    /// pasos se ejecutan dentro del span vigente al llamar a `start`.
+    pub fn start<P: Participant>(&self, binding: Binding, participant: P) -> Inbox {
+        let (wake, wake_receiver) = unbounded_channel();
+        let inbox = Inbox {
+            incoming: binding.incoming.clone(),
+            wake,
+        };
+        let task = self.runtime.spawn(run_participant(
+            binding,
+            participant,
+            wake_receiver,
+            Span::current(),
+        ));
+        self.tasks
+            .lock()
+            .expect("[Event Loop] Lock de tareas envenenado")
+            .push(task);
+        inbox
+    }
+
+    /// Bindea un socket en `addr` y registra al participante que
+    /// construye `build` con el receptor y el emisor de ese socket
+    ///
+    /// # Errors
+    ///
+    /// Devuelve error si no fue posible bindear el socket
+    pub fn host<P, F>(&self, addr: SocketAddr, build: F) -> Result<Inbox, String>
+    where
+        P: Participant,
+        F: FnOnce(Box<dyn UdpSocketReceiver + Send>, Box<dyn UdpSocketSender + Send>) -> P,
+    {
+        let (binding, (sender, receiver)) = self.bind(addr)?;
+        Ok(self.start(binding, build(receiver, sender)))
+    }
+
+    /// Bindea un socket en `addr` para un usuario que corre en su
+    /// propio hilo. `wrap` recibe el emisor y el receptor no bloqueante
+    /// del socket y devuelve la pila que se quiere usar (seguridad,
+    /// canal confiable); esa pila la atiende el event loop y el
+    /// receptor devuelto espera por un canal, respetando su timeout.
+    ///
+    /// # Errors
+    ///
+    /// Devuelve error si no fue posible bindear el socket
+    pub fn attach<F>(&self, addr: SocketAddr, wrap: F) -> Result<Endpoints, String>
+    where
+        F: FnOnce(Box<dyn UdpSocketSender + Send>, Box<dyn UdpSocketReceiver + Send>) -> Endpoints,
+    {
+        let (binding, (sender, receiver)) = self.bind(addr)?;
+        let (sender, receiver) = wrap(sender, receiver);
+        let (delivered, delivered_receiver) = mpsc::channel();
+        self.start(
+            binding,
+            Relay {
+                receiver,
+                delivered,
+            },
+        );
+        Ok((
+            sender,
+            Box::new(ChannelSocketReceiver::new(delivered_receiver)),
+        ))
+    }
+
+    /// Bloquea hasta que terminen todos los participantes registrados
+    pub fn run(&self) {
+        let tasks: Vec<JoinHandle<()>> = self
+            .tasks
+            .lock()
+            .expect("[Event Loop] Lock de tareas envenenado")
+            .drain(..)
+            .collect();
+        self.runtime.block_on(async move {
+            for task in tasks {
+                let _drop = task.await;
+            }
+        });
+    }
+}
+
+async fn run_participant<P: Participant>(
+    binding: Binding,
+    mut participant: P,
+    mut wake: UnboundedReceiver<()>,
+    span: Span,
+) {
+    let Binding {
+        socket,
+        incoming,
+        mut outgoing,
+    } = binding;
+    let mut socket_recv = socket.clone();
+    let mut socket_send = socket;
+    let mut tick = tokio::time::interval(TICK);
+    loop {
+        tokio::select! {
+            result = socket_recv.recv(MAX_MESSAGE_SIZE) => {
+                match result {
+                    Ok(datagram) => {
+                        if incoming.send(datagram).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => continue,
+                }
+            }
+            Some(()) = wake.recv() => {}
+            _ = tick.tick() => {}
+            Some((message, addr)) = outgoing.recv() => {
+                let _drop = socket_send.send_to(&message, &addr).await;
+                continue;
+            }
+        }
+        let span = span.clone();
+        let step = tokio::task::spawn_blocking(move || {
+            span.in_scope(|| participant.step());
+            participant
+        });
+        participant = match step.await {
+            Ok(value) => value,
+            // El participante paniqueo
+            Err(_) => break,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::airline_client::Airline;
+    use crate::sockets::constants::UDP_PACKET_SIZE;
+    use crate::sockets::{
+        reliable_socket::{maybe_polled_reliable, reliable_pair, ReliableConfig},
+        udp_socket_wrap::UdpSocketWrap,
+    };
+    use crate::transaction_messages::{
+        transaction_code::TransactionCode, transaction_info::TransactionInfo,
+        transaction_request::TransactionRequest, transaction_response::TransactionResponse,
+    };
+    use ntest::timeout;
+    use std::{net::UdpSocket, thread, time::Duration};
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_answer_prepare_from_a_hosted_service() {
+        let airline_addr: SocketAddr = "127.0.0.1:59166".parse().unwrap();
+        let client_addr: SocketAddr = "127.0.0.1:59167".parse().unwrap();
+        let event_loop = EventLoop::new().unwrap();
+        event_loop
+            .host(airline_addr, |receiver, sender| {
+                Airline::new(receiver, sender, airline_addr, 0)
+            })
+            .unwrap();
+        thread::spawn(move || event_loop.run());
+
+        let client = UdpSocket::bind(client_addr).unwrap();
+        let request = TransactionRequest::build(TransactionCode::Prepare, 3, 100.0);
+        client.send_to(&request, airline_addr).unwrap();
+
+        let mut expected = TransactionResponse::build(TransactionCode::Accept, 3);
+        TransactionInfo::add_padding(&mut expected);
+        let mut buf = [0; UDP_PACKET_SIZE];
+        let (n, from) = client.recv_from(&mut buf).unwrap();
+        assert_eq!(from, airline_addr);
+        assert_eq!(buf[..n].to_vec(), expected);
+    }
+
+    #[test]
+    #[timeout(5000)]
+    fn it_should_keep_answering_from_a_reliable_hosted_service() {
+        let airline_addr: SocketAddr = "127.0.0.1:59209".parse().unwrap();
+        let client_addr: SocketAddr = "127.0.0.1:59210".parse().unwrap();
+        let config = ReliableConfig {
+            retransmit_timeout: Duration::from_millis(50),
+            max_attempts: 3,
+        };
+        let event_loop = EventLoop::new().unwrap();
+        event_loop
+            .host(airline_addr, move |receiver, sender| {
+                let (sender, receiver) = maybe_polled_reliable(sender, receiver, Some(config));
+                Airline::new(receiver, sender, airline_addr, 0)
+            })
+            .unwrap();
+        thread::spawn(move || event_loop.run());
+
+        let socket =
+            UdpSocketWrap::new_with_addr(Some(Duration::from_millis(1000)), client_addr).unwrap();
+        let clone = socket.try_clone().unwrap();
+        let (mut sender, mut receiver) = reliable_pair(Box::new(socket), Box::new(clone), config);
+
+        // Los acks de cada respuesta no deben trabar al servicio
+        for transaction_id in [3, 4] {
+            let request =
+                TransactionRequest::build(TransactionCode::Prepare, transaction_id, 100.0);
+            sender.send_to(&request, &airline_addr).unwrap();
+
+            let mut expected = TransactionResponse::build(TransactionCode::Accept, transaction_id);
+            TransactionInfo::add_padding(&mut expected);
+            let (response, from) = receiver.recv(UDP_PACKET_SIZE).unwrap();
+            assert_eq!(from, airline_addr);
+            assert_eq!(response, expected);
+        }
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_deliver_to_a_hosted_service_through_its_inbox() {
+        let airline_addr: SocketAddr = "127.0.0.1:59223".parse().unwrap();
+        let client_addr: SocketAddr = "127.0.0.1:59224".parse().unwrap();
+        let event_loop = EventLoop::new().unwrap();
+        let inbox = event_loop
+            .host(airline_addr, |receiver, sender| {
+                Airline::new(receiver, sender, airline_addr, 0)
+            })
+            .unwrap();
+
+        let client = UdpSocket::bind(client_addr).unwrap();
+        let request = TransactionRequest::build(TransactionCode::Prepare, 5, 100.0);
+        inbox.deliver((request, client_addr)).unwrap();
+
+        // La respuesta sale por el socket del servicio
+        let mut expected = TransactionResponse::build(TransactionCode::Accept, 5);
+        TransactionInfo::add_padding(&mut expected);
+        let mut buf = [0; UDP_PACKET_SIZE];
+        let (n, from) = client.recv_from(&mut buf).unwrap();
+        assert_eq!(from, airline_addr);
+        assert_eq!(buf[..n].to_vec(), expected);
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn an_attached_user_should_wait_with_its_own_timeout() {
+        let attached_addr: SocketAddr = "127.0.0.1:59225".parse().unwrap();
+        let peer_addr: SocketAddr = "127.0.0.1:59226".parse().unwrap();
+        let config = ReliableConfig {
+            retransmit_timeout: Duration::from_millis(50),
+            max_attempts: 3,
+        };
+        let event_loop = EventLoop::new().unwrap();
+        let (mut sender, mut receiver) = event_loop
+            .attach(attached_addr, move |sender, receiver| {
+                maybe_polled_reliable(sender, receiver, Some(config))
+            })
+            .unwrap();
+
+        let socket =
+            UdpSocketWrap::new_with_addr(Some(Duration::from_millis(1000)), peer_addr).unwrap();
+        let clone = socket.try_clone().unwrap();
+        let (mut peer_sender, mut peer_receiver) =
+            reliable_pair(Box::new(socket), Box::new(clone), config);
+
+        receiver.set_timeout(Some(Duration::from_millis(100)));
+        assert_eq!(receiver.recv(UDP_PACKET_SIZE), Err(SocketError::Timeout));
+
+        peer_sender.send_to(b"hola", &attached_addr).unwrap();
+        receiver.set_timeout(Some(Duration::from_millis(1000)));
+        assert_eq!(
+            receiver.recv(UDP_PACKET_SIZE),
+            Ok((b"hola".to_vec(), peer_addr))
+        );
+
+        sender.send_to(b"chau", &peer_addr).unwrap();
+        assert_eq!(
+            peer_receiver.recv(UDP_PACKET_SIZE),
+            Ok((b"chau".to_vec(), attached_addr))
+        );
+        // El event loop confirma lo que recibe el socket adjunto
+        while peer_sender.pending() > 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use crate::alglobo::transaction_receiver::TransactionReceiver;
+use crate::services::{
+    airline_client::Airline, bank_client::Bank, common_client::CommonClient, hotel_client::Hotel,
+};
+
+/// Participante sincronico que el `EventLoop` despierta cada vez que
+/// llega un datagrama a su socket o a su `Inbox`, y en cada tick.
+/// `step` debe volver sin esperar mensajes nuevos, ya que el event
+/// loop no le entrega datagramas hasta que termina; si su receptor
+/// esta vacio, `recv` devuelve `Timeout` de inmediato.
+pub trait Participant: Send + 'static {
+    fn step(&mut self);
+}
+
+impl Participant for TransactionReceiver {
+    fn step(&mut self) {
+        let _drop = self.recv();
+    }
+}
+
+impl Participant for Airline {
+    fn step(&mut self) {
+        let _drop = self.process_one_transaction();
+    }
+}
+
+impl Participant for Hotel {
+    fn step(&mut self) {
+        let _drop = self.process_one_transaction();
+    }
+}
+
+impl Participant for Bank {
+    fn step(&mut self) {
+        let _drop = self.process_one_transaction();
+    }
+}
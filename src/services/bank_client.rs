@@ -1,31 +1,40 @@
-use crate::services::constants::PERCENTAGE_ERROR;
 use crate::sockets::udp_socket_receiver::UdpSocketReceiver;
 use crate::sockets::udp_socket_sender::UdpSocketSender;
+use crate::telemetry::trace_context::TraceContext;
 use crate::transaction_messages::transaction_code::TransactionCode;
 use crate::transaction_messages::transaction_info::TransactionInfo;
 use crate::transaction_messages::transaction_request::TransactionRequest;
 use crate::transaction_messages::transaction_response::TransactionResponse;
-use rand::Rng;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::mem::size_of;
+use std::net::SocketAddr;
 
-use super::common_client::CommonClient;
+use super::common_client::{recv_request, serve_request, should_abort, CommonClient};
+use super::service_name::ServiceName;
 
 #[allow(dead_code)]
 pub struct Bank {
     socket_receiver: Box<dyn UdpSocketReceiver + Send>,
     socket_sender: Box<dyn UdpSocketSender + Send>,
     fee_sum: f64,
-    addr: String,
+    addr: SocketAddr,
     old_responses: HashMap<u64, bool>,
+    // Ids commiteados (true) o abortados (false): un COMMIT o ABORT
+    // repetido se responde sin volver a aplicar el monto
+    finished: HashMap<u64, bool>,
+    percentage_error: u32,
+    // Contexto del span del pedido en curso, que viaja en la respuesta
+    trace_opt: Option<TraceContext>,
 }
 impl Bank {
     #[must_use]
     pub fn new(
         socket_receiver: Box<dyn UdpSocketReceiver + Send>,
         socket_sender: Box<dyn UdpSocketSender + Send>,
-        addr: String,
+        addr: SocketAddr,
+        percentage_error: u32,
     ) -> Bank {
         Bank {
             socket_sender,
@@ -33,62 +42,89 @@ impl Bank {
             fee_sum: 0.0,
             addr,
             old_responses: HashMap::new(),
+            finished: HashMap::new(),
+            percentage_error,
+            trace_opt: None,
         }
     }
 }
 
 impl CommonClient for Bank {
-    fn answer_message(&mut self, vector: Vec<u8>, addr_to_answer: String) {
+    fn answer_message(&mut self, vector: Vec<u8>, addr_to_answer: SocketAddr) {
         let code = vector[0];
         let id_bytes: [u8; size_of::<u64>()] = vector[1..=size_of::<u64>()]
             .try_into()
             .expect("[Client] Los ids deberian ocupar 8 bytes");
         let transaction_id = u64::from_be_bytes(id_bytes);
         if code == TransactionRequest::map_transaction_code(TransactionCode::Prepare) {
-            if let Some(_response) = self.old_responses.get(&transaction_id) {
-                if let Some(_value) = self.old_responses.get(&transaction_id) {
-                    let mut response =
-                        TransactionResponse::build(TransactionCode::Accept, transaction_id);
-                    TransactionInfo::add_padding(&mut response);
-                    let _drop = self.socket_sender.send_to(&response, &addr_to_answer);
-                    return;
-                }
-                let mut response =
-                    TransactionResponse::build(TransactionCode::Abort, transaction_id);
+            // Un PREPARAR repetido se responde con el mismo voto
+            if let Some(accepted) = self.old_responses.get(&transaction_id) {
+                let code = if *accepted {
+                    TransactionCode::Accept
+                } else {
+                    TransactionCode::Abort
+                };
+                let mut response = TransactionResponse::build_with_trace(
+                    code,
+                    transaction_id,
+                    self.trace_opt.as_ref(),
+                );
                 TransactionInfo::add_padding(&mut response);
                 let _drop = self.socket_sender.send_to(&response, &addr_to_answer);
+                return;
             }
-            let mut rng = rand::thread_rng();
-            let n: u32 = rng.gen_range(0..10);
-            if n < PERCENTAGE_ERROR {
-                let mut response =
-                    TransactionResponse::build(TransactionCode::Abort, transaction_id);
+            if should_abort(self.percentage_error, &mut rand::thread_rng()) {
+                let mut response = TransactionResponse::build_with_trace(
+                    TransactionCode::Abort,
+                    transaction_id,
+                    self.trace_opt.as_ref(),
+                );
                 TransactionInfo::add_padding(&mut response);
                 let _drop = self.socket_sender.send_to(&response, &addr_to_answer);
                 self.old_responses.insert(transaction_id, false);
                 return;
             }
-            let mut response = TransactionResponse::build(TransactionCode::Accept, transaction_id);
+            let mut response = TransactionResponse::build_with_trace(
+                TransactionCode::Accept,
+                transaction_id,
+                self.trace_opt.as_ref(),
+            );
             TransactionInfo::add_padding(&mut response);
             let _drop = self.socket_sender.send_to(&response, &addr_to_answer);
             self.old_responses.insert(transaction_id, true);
         } else if code == TransactionRequest::map_transaction_code(TransactionCode::Abort) {
-            let mut response = TransactionResponse::build(TransactionCode::Abort, transaction_id);
+            let mut response = TransactionResponse::build_with_trace(
+                TransactionCode::Abort,
+                transaction_id,
+                self.trace_opt.as_ref(),
+            );
             TransactionInfo::add_padding(&mut response);
-            let fee: [u8; size_of::<f64>()] = vector[size_of::<u64>() + 1..]
+            let fee: [u8; size_of::<f64>()] = vector
+                [size_of::<u64>() + 1..=size_of::<u64>() + size_of::<f64>()]
                 .try_into()
                 .expect("[Client] Los fee deberian ocupar size_of::<f64> bytes");
             let fee_value = f64::from_be_bytes(fee);
-            self.fee_sum -= fee_value;
+            if let Entry::Vacant(entry) = self.finished.entry(transaction_id) {
+                entry.insert(false);
+                self.fee_sum -= fee_value;
+            }
             let _drop = self.socket_sender.send_to(&response, &addr_to_answer);
         } else {
-            let mut response = TransactionResponse::build(TransactionCode::Commit, transaction_id);
+            let mut response = TransactionResponse::build_with_trace(
+                TransactionCode::Commit,
+                transaction_id,
+                self.trace_opt.as_ref(),
+            );
             TransactionInfo::add_padding(&mut response);
-            let fee: [u8; size_of::<f64>()] = vector[size_of::<u64>() + 1..]
+            let fee: [u8; size_of::<f64>()] = vector
+                [size_of::<u64>() + 1..=size_of::<u64>() + size_of::<f64>()]
                 .try_into()
                 .expect("[Client] Los fee deberian ocupar size_of::<f64> bytes");
             let fee_value = f64::from_be_bytes(fee);
-            self.fee_sum += fee_value;
+            if let Entry::Vacant(entry) = self.finished.entry(transaction_id) {
+                entry.insert(true);
+                self.fee_sum += fee_value;
+            }
             let _drop = self.socket_sender.send_to(&response, &addr_to_answer);
         }
     }
@@ -101,13 +137,14 @@ impl CommonClient for Bank {
 
     /// # Errors
     ///
-    /// It wont return error if the `socket_receiver.recv` doesnt return error
+    /// Devuelve el motivo si no se recibio un pedido valido
     fn process_one_transaction(&mut self) -> Result<i64, String> {
-        let res = self.socket_receiver.recv(TransactionRequest::size());
-        let res_vec = res.unwrap();
-        let res_vector = res_vec.0;
-        let addr_to_answer = res_vec.1;
-        self.answer_message(res_vector, addr_to_answer);
+        let (res_vector, addr_to_answer) =
+            recv_request(ServiceName::Bank, &mut self.socket_receiver)?;
+        serve_request(ServiceName::Bank, res_vector, |request, trace_opt| {
+            self.trace_opt = trace_opt;
+            self.answer_message(request, addr_to_answer);
+        });
         Ok(0)
     }
 
@@ -133,7 +170,7 @@ mod tests {
     #[test]
     #[timeout(3000)]
     fn it_should_return_accept_when_receives_prepare() {
-        let bank_addr = "127.0.0.1:49157";
+        let bank_addr: SocketAddr = "127.0.0.1:49157".parse().unwrap();
         let transaction_id = 0;
         let bank_fee = 100.0;
         let first_msg =
@@ -145,7 +182,7 @@ mod tests {
         let mut mock_socket_sender = MockUdpSocketSender::new();
         mock_socket_sender
             .expect_send_to()
-            .withf(move |buff, addr| buff.to_vec() == response && addr == bank_addr)
+            .withf(move |buff, addr| buff.to_vec() == response && addr == &bank_addr)
             .times(1)
             .returning(|_, _| Ok(()));
 
@@ -154,12 +191,13 @@ mod tests {
             .expect_recv()
             .withf(move |n| n == &first_msg_len)
             .times(1)
-            .returning(move |_| Ok((first_msg.clone(), bank_addr.to_string())));
+            .returning(move |_| Ok((first_msg.clone(), bank_addr)));
 
         let mut bank = Bank::new(
             Box::new(mock_socket_receiver),
             Box::new(mock_socket_sender),
-            bank_addr.to_string(),
+            bank_addr,
+            0,
         );
 
         let _drop = bank.process_one_transaction();
@@ -167,19 +205,19 @@ mod tests {
 
     #[test]
     #[timeout(3000)]
-    fn it_should_return_accept_when_receives_abort() {
-        let bank_addr = "127.0.0.1:49157";
+    fn it_should_return_abort_when_receives_abort() {
+        let bank_addr: SocketAddr = "127.0.0.1:49157".parse().unwrap();
         let transaction_id = 0;
         let bank_fee = 100.0;
         let first_msg = TransactionRequest::build(TransactionCode::Abort, transaction_id, bank_fee);
-        let mut response = TransactionResponse::build(TransactionCode::Accept, transaction_id);
+        let mut response = TransactionResponse::build(TransactionCode::Abort, transaction_id);
         TransactionInfo::add_padding(&mut response);
         let first_msg_len = first_msg.len();
 
         let mut mock_socket_sender = MockUdpSocketSender::new();
         mock_socket_sender
             .expect_send_to()
-            .withf(move |buff, addr| buff.to_vec() == response && addr == bank_addr)
+            .withf(move |buff, addr| buff.to_vec() == response && addr == &bank_addr)
             .times(1)
             .returning(|_, _| Ok(()));
 
@@ -188,12 +226,13 @@ mod tests {
             .expect_recv()
             .withf(move |n| n == &first_msg_len)
             .times(1)
-            .returning(move |_| Ok((first_msg.clone(), bank_addr.to_string())));
+            .returning(move |_| Ok((first_msg.clone(), bank_addr)));
 
         let mut bank = Bank::new(
             Box::new(mock_socket_receiver),
             Box::new(mock_socket_sender),
-            bank_addr.to_string(),
+            bank_addr,
+            0,
         );
 
         let _drop = bank.process_one_transaction();
@@ -201,20 +240,20 @@ mod tests {
 
     #[test]
     #[timeout(3000)]
-    fn it_should_return_accept_when_receives_commit() {
-        let bank_addr = "127.0.0.1:49157";
+    fn it_should_return_commit_when_receives_commit() {
+        let bank_addr: SocketAddr = "127.0.0.1:49157".parse().unwrap();
         let transaction_id = 0;
         let bank_fee = 100.0;
         let first_msg =
             TransactionRequest::build(TransactionCode::Commit, transaction_id, bank_fee);
-        let mut response = TransactionResponse::build(TransactionCode::Accept, transaction_id);
+        let mut response = TransactionResponse::build(TransactionCode::Commit, transaction_id);
         TransactionInfo::add_padding(&mut response);
         let first_msg_len = first_msg.len();
 
         let mut mock_socket_sender = MockUdpSocketSender::new();
         mock_socket_sender
             .expect_send_to()
-            .withf(move |buff, addr| buff.to_vec() == response && addr == bank_addr)
+            .withf(move |buff, addr| buff.to_vec() == response && addr == &bank_addr)
             .times(1)
             .returning(|_, _| Ok(()));
 
@@ -223,12 +262,13 @@ mod tests {
             .expect_recv()
             .withf(move |n| n == &first_msg_len)
             .times(1)
-            .returning(move |_| Ok((first_msg.clone(), bank_addr.to_string())));
+            .returning(move |_| Ok((first_msg.clone(), bank_addr)));
 
         let mut bank = Bank::new(
             Box::new(mock_socket_receiver),
             Box::new(mock_socket_sender),
-            bank_addr.to_string(),
+            bank_addr,
+            0,
         );
 
         let _drop = bank.process_one_transaction();
@@ -237,19 +277,19 @@ mod tests {
     #[test]
     #[timeout(3000)]
     fn it_should_change_fee_when_receives_commit() {
-        let bank_addr = "127.0.0.1:49157";
+        let bank_addr: SocketAddr = "127.0.0.1:49157".parse().unwrap();
         let transaction_id = 0;
         let bank_fee = 100.0;
         let first_msg =
             TransactionRequest::build(TransactionCode::Commit, transaction_id, bank_fee);
-        let mut response = TransactionResponse::build(TransactionCode::Accept, transaction_id);
+        let mut response = TransactionResponse::build(TransactionCode::Commit, transaction_id);
         TransactionInfo::add_padding(&mut response);
         let first_msg_len = first_msg.len();
 
         let mut mock_socket_sender = MockUdpSocketSender::new();
         mock_socket_sender
             .expect_send_to()
-            .withf(move |buff, addr| buff.to_vec() == response && addr == bank_addr)
+            .withf(move |buff, addr| buff.to_vec() == response && addr == &bank_addr)
             .times(1)
             .returning(|_, _| Ok(()));
 
@@ -258,15 +298,91 @@ mod tests {
             .expect_recv()
             .withf(move |n| n == &first_msg_len)
             .times(1)
-            .returning(move |_| Ok((first_msg.clone(), bank_addr.to_string())));
+            .returning(move |_| Ok((first_msg.clone(), bank_addr)));
 
         let mut bank = Bank::new(
             Box::new(mock_socket_receiver),
             Box::new(mock_socket_sender),
-            bank_addr.to_string(),
+            bank_addr,
+            0,
         );
 
         let _drop = bank.process_one_transaction();
         assert_eq!(bank.fee_sum, 100.0)
     }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_repeat_the_vote_when_receives_a_repeated_prepare() {
+        let bank_addr: SocketAddr = "127.0.0.1:49157".parse().unwrap();
+        let request = TransactionRequest::build(TransactionCode::Prepare, 0, 100.0);
+        let mut response = TransactionResponse::build(TransactionCode::Abort, 0);
+        TransactionInfo::add_padding(&mut response);
+
+        let mut mock_socket_sender = MockUdpSocketSender::new();
+        mock_socket_sender
+            .expect_send_to()
+            .withf(move |buff, _| buff.to_vec() == response)
+            .times(2)
+            .returning(|_, _| Ok(()));
+
+        let mut mock_socket_receiver = MockUdpSocketReceiver::new();
+        mock_socket_receiver
+            .expect_recv()
+            .times(2)
+            .returning(move |_| Ok((request.clone(), bank_addr)));
+
+        // Vota abortar el primer PREPARAR y despues repite ese voto
+        let mut bank = Bank::new(
+            Box::new(mock_socket_receiver),
+            Box::new(mock_socket_sender),
+            bank_addr,
+            100,
+        );
+        let _drop = bank.process_one_transaction();
+        bank.percentage_error = 0;
+        let _drop = bank.process_one_transaction();
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_apply_the_fee_once_when_receives_a_repeated_commit_or_abort() {
+        let bank_addr: SocketAddr = "127.0.0.1:49157".parse().unwrap();
+        let requests = vec![
+            TransactionRequest::build(TransactionCode::Commit, 0, 100.0),
+            TransactionRequest::build(TransactionCode::Commit, 0, 100.0),
+            TransactionRequest::build(TransactionCode::Abort, 0, 100.0),
+            TransactionRequest::build(TransactionCode::Abort, 1, 30.0),
+            TransactionRequest::build(TransactionCode::Abort, 1, 30.0),
+        ];
+        let n_requests = requests.len();
+
+        let mut mock_socket_sender = MockUdpSocketSender::new();
+        mock_socket_sender
+            .expect_send_to()
+            .times(n_requests)
+            .returning(|_, _| Ok(()));
+
+        let mut mock_socket_receiver = MockUdpSocketReceiver::new();
+        let mut seq = mockall::Sequence::new();
+        for request in requests {
+            mock_socket_receiver
+                .expect_recv()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move |_| Ok((request.clone(), bank_addr)));
+        }
+
+        let mut bank = Bank::new(
+            Box::new(mock_socket_receiver),
+            Box::new(mock_socket_sender),
+            bank_addr,
+            0,
+        );
+
+        for _ in 0..n_requests {
+            let _drop = bank.process_one_transaction();
+        }
+        assert_eq!(bank.fee_sum, 70.0)
+    }
 }
@@ -1,9 +1,126 @@
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use rand::Rng;
+use tracing::{info, warn};
+
+use super::service_name::ServiceName;
+use crate::metrics::registry::metrics;
+use crate::sockets::socket_error::SocketError;
+use crate::sockets::udp_socket_receiver::UdpSocketReceiver;
+use crate::telemetry::trace_context::TraceContext;
+use crate::telemetry::trace_span::{SpanKind, TraceSpan};
+use crate::transaction_messages::transaction_code::TransactionCode;
+use crate::transaction_messages::transaction_request::TransactionRequest;
+
 pub trait CommonClient {
-    fn answer_message(&mut self, _vector: Vec<u8>, _string: String) {}
+    fn answer_message(&mut self, _vector: Vec<u8>, _addr: SocketAddr) {}
     fn start_client(&mut self) {}
     /// # Errors
     ///
-    /// It wont return error if the `socket_receiver.recv` doesnt return error
+    /// Devuelve el motivo si no se recibio un pedido valido
     fn process_one_transaction(&mut self) -> Result<i64, String>;
     fn get_fee_sum(&mut self) -> f64;
 }
+
+/// Recibe el proximo pedido del servicio. Los datagramas que no se
+/// pudieron recibir (demasiado largos, fragmentos mal formados) y los
+/// que no son un pedido se registran y se descartan.
+///
+/// # Errors
+///
+/// Devuelve el motivo si no se recibio un pedido valido
+pub fn recv_request(
+    service: ServiceName,
+    receiver: &mut Box<dyn UdpSocketReceiver + Send>,
+) -> Result<(Vec<u8>, SocketAddr), String> {
+    let (request, addr) = match receiver.recv(TransactionRequest::size()) {
+        Ok(value) => value,
+        Err(SocketError::Timeout) => return Err(SocketError::Timeout.to_string()),
+        Err(err) => {
+            warn!(service = %service.string_name(), error = %err, "Datagrama descartado");
+            return Err(err.to_string());
+        }
+    };
+    if !TransactionRequest::is_valid(&request) {
+        warn!(service = %service.string_name(), from = %addr, "Pedido invalido descartado");
+        return Err("Pedido invalido".to_string());
+    }
+    Ok((request, addr))
+}
+
+/// Decide si el servicio vota abortar un PREPARAR: `percentage_error`
+/// es el porcentaje, de 0 a 100, de preparaciones que se abortan
+pub fn should_abort<R: Rng>(percentage_error: u32, rng: &mut R) -> bool {
+    rng.gen_range(0..100) < percentage_error
+}
+
+/// Atiende el pedido `request` con `answer`, que recibe el contexto
+/// del span del servicio para propagarlo en la respuesta. Si el
+/// pedido trae contexto de traza se exporta un span hijo del span
+/// del coordinador. Ademas registra el log y las metricas del pedido.
+pub fn serve_request<F>(service: ServiceName, request: Vec<u8>, answer: F)
+where
+    F: FnOnce(Vec<u8>, Option<TraceContext>),
+{
+    let service = service.string_name();
+    let code = TransactionRequest::parse_code(&request);
+    let transaction_id = TransactionRequest::parse_id(&request);
+    let span_name = match code {
+        TransactionCode::Prepare => "vote",
+        TransactionCode::Abort => "abort",
+        _ => "commit",
+    };
+    let span_opt = TransactionRequest::parse_trace(&request).map(|parent| {
+        let mut span = TraceSpan::child_of(&service, span_name, SpanKind::Server, &parent);
+        span.set_attribute("transaction.id", transaction_id);
+        span
+    });
+    let trace_opt = span_opt.as_ref().map(|span| span.context);
+    info!(
+        transaction_id,
+        service = %service,
+        code = %code,
+        trace_id = %trace_opt.map(|trace| trace.trace_id_hex()).unwrap_or_default(),
+        "Pedido de transaccion recibido"
+    );
+    let started = Instant::now();
+    answer(request, trace_opt);
+    let code = code.to_string();
+    let labels = [("service", service.as_str()), ("code", code.as_str())];
+    metrics().service_requests.inc(&labels);
+    metrics()
+        .service_request_duration
+        .observe(&labels[..1], started.elapsed());
+    if let Some(span) = span_opt {
+        span.end();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::constants::PERCENTAGE_ERROR;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn aborted(percentage_error: u32) -> usize {
+        let mut rng = StdRng::seed_from_u64(28);
+        (0..10_000)
+            .filter(|_| should_abort(percentage_error, &mut rng))
+            .count()
+    }
+
+    #[test]
+    fn should_abort_should_take_the_error_as_a_percentage() {
+        assert_eq!(aborted(0), 0);
+        assert!((900..1100).contains(&aborted(10)));
+        assert!((4800..5200).contains(&aborted(50)));
+        assert_eq!(aborted(100), 10_000);
+    }
+
+    #[test]
+    fn the_default_error_should_abort_a_fifth_of_the_prepares() {
+        assert_eq!(PERCENTAGE_ERROR, 20);
+        assert!((1900..2100).contains(&aborted(PERCENTAGE_ERROR)));
+    }
+}
@@ -1 +1,2 @@
-pub static PERCENTAGE_ERROR: u32 = 2;
+/// Porcentaje de PREPARAR que un servicio vota abortar por defecto
+pub static PERCENTAGE_ERROR: u32 = 20;
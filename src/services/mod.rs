@@ -1,6 +1,6 @@
 pub mod airline_client;
 pub mod bank_client;
 pub mod common_client;
-mod constants;
+pub mod constants;
 pub mod hotel_client;
 pub mod service_name;
@@ -1,4 +1,4 @@
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum ServiceName {
     Airline,
     Hotel,
@@ -0,0 +1,20 @@
+use super::socket_error::SocketError;
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+#[cfg(test)]
+use mockall::automock;
+
+/// Version asincronica de `UdpSocketReceiver`
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait AsyncUdpSocketReceiver {
+    /// # Errors
+    ///
+    /// `SocketError::ZeroBytes` => El datagrama recibido no tenia bytes
+    /// `SocketError::Timeout` => Paso demasiado tiempo sin recibir ningún byte.
+    async fn recv(&mut self, n: usize) -> Result<(Vec<u8>, SocketAddr), SocketError>;
+
+    fn set_timeout(&mut self, opt_timeout: Option<Duration>);
+}
@@ -0,0 +1,16 @@
+use super::socket_error::SocketError;
+use async_trait::async_trait;
+use std::net::SocketAddr;
+
+#[cfg(test)]
+use mockall::automock;
+
+/// Version asincronica de `UdpSocketSender`
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait AsyncUdpSocketSender {
+    /// # Errors
+    ///
+    /// `SocketError::ZeroBytes` => El socket interno no envió ningun byte
+    async fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<(), SocketError>;
+}
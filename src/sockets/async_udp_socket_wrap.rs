@@ -0,0 +1,164 @@
+use crate::sockets::constants::UDP_PACKET_SIZE;
+use async_trait::async_trait;
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{net::UdpSocket, time::timeout};
+
+use super::{
+    async_udp_socket_receiver::AsyncUdpSocketReceiver,
+    async_udp_socket_sender::AsyncUdpSocketSender,
+    fragment::{fragment, Reassembler},
+    socket_error::SocketError,
+};
+
+/// Socket UDP no bloqueante. Los clones comparten el mismo
+/// socket, por lo que una tarea puede enviar mientras otra recibe.
+/// Fragmenta y rearma los mensajes igual que `UdpSocketWrap`.
+#[derive(Clone)]
+pub struct AsyncUdpSocketWrap {
+    socket: Arc<UdpSocket>,
+    opt_timeout: Option<Duration>,
+    next_msg_id: Arc<AtomicU32>,
+    reassembler: Arc<Mutex<Reassembler>>,
+}
+
+impl AsyncUdpSocketWrap {
+    /// # Errors
+    ///
+    /// Devuelve error si no fue posible inicializar un socket con esa addr
+    pub async fn new_with_addr(
+        opt_timeout: Option<Duration>,
+        addr: SocketAddr,
+    ) -> Result<AsyncUdpSocketWrap, String> {
+        match UdpSocket::bind(addr).await {
+            Ok(socket) => Ok(AsyncUdpSocketWrap {
+                socket: Arc::new(socket),
+                opt_timeout,
+                next_msg_id: Arc::new(AtomicU32::new(rand::random())),
+                reassembler: Arc::new(Mutex::new(Reassembler::new())),
+            }),
+            Err(_) => Err(format!("[AsyncUdpSocketWrap] Bind a {} ha fallado", addr)),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Devuelve error si no fue posible obtener la direccion local
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        self.socket
+            .local_addr()
+            .map_err(|_| "[AsyncUdpSocketWrap] No se pudo obtener la direccion local".to_string())
+    }
+}
+
+#[async_trait]
+impl AsyncUdpSocketSender for AsyncUdpSocketWrap {
+    async fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<(), SocketError> {
+        let msg_id = self.next_msg_id.fetch_add(1, Ordering::SeqCst);
+        for datagram in fragment(buf, msg_id)? {
+            match self.socket.send_to(&datagram, addr).await {
+                Ok(bytes_sent) if bytes_sent == datagram.len() => {}
+                _ => return Err(SocketError::ZeroBytes),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AsyncUdpSocketReceiver for AsyncUdpSocketWrap {
+    async fn recv(&mut self, n_bytes: usize) -> Result<(Vec<u8>, SocketAddr), SocketError> {
+        // Un byte extra permite detectar datagramas mas largos que el maximo
+        let mut buf = [0; UDP_PACKET_SIZE + 1];
+        loop {
+            let res = match self.opt_timeout {
+                Some(duration) => match timeout(duration, self.socket.recv_from(&mut buf)).await {
+                    Ok(res) => res,
+                    Err(_) => return Err(SocketError::Timeout),
+                },
+                None => self.socket.recv_from(&mut buf).await,
+            };
+            let (bytes_recv, addr) = match res {
+                Ok(value) => value,
+                Err(_) => return Err(SocketError::Timeout),
+            };
+            if bytes_recv == 0 {
+                return Err(SocketError::ZeroBytes);
+            }
+            if bytes_recv > UDP_PACKET_SIZE {
+                return Err(SocketError::Truncated);
+            }
+            let complete = self
+                .reassembler
+                .lock()
+                .expect("[AsyncUdpSocketWrap] Lock del rearmador envenenado")
+                .push(&buf[..bytes_recv], addr)?;
+            if let Some(message) = complete {
+                if message.len() > n_bytes {
+                    return Err(SocketError::Truncated);
+                }
+                return Ok((message, addr));
+            }
+        }
+    }
+
+    fn set_timeout(&mut self, opt_timeout: Option<Duration>) {
+        self.opt_timeout = opt_timeout;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_should_send_and_recv_a_message() {
+        let addr_recv: SocketAddr = "127.0.0.1:59163".parse().unwrap();
+        let addr_send: SocketAddr = "127.0.0.1:59164".parse().unwrap();
+        let mut receiver = AsyncUdpSocketWrap::new_with_addr(None, addr_recv)
+            .await
+            .unwrap();
+        let mut sender = AsyncUdpSocketWrap::new_with_addr(None, addr_send)
+            .await
+            .unwrap();
+
+        sender.send_to(b"hola", &addr_recv).await.unwrap();
+
+        assert_eq!(receiver.recv(4).await, Ok((b"hola".to_vec(), addr_send)));
+    }
+
+    #[tokio::test]
+    async fn it_should_return_timeout_if_nothing_is_received() {
+        let addr: SocketAddr = "127.0.0.1:59165".parse().unwrap();
+        let mut receiver = AsyncUdpSocketWrap::new_with_addr(Some(Duration::from_millis(10)), addr)
+            .await
+            .unwrap();
+
+        assert_eq!(receiver.recv(4).await, Err(SocketError::Timeout));
+    }
+
+    #[tokio::test]
+    async fn it_should_send_and_recv_a_fragmented_message() {
+        let addr_recv: SocketAddr = "127.0.0.1:59168".parse().unwrap();
+        let addr_send: SocketAddr = "127.0.0.1:59169".parse().unwrap();
+        let mut receiver = AsyncUdpSocketWrap::new_with_addr(None, addr_recv)
+            .await
+            .unwrap();
+        let mut sender = AsyncUdpSocketWrap::new_with_addr(None, addr_send)
+            .await
+            .unwrap();
+        let message: Vec<u8> = (0..2000).map(|i| (i % 251) as u8).collect();
+
+        sender.send_to(&message, &addr_recv).await.unwrap();
+
+        assert_eq!(receiver.recv(1).await, Err(SocketError::Truncated));
+        sender.send_to(&message, &addr_recv).await.unwrap();
+        assert_eq!(receiver.recv(message.len()).await, Ok((message, addr_send)));
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    net::SocketAddr,
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{
+    socket_error::SocketError, udp_socket_receiver::UdpSocketReceiver,
+    udp_socket_sender::UdpSocketSender,
+};
+
+pub type Datagram = (Vec<u8>, SocketAddr);
+
+/// Receptor que lee los datagramas que el event loop recibio por
+/// un socket asincronico. Permite usar servicios sincronicos dentro
+/// del event loop.
+pub struct ChannelSocketReceiver {
+    receiver: Receiver<Datagram>,
+    opt_timeout: Option<Duration>,
+    blocking: bool,
+}
+
+impl ChannelSocketReceiver {
+    #[must_use]
+    pub fn new(receiver: Receiver<Datagram>) -> Self {
+        ChannelSocketReceiver {
+            receiver,
+            opt_timeout: None,
+            blocking: true,
+        }
+    }
+
+    /// Receptor que nunca espera: sin datagramas encolados devuelve
+    /// `SocketError::Timeout`, cualquiera sea el timeout configurado.
+    /// Es el que usa el event loop, ya que los datagramas los encola
+    /// la misma tarea que ejecuta al participante.
+    #[must_use]
+    pub fn non_blocking(receiver: Receiver<Datagram>) -> Self {
+        ChannelSocketReceiver {
+            receiver,
+            opt_timeout: Some(Duration::ZERO),
+            blocking: false,
+        }
+    }
+}
+
+impl UdpSocketReceiver for ChannelSocketReceiver {
+    fn recv(&mut self, n: usize) -> Result<Datagram, SocketError> {
+        let (message, addr) = match self.opt_timeout {
+            Some(timeout) => match self.receiver.recv_timeout(timeout) {
+                Ok(value) => value,
+                Err(RecvTimeoutError::Timeout) => return Err(SocketError::Timeout),
+                Err(RecvTimeoutError::Disconnected) => return Err(SocketError::ZeroBytes),
+            },
+            None => match self.receiver.recv() {
+                Ok(value) => value,
+                Err(_) => return Err(SocketError::ZeroBytes),
+            },
+        };
+        if message.len() > n {
+            return Err(SocketError::Truncated);
+        }
+        Ok((message, addr))
+    }
+
+    fn set_timeout(&mut self, opt_timeout: Option<Duration>) {
+        if self.blocking {
+            self.opt_timeout = opt_timeout;
+        }
+    }
+}
+
+/// Emisor que encola los datagramas para que el event loop
+/// los envie por su socket asincronico sin bloquear al participante
+pub struct ChannelSocketSender {
+    sender: UnboundedSender<Datagram>,
+}
+
+impl ChannelSocketSender {
+    #[must_use]
+    pub fn new(sender: UnboundedSender<Datagram>) -> Self {
+        ChannelSocketSender { sender }
+    }
+}
+
+impl UdpSocketSender for ChannelSocketSender {
+    fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<(), SocketError> {
+        self.sender
+            .send((buf.to_vec(), *addr))
+            .map_err(|_| SocketError::ZeroBytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[test]
+    fn it_should_recv_the_queued_datagram_or_report_truncation() {
+        let addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let (sender, receiver) = channel();
+        let mut socket = ChannelSocketReceiver::new(receiver);
+        socket.set_timeout(Some(Duration::from_millis(10)));
+
+        sender.send((vec![1, 2, 3], addr)).unwrap();
+        sender.send((vec![1, 2, 3, 4], addr)).unwrap();
+
+        assert_eq!(socket.recv(3), Ok((vec![1, 2, 3], addr)));
+        assert_eq!(socket.recv(3), Err(SocketError::Truncated));
+        assert_eq!(socket.recv(3), Err(SocketError::Timeout));
+    }
+
+    #[test]
+    fn it_should_not_wait_when_non_blocking() {
+        let addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let (sender, receiver) = channel();
+        let mut socket = ChannelSocketReceiver::non_blocking(receiver);
+        socket.set_timeout(None);
+
+        sender.send((vec![1, 2, 3], addr)).unwrap();
+
+        assert_eq!(socket.recv(3), Ok((vec![1, 2, 3], addr)));
+        assert_eq!(socket.recv(3), Err(SocketError::Timeout));
+    }
+
+    #[test]
+    fn it_should_queue_the_sent_datagram() {
+        let addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let (sender, mut receiver) = unbounded_channel();
+        let mut socket = ChannelSocketSender::new(sender);
+
+        socket.send_to(&[1, 2, 3], &addr).unwrap();
+
+        assert_eq!(receiver.try_recv().unwrap(), (vec![1, 2, 3], addr));
+    }
+}
@@ -0,0 +1,236 @@
+use std::{net::SocketAddr, time::Duration};
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use tracing::warn;
+
+use super::{
+    signed_socket::{encode_identity, split_identity, Keys, Peers, MAX_IDENTITY_SIZE},
+    socket_error::SocketError,
+    udp_socket_receiver::UdpSocketReceiver,
+    udp_socket_sender::UdpSocketSender,
+};
+
+pub const NONCE_SIZE: usize = 24;
+pub const AEAD_TAG_SIZE: usize = 16;
+// Separa las claves de cifrado de las de firma aunque se repitan
+const KEY_CONTEXT: &[u8] = b"alglobo-aead";
+
+fn cipher(psks: &Keys, identity: SocketAddr, peer: SocketAddr) -> Option<XChaCha20Poly1305> {
+    let key = psks.link_key(identity, peer, KEY_CONTEXT)?;
+    Some(XChaCha20Poly1305::new(&key.into()))
+}
+
+/// Emisor que cifra cada mensaje con XChaCha20-Poly1305 y la clave
+/// derivada de las claves precompartidas del enlace. Cada mensaje
+/// lleva en claro, pero autenticada, la identidad del emisor, y un
+/// nonce aleatorio. Los mensajes a un par sin identidad conocida no
+/// se envian.
+pub struct EncryptedSender {
+    sender: Box<dyn UdpSocketSender + Send>,
+    identity: SocketAddr,
+    psks: Keys,
+    peers: Peers,
+}
+
+impl UdpSocketSender for EncryptedSender {
+    fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<(), SocketError> {
+        let cipher = self
+            .peers
+            .identity(&self.psks, *addr)
+            .and_then(|peer| cipher(&self.psks, self.identity, peer))
+            .ok_or(SocketError::UnknownPeer)?;
+        let mut message = encode_identity(self.identity);
+        let nonce: [u8; NONCE_SIZE] = rand::random();
+        let ciphertext = cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: buf,
+                    aad: &message,
+                },
+            )
+            .expect("[Encrypted Socket] Cifrar un mensaje no deberia fallar");
+        message.extend_from_slice(&nonce);
+        message.extend_from_slice(&ciphertext);
+        self.sender.send_to(&message, addr)
+    }
+}
+
+/// Receptor que descifra los mensajes de `EncryptedSender` con la
+/// clave del enlace con la identidad que declaran. Los que no se
+/// pueden descifrar o autenticar se descartan.
+pub struct EncryptedReceiver {
+    receiver: Box<dyn UdpSocketReceiver + Send>,
+    identity: SocketAddr,
+    psks: Keys,
+    peers: Peers,
+}
+
+impl EncryptedReceiver {
+    /// Descifra `message` y devuelve la identidad del emisor y el texto
+    fn open(&self, message: &[u8]) -> Option<(SocketAddr, Vec<u8>)> {
+        let (peer, rest) = split_identity(message)?;
+        if rest.len() < NONCE_SIZE + AEAD_TAG_SIZE {
+            return None;
+        }
+        let cipher = cipher(&self.psks, self.identity, peer)?;
+        let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+        let plaintext = cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &message[..message.len() - rest.len()],
+                },
+            )
+            .ok()?;
+        Some((peer, plaintext))
+    }
+}
+
+impl UdpSocketReceiver for EncryptedReceiver {
+    fn recv(&mut self, n: usize) -> Result<(Vec<u8>, SocketAddr), SocketError> {
+        loop {
+            let (message, addr) = self
+                .receiver
+                .recv(MAX_IDENTITY_SIZE + NONCE_SIZE + n + AEAD_TAG_SIZE)?;
+            if let Some((peer, plaintext)) = self.open(&message) {
+                self.peers.learn(addr, peer);
+                return Ok((plaintext, addr));
+            }
+            warn!(
+                peer = %addr,
+                error = %SocketError::DecryptFailed,
+                "Mensaje descartado"
+            );
+        }
+    }
+
+    fn set_timeout(&mut self, opt_timeout: Option<Duration>) {
+        self.receiver.set_timeout(opt_timeout);
+    }
+}
+
+/// Cifra los mensajes de emisor y receptor si hay claves
+/// precompartidas, o los devuelve sin cambios en caso contrario.
+/// `identity` tiene el mismo significado que en `maybe_signed`.
+#[must_use]
+pub fn maybe_encrypted(
+    sender: Box<dyn UdpSocketSender + Send>,
+    receiver: Box<dyn UdpSocketReceiver + Send>,
+    identity: SocketAddr,
+    psks: &Keys,
+) -> (
+    Box<dyn UdpSocketSender + Send>,
+    Box<dyn UdpSocketReceiver + Send>,
+) {
+    if psks.is_empty() {
+        return (sender, receiver);
+    }
+    let peers = Peers::default();
+    (
+        Box::new(EncryptedSender {
+            sender,
+            identity,
+            psks: psks.clone(),
+            peers: peers.clone(),
+        }),
+        Box::new(EncryptedReceiver {
+            receiver,
+            identity,
+            psks: psks.clone(),
+            peers,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sockets::udp_socket_wrap::UdpSocketWrap;
+    use ntest::timeout;
+    use std::{collections::HashMap, net::UdpSocket};
+
+    fn encrypted(addr: SocketAddr, psks: &Keys) -> (EncryptedSender, EncryptedReceiver) {
+        let socket = UdpSocketWrap::new_with_addr(Some(Duration::from_millis(300)), addr).unwrap();
+        let clone = socket.try_clone().unwrap();
+        let peers = Peers::default();
+        (
+            EncryptedSender {
+                sender: Box::new(socket),
+                identity: addr,
+                psks: psks.clone(),
+                peers: peers.clone(),
+            },
+            EncryptedReceiver {
+                receiver: Box::new(clone),
+                identity: addr,
+                psks: psks.clone(),
+                peers,
+            },
+        )
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_not_send_the_payload_in_cleartext_and_decrypt_it_on_recv() {
+        let addr_a: SocketAddr = "127.0.0.1:59197".parse().unwrap();
+        let addr_b: SocketAddr = "127.0.0.1:59198".parse().unwrap();
+        let addr_spy: SocketAddr = "127.0.0.1:59199".parse().unwrap();
+        let psks = Keys::new(HashMap::from([
+            (addr_a, vec![6; 32]),
+            (addr_b, vec![7; 32]),
+            (addr_spy, vec![7; 32]),
+        ]));
+        let (mut sender_a, _receiver_a) = encrypted(addr_a, &psks);
+        let (_sender_b, mut receiver_b) = encrypted(addr_b, &psks);
+        let spy = UdpSocket::bind(addr_spy).unwrap();
+
+        sender_a.send_to(b"pago 1500", &addr_b).unwrap();
+        sender_a.send_to(b"pago 1500", &addr_spy).unwrap();
+
+        assert_eq!(receiver_b.recv(9), Ok((b"pago 1500".to_vec(), addr_a)));
+        let mut buf = [0; 512];
+        let (n, _) = spy.recv_from(&mut buf).unwrap();
+        assert_eq!(n, 7 + NONCE_SIZE + 9 + AEAD_TAG_SIZE);
+        assert!(!buf[..n].windows(4).any(|window| window == b"1500"));
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_discard_messages_that_cannot_be_decrypted() {
+        let addr_raw: SocketAddr = "127.0.0.1:59200".parse().unwrap();
+        let addr_b: SocketAddr = "127.0.0.1:59201".parse().unwrap();
+        let psks = Keys::new(HashMap::from([
+            (addr_raw, vec![6; 32]),
+            (addr_b, vec![7; 32]),
+        ]));
+        let (_sender_b, mut receiver_b) = encrypted(addr_b, &psks);
+        let raw = UdpSocket::bind(addr_raw).unwrap();
+        let mut message = encode_identity(addr_raw);
+        message.extend_from_slice(&[0; NONCE_SIZE + 4 + AEAD_TAG_SIZE]);
+
+        raw.send_to(&[0; NONCE_SIZE + 4 + AEAD_TAG_SIZE], addr_b)
+            .unwrap();
+        raw.send_to(&message, addr_b).unwrap();
+
+        assert_eq!(receiver_b.recv(4), Err(SocketError::Timeout));
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_not_encrypt_with_a_single_key_of_the_link() {
+        let addr_a: SocketAddr = "127.0.0.1:59216".parse().unwrap();
+        let addr_b: SocketAddr = "127.0.0.1:59217".parse().unwrap();
+        let psks = Keys::new(HashMap::from([(addr_b, vec![7; 32])]));
+        let (mut sender_a, _receiver_a) = encrypted(addr_a, &psks);
+
+        assert_eq!(
+            sender_a.send_to(b"pago 1500", &addr_b),
+            Err(SocketError::UnknownPeer)
+        );
+    }
+}
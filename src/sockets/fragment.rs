@@ -0,0 +1,244 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryInto,
+    mem::size_of,
+    net::SocketAddr,
+};
+
+use super::{constants::UDP_PACKET_SIZE, socket_error::SocketError};
+
+// Prefijo de los fragmentos. Ningun mensaje del sistema empieza con 0xFF
+const MAGIC: [u8; 4] = [0xFF, b'F', b'R', b'G'];
+pub const FRAGMENT_HEADER_SIZE: usize = MAGIC.len() + size_of::<u32>() + 2 * size_of::<u16>();
+const FRAGMENT_PAYLOAD_SIZE: usize = UDP_PACKET_SIZE - FRAGMENT_HEADER_SIZE;
+/// Largo maximo de un mensaje que puede fragmentarse
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+// Cantidad maxima de fragmentos de un mensaje
+const MAX_FRAGMENTS: usize = MAX_MESSAGE_SIZE.div_ceil(FRAGMENT_PAYLOAD_SIZE);
+// Cantidad de mensajes incompletos recordados a la vez
+const MAX_PARTIAL: usize = 64;
+// Bytes de fragmentos guardados entre todos los mensajes incompletos
+const MAX_BUFFERED: usize = 4 * MAX_MESSAGE_SIZE;
+
+/// Divide `message` en datagramas de a lo sumo `UDP_PACKET_SIZE` bytes.
+/// Los mensajes que entran en un datagrama se envian tal cual; el
+/// resto viaja en fragmentos con el encabezado
+/// `[magic][msg_id u32][indice u16][cantidad u16]`.
+///
+/// # Errors
+///
+/// `SocketError::Truncated` => El mensaje supera `MAX_MESSAGE_SIZE`
+pub fn fragment(message: &[u8], msg_id: u32) -> Result<Vec<Vec<u8>>, SocketError> {
+    if message.len() <= UDP_PACKET_SIZE && !message.starts_with(&MAGIC) {
+        return Ok(vec![message.to_vec()]);
+    }
+    if message.len() > MAX_MESSAGE_SIZE {
+        return Err(SocketError::Truncated);
+    }
+    let chunks: Vec<&[u8]> = message.chunks(FRAGMENT_PAYLOAD_SIZE).collect();
+    let count = chunks.len() as u16;
+    Ok(chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut datagram = MAGIC.to_vec();
+            datagram.extend_from_slice(&msg_id.to_be_bytes());
+            datagram.extend_from_slice(&(index as u16).to_be_bytes());
+            datagram.extend_from_slice(&count.to_be_bytes());
+            datagram.extend_from_slice(chunk);
+            datagram
+        })
+        .collect())
+}
+
+struct Partial {
+    chunks: Vec<Option<Vec<u8>>>,
+    missing: usize,
+    // Bytes de los fragmentos ya recibidos
+    size: usize,
+}
+
+/// Rearma los mensajes fragmentados por `fragment`. Los mensajes
+/// incompletos mas viejos se descartan al superar `MAX_PARTIAL`
+/// mensajes o `MAX_BUFFERED` bytes guardados.
+#[derive(Default)]
+pub struct Reassembler {
+    partial: HashMap<(SocketAddr, u32), Partial>,
+    order: VecDeque<(SocketAddr, u32)>,
+    buffered: usize,
+}
+
+impl Reassembler {
+    #[must_use]
+    pub fn new() -> Self {
+        Reassembler::default()
+    }
+
+    /// Procesa un datagrama recibido de `addr`. Devuelve el mensaje
+    /// completo si el datagrama lo termina de armar.
+    ///
+    /// # Errors
+    ///
+    /// `SocketError::Malformed` => El encabezado del fragmento es invalido,
+    /// anuncia mas de `MAX_FRAGMENTS` fragmentos o el fragmento es mas
+    /// largo que los que arma `fragment`
+    pub fn push(
+        &mut self,
+        datagram: &[u8],
+        addr: SocketAddr,
+    ) -> Result<Option<Vec<u8>>, SocketError> {
+        if !datagram.starts_with(&MAGIC) {
+            return Ok(Some(datagram.to_vec()));
+        }
+        if datagram.len() < FRAGMENT_HEADER_SIZE {
+            return Err(SocketError::Malformed);
+        }
+        let mut offset = MAGIC.len();
+        let msg_id = u32::from_be_bytes(read_bytes(datagram, &mut offset));
+        let index = u16::from_be_bytes(read_bytes(datagram, &mut offset)) as usize;
+        let count = u16::from_be_bytes(read_bytes(datagram, &mut offset)) as usize;
+        let chunk = &datagram[FRAGMENT_HEADER_SIZE..];
+        if index >= count || count > MAX_FRAGMENTS || chunk.len() > FRAGMENT_PAYLOAD_SIZE {
+            return Err(SocketError::Malformed);
+        }
+        let key = (addr, msg_id);
+        if !self.partial.contains_key(&key) {
+            self.start(key, count);
+        }
+        let partial = self
+            .partial
+            .get_mut(&key)
+            .expect("[Reassembler] El mensaje parcial deberia existir");
+        if partial.chunks.len() != count {
+            return Err(SocketError::Malformed);
+        }
+        if partial.chunks[index].is_some() {
+            return Ok(None);
+        }
+        partial.chunks[index] = Some(chunk.to_vec());
+        partial.missing -= 1;
+        partial.size += chunk.len();
+        self.buffered += chunk.len();
+        if partial.missing > 0 {
+            // Se descartan los mas viejos, nunca el que se esta armando
+            while self.buffered > MAX_BUFFERED && self.order.front() != Some(&key) {
+                self.drop_oldest();
+            }
+            return Ok(None);
+        }
+        self.order.retain(|other| *other != key);
+        let message = self.partial.remove(&key).map(|partial| {
+            self.buffered -= partial.size;
+            partial.chunks.into_iter().flatten().flatten().collect()
+        });
+        Ok(message)
+    }
+
+    fn start(&mut self, key: (SocketAddr, u32), count: usize) {
+        if self.order.len() >= MAX_PARTIAL {
+            self.drop_oldest();
+        }
+        self.partial.insert(
+            key,
+            Partial {
+                chunks: vec![None; count],
+                missing: count,
+                size: 0,
+            },
+        );
+        self.order.push_back(key);
+    }
+
+    fn drop_oldest(&mut self) {
+        if let Some(oldest) = self.order.pop_front() {
+            if let Some(partial) = self.partial.remove(&oldest) {
+                self.buffered -= partial.size;
+            }
+        }
+    }
+}
+
+fn read_bytes<const N: usize>(datagram: &[u8], offset: &mut usize) -> [u8; N] {
+    let bytes = datagram[*offset..*offset + N]
+        .try_into()
+        .expect("[Reassembler] El encabezado deberia tener el largo esperado");
+    *offset += N;
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_messages_should_be_sent_as_they_are() {
+        assert_eq!(fragment(b"hola", 1), Ok(vec![b"hola".to_vec()]));
+    }
+
+    #[test]
+    fn it_should_reassemble_fragments_received_out_of_order() {
+        let addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let message: Vec<u8> = (0..2000).map(|i| (i % 251) as u8).collect();
+        let mut fragments = fragment(&message, 7).unwrap();
+        assert_eq!(fragments.len(), 4);
+        assert!(fragments.iter().all(|f| f.len() <= UDP_PACKET_SIZE));
+        fragments.reverse();
+
+        let mut reassembler = Reassembler::new();
+        let last = fragments.pop().unwrap();
+        for datagram in &fragments {
+            assert_eq!(reassembler.push(datagram, addr), Ok(None));
+        }
+        assert_eq!(reassembler.push(&last, addr), Ok(Some(message)));
+    }
+
+    #[test]
+    fn it_should_reject_a_fragment_that_announces_too_many_fragments() {
+        let addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let mut datagram = fragment(&[0; 600], 1).unwrap().remove(0);
+        datagram[10..12].copy_from_slice(&u16::MAX.to_be_bytes());
+
+        let mut reassembler = Reassembler::new();
+        assert_eq!(
+            reassembler.push(&datagram, addr),
+            Err(SocketError::Malformed)
+        );
+        assert!(reassembler.partial.is_empty());
+    }
+
+    #[test]
+    fn it_should_bound_the_bytes_of_the_incomplete_messages() {
+        let addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let message = vec![0; MAX_MESSAGE_SIZE];
+        let mut reassembler = Reassembler::new();
+        // Todos los fragmentos menos el ultimo de varios mensajes
+        for msg_id in 0..MAX_PARTIAL as u32 {
+            let mut fragments = fragment(&message, msg_id).unwrap();
+            fragments.pop();
+            for datagram in &fragments {
+                assert_eq!(reassembler.push(datagram, addr), Ok(None));
+            }
+            assert!(reassembler.buffered <= MAX_BUFFERED);
+        }
+        assert!(reassembler.partial.len() < 5);
+        let buffered: usize = reassembler.partial.values().map(|p| p.size).sum();
+        assert_eq!(reassembler.buffered, buffered);
+
+        let last = fragment(&message, MAX_PARTIAL as u32 - 1).unwrap().pop();
+        assert_eq!(reassembler.push(&last.unwrap(), addr), Ok(Some(message)));
+    }
+
+    #[test]
+    fn it_should_reject_a_fragment_with_an_invalid_index() {
+        let addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let mut datagram = fragment(&[0; 600], 1).unwrap().remove(0);
+        // Indice 2 de 2 fragmentos
+        datagram[8..10].copy_from_slice(&2u16.to_be_bytes());
+
+        let mut reassembler = Reassembler::new();
+        assert_eq!(
+            reassembler.push(&datagram, addr),
+            Err(SocketError::Malformed)
+        );
+    }
+}
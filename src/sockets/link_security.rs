@@ -0,0 +1,38 @@
+use std::{net::SocketAddr, time::Duration};
+
+use super::{
+    encrypted_socket::maybe_encrypted,
+    signed_socket::{maybe_signed, Keys},
+    udp_socket_receiver::UdpSocketReceiver,
+    udp_socket_sender::UdpSocketSender,
+};
+
+/// Proteccion de los enlaces: claves de firma, ventana anti-replay y
+/// claves precompartidas de cifrado. Sin claves no se aplica nada.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LinkSecurity {
+    pub keys: Keys,
+    pub replay_window: Duration,
+    pub psks: Keys,
+}
+
+impl LinkSecurity {
+    /// Cifra (si hay `psks`) y firma (si hay `keys`) los mensajes del
+    /// par de emisor y receptor. El cifrado queda mas cerca del
+    /// socket, por lo que tambien oculta la firma y el nonce.
+    /// `identity` es la direccion de la tabla de claves con la que se
+    /// identifica el socket, aunque envie desde un puerto efimero.
+    #[must_use]
+    pub fn secure(
+        &self,
+        sender: Box<dyn UdpSocketSender + Send>,
+        receiver: Box<dyn UdpSocketReceiver + Send>,
+        identity: SocketAddr,
+    ) -> (
+        Box<dyn UdpSocketSender + Send>,
+        Box<dyn UdpSocketReceiver + Send>,
+    ) {
+        let (sender, receiver) = maybe_encrypted(sender, receiver, identity, &self.psks);
+        maybe_signed(sender, receiver, identity, &self.keys, self.replay_window)
+    }
+}
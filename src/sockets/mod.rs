@@ -1,5 +1,17 @@
+pub mod async_udp_socket_receiver;
+pub mod async_udp_socket_sender;
+pub mod async_udp_socket_wrap;
+pub mod channel_socket;
 pub mod constants;
+pub mod encrypted_socket;
+pub mod fragment;
+pub mod link_security;
+pub mod reliable_socket;
+pub mod replay_window;
+pub mod shared_socket_sender;
+pub mod signed_socket;
 pub mod socket_error;
+pub mod tcp_transport;
 pub mod udp_socket_receiver;
 pub mod udp_socket_sender;
 pub mod udp_socket_wrap;
@@ -0,0 +1,596 @@
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    convert::TryInto,
+    mem::size_of,
+    net::SocketAddr,
+    sync::{mpsc, Arc, Mutex, Weak},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use tracing::warn;
+
+use crate::metrics::registry::metrics;
+
+use super::{
+    channel_socket::ChannelSocketReceiver, fragment::MAX_MESSAGE_SIZE, socket_error::SocketError,
+    udp_socket_receiver::UdpSocketReceiver, udp_socket_sender::UdpSocketSender,
+};
+
+const DATA_BYTE: u8 = b'D';
+const ACK_BYTE: u8 = b'K';
+pub const HEADER_SIZE: usize = 1 + size_of::<u64>();
+// Cuantos numeros de secuencia por debajo del mayor recibido de un
+// par se recuerdan. Los anteriores a la ventana se descartan.
+const DEDUP_WINDOW: u64 = 1024;
+// Cantidad de pares cuya secuencia se recuerda
+const MAX_PEERS: usize = 1024;
+
+/// Parametros de retransmision del canal confiable
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ReliableConfig {
+    pub retransmit_timeout: Duration,
+    pub max_attempts: u32,
+}
+
+/// Estado por par. Al superar `MAX_PEERS` se olvida el par mas antiguo.
+struct PeerMap<T> {
+    order: VecDeque<SocketAddr>,
+    values: HashMap<SocketAddr, T>,
+}
+
+impl<T> PeerMap<T> {
+    fn new() -> Self {
+        PeerMap {
+            order: VecDeque::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    fn entry(&mut self, addr: SocketAddr, default: impl FnOnce() -> T) -> &mut T {
+        if !self.values.contains_key(&addr) {
+            if self.order.len() >= MAX_PEERS {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.values.remove(&oldest);
+                }
+            }
+            self.order.push_back(addr);
+        }
+        self.values.entry(addr).or_insert_with(default)
+    }
+}
+
+/// Primer numero de secuencia hacia un par. Arranca del reloj para
+/// que un proceso reiniciado no reuse numeros que el par recuerda.
+fn first_seq() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64)
+}
+
+struct Pending {
+    frame: Vec<u8>,
+    addr: SocketAddr,
+    sent_at: Instant,
+    attempts: u32,
+}
+
+struct Shared {
+    sender: Mutex<Box<dyn UdpSocketSender + Send>>,
+    pending: Mutex<HashMap<(SocketAddr, u64), Pending>>,
+    next_seqs: Mutex<PeerMap<u64>>,
+}
+
+impl Shared {
+    fn send_raw(&self, frame: &[u8], addr: &SocketAddr) -> Result<(), SocketError> {
+        self.sender
+            .lock()
+            .expect("[Reliable Socket] Lock de emisor envenenado")
+            .send_to(frame, addr)
+    }
+
+    /// Proximo numero de secuencia hacia `addr`. Cada par tiene su
+    /// propia secuencia, por lo que el receptor la ve sin saltos.
+    fn next_seq(&self, addr: SocketAddr) -> u64 {
+        let mut next_seqs = self
+            .next_seqs
+            .lock()
+            .expect("[Reliable Socket] Lock de secuencias envenenado");
+        let next = next_seqs.entry(addr, first_seq);
+        let seq = *next;
+        *next = next.wrapping_add(1);
+        seq
+    }
+
+    fn retransmit(&self, config: ReliableConfig) {
+        let mut to_send = Vec::new();
+        {
+            let mut pending = self
+                .pending
+                .lock()
+                .expect("[Reliable Socket] Lock de pendientes envenenado");
+            pending.retain(|(addr, seq), message| {
+                if message.sent_at.elapsed() < config.retransmit_timeout {
+                    return true;
+                }
+                if message.attempts >= config.max_attempts {
+                    warn!(
+                        seq,
+                        peer = %addr,
+                        attempts = message.attempts,
+                        "Mensaje descartado sin ack"
+                    );
+                    metrics().dropped_messages.inc(&[]);
+                    return false;
+                }
+                message.attempts += 1;
+                message.sent_at = Instant::now();
+                to_send.push((message.frame.clone(), message.addr));
+                true
+            });
+        }
+        metrics().retransmissions.inc_by(&[], to_send.len() as u64);
+        for (frame, addr) in to_send {
+            let _drop = self.send_raw(&frame, &addr);
+        }
+    }
+}
+
+/// Numeros de secuencia recibidos de un par: el mayor y los que
+/// estan hasta `DEDUP_WINDOW` por debajo de el
+#[derive(Default)]
+struct SeqWindow {
+    highest: u64,
+    seen: BTreeSet<u64>,
+}
+
+enum Received {
+    New,
+    Duplicate,
+    /// Anterior a la ventana: no se puede saber si ya se entrego
+    Stale,
+}
+
+impl SeqWindow {
+    fn receive(&mut self, seq: u64) -> Received {
+        if self.seen.is_empty() || seq > self.highest {
+            self.highest = seq;
+            let floor = seq.saturating_sub(DEDUP_WINDOW);
+            self.seen = self.seen.split_off(&floor);
+        } else if seq < self.highest.saturating_sub(DEDUP_WINDOW) {
+            return Received::Stale;
+        }
+        if self.seen.insert(seq) {
+            Received::New
+        } else {
+            Received::Duplicate
+        }
+    }
+}
+
+/// Procesa las tramas recibidas: quita de los pendientes los mensajes
+/// confirmados, confirma los datos y descarta los repetidos
+struct Frames {
+    windows: PeerMap<SeqWindow>,
+}
+
+impl Frames {
+    fn new() -> Self {
+        Frames {
+            windows: PeerMap::new(),
+        }
+    }
+
+    /// Devuelve el contenido de `frame` si es un mensaje nuevo
+    fn handle(&mut self, shared: &Shared, frame: &[u8], addr: SocketAddr) -> Option<Vec<u8>> {
+        if frame.len() < HEADER_SIZE {
+            warn!(peer = %addr, len = frame.len(), "Trama demasiado corta descartada");
+            return None;
+        }
+        let seq_bytes: [u8; size_of::<u64>()] = frame[1..HEADER_SIZE]
+            .try_into()
+            .expect("[Reliable Socket] Los numeros de secuencia deberian ocupar 8 bytes");
+        let seq = u64::from_be_bytes(seq_bytes);
+        match frame[0] {
+            ACK_BYTE => {
+                shared
+                    .pending
+                    .lock()
+                    .expect("[Reliable Socket] Lock de pendientes envenenado")
+                    .remove(&(addr, seq));
+            }
+            DATA_BYTE => match self.windows.entry(addr, SeqWindow::default).receive(seq) {
+                Received::New => {
+                    let _drop = shared.send_raw(&build_frame(ACK_BYTE, seq, &[]), &addr);
+                    return Some(frame[HEADER_SIZE..].to_vec());
+                }
+                // Los duplicados tambien se confirman: puede haberse perdido el ack
+                Received::Duplicate => {
+                    let _drop = shared.send_raw(&build_frame(ACK_BYTE, seq, &[]), &addr);
+                }
+                // Sin ack el emisor lo termina descartando y lo registra
+                Received::Stale => {
+                    warn!(peer = %addr, seq, "Trama anterior a la ventana descartada")
+                }
+            },
+            code => warn!(peer = %addr, code, "Codigo de trama desconocido"),
+        }
+        None
+    }
+}
+
+/// Emisor del canal confiable. Cada mensaje lleva un numero de
+/// secuencia y se retransmite hasta recibir su ack o agotar los
+/// intentos. Los clones comparten la secuencia y los pendientes.
+#[derive(Clone)]
+pub struct ReliableSender {
+    shared: Arc<Shared>,
+}
+
+enum Inbound {
+    /// Las tramas las procesa el hilo del canal, que entrega los
+    /// mensajes nuevos por un canal
+    Pumped(ChannelSocketReceiver),
+    /// Las tramas se procesan y los pendientes se retransmiten al
+    /// recibir
+    Polled {
+        receiver: Box<dyn UdpSocketReceiver + Send>,
+        frames: Frames,
+        config: ReliableConfig,
+    },
+}
+
+/// Receptor del canal confiable. Entrega cada mensaje una sola vez;
+/// los acks, tanto los que envia como los que recibe, los procesa el
+/// canal aunque no se llame a `recv`, salvo en `polled_pair`.
+pub struct ReliableReceiver {
+    shared: Arc<Shared>,
+    inbound: Inbound,
+}
+
+fn new_shared(sender: Box<dyn UdpSocketSender + Send>) -> Arc<Shared> {
+    Arc::new(Shared {
+        sender: Mutex::new(sender),
+        pending: Mutex::new(HashMap::new()),
+        next_seqs: Mutex::new(PeerMap::new()),
+    })
+}
+
+/// Crea las dos mitades del canal confiable sobre un emisor y un
+/// receptor del mismo socket. Un hilo lee el receptor, procesa los
+/// acks, confirma y filtra los mensajes recibidos y retransmite los
+/// pendientes mientras exista alguna de las dos mitades. Asi un
+/// usuario que solo envia tambien recibe los acks de sus mensajes.
+#[must_use]
+pub fn reliable_pair(
+    sender: Box<dyn UdpSocketSender + Send>,
+    mut receiver: Box<dyn UdpSocketReceiver + Send>,
+    config: ReliableConfig,
+) -> (ReliableSender, ReliableReceiver) {
+    let shared = new_shared(sender);
+    let weak: Weak<Shared> = Arc::downgrade(&shared);
+    let (delivered, delivered_receiver) = mpsc::channel();
+    thread::spawn(move || {
+        receiver.set_timeout(Some(config.retransmit_timeout / 2));
+        let mut frames = Frames::new();
+        loop {
+            let result = receiver.recv(HEADER_SIZE + MAX_MESSAGE_SIZE);
+            let shared = match weak.upgrade() {
+                Some(shared) => shared,
+                None => break,
+            };
+            if let Ok((frame, addr)) = result {
+                if let Some(message) = frames.handle(&shared, &frame, addr) {
+                    // Sin receptor el mensaje se pierde, pero ya se confirmo
+                    let _drop = delivered.send((message, addr));
+                }
+            }
+            shared.retransmit(config);
+        }
+    });
+    (
+        ReliableSender {
+            shared: shared.clone(),
+        },
+        ReliableReceiver {
+            shared,
+            inbound: Inbound::Pumped(ChannelSocketReceiver::new(delivered_receiver)),
+        },
+    )
+}
+
+/// Canal confiable para receptores que no esperan, como los del
+/// `EventLoop`: no usa hilos propios. Las tramas se procesan y los
+/// pendientes se retransmiten en cada `recv`, que el event loop
+/// llama con cada datagrama que llega al socket y en cada tick.
+#[must_use]
+pub fn polled_pair(
+    sender: Box<dyn UdpSocketSender + Send>,
+    receiver: Box<dyn UdpSocketReceiver + Send>,
+    config: ReliableConfig,
+) -> (ReliableSender, ReliableReceiver) {
+    let shared = new_shared(sender);
+    (
+        ReliableSender {
+            shared: shared.clone(),
+        },
+        ReliableReceiver {
+            shared,
+            inbound: Inbound::Polled {
+                receiver,
+                frames: Frames::new(),
+                config,
+            },
+        },
+    )
+}
+
+/// Envuelve emisor y receptor en un canal confiable si `config_opt`
+/// lo indica, o los devuelve sin cambios en caso contrario
+#[must_use]
+pub fn maybe_reliable(
+    sender: Box<dyn UdpSocketSender + Send>,
+    receiver: Box<dyn UdpSocketReceiver + Send>,
+    config_opt: Option<ReliableConfig>,
+) -> (
+    Box<dyn UdpSocketSender + Send>,
+    Box<dyn UdpSocketReceiver + Send>,
+) {
+    match config_opt {
+        Some(config) => {
+            let (sender, receiver) = reliable_pair(sender, receiver, config);
+            (Box::new(sender), Box::new(receiver))
+        }
+        None => (sender, receiver),
+    }
+}
+
+/// Como `maybe_reliable`, pero con `polled_pair`
+#[must_use]
+pub fn maybe_polled_reliable(
+    sender: Box<dyn UdpSocketSender + Send>,
+    receiver: Box<dyn UdpSocketReceiver + Send>,
+    config_opt: Option<ReliableConfig>,
+) -> (
+    Box<dyn UdpSocketSender + Send>,
+    Box<dyn UdpSocketReceiver + Send>,
+) {
+    match config_opt {
+        Some(config) => {
+            let (sender, receiver) = polled_pair(sender, receiver, config);
+            (Box::new(sender), Box::new(receiver))
+        }
+        None => (sender, receiver),
+    }
+}
+
+fn build_frame(code: u8, seq: u64, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![code];
+    frame.append(&mut seq.to_be_bytes().to_vec());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+impl ReliableSender {
+    /// Cantidad de mensajes que todavia no recibieron ack
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.shared
+            .pending
+            .lock()
+            .expect("[Reliable Socket] Lock de pendientes envenenado")
+            .len()
+    }
+}
+
+impl UdpSocketSender for ReliableSender {
+    fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<(), SocketError> {
+        let seq = self.shared.next_seq(*addr);
+        let frame = build_frame(DATA_BYTE, seq, buf);
+        self.shared
+            .pending
+            .lock()
+            .expect("[Reliable Socket] Lock de pendientes envenenado")
+            .insert(
+                (*addr, seq),
+                Pending {
+                    frame: frame.clone(),
+                    addr: *addr,
+                    sent_at: Instant::now(),
+                    attempts: 1,
+                },
+            );
+        self.shared.send_raw(&frame, addr)
+    }
+}
+
+impl UdpSocketReceiver for ReliableReceiver {
+    fn recv(&mut self, n: usize) -> Result<(Vec<u8>, SocketAddr), SocketError> {
+        match &mut self.inbound {
+            Inbound::Pumped(delivered) => delivered.recv(n),
+            Inbound::Polled {
+                receiver,
+                frames,
+                config,
+            } => loop {
+                self.shared.retransmit(*config);
+                let (frame, addr) = receiver.recv(HEADER_SIZE + n)?;
+                if let Some(message) = frames.handle(&self.shared, &frame, addr) {
+                    return Ok((message, addr));
+                }
+            },
+        }
+    }
+
+    fn set_timeout(&mut self, opt_timeout: Option<Duration>) {
+        match &mut self.inbound {
+            Inbound::Pumped(delivered) => delivered.set_timeout(opt_timeout),
+            Inbound::Polled { receiver, .. } => receiver.set_timeout(opt_timeout),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sockets::{constants::UDP_PACKET_SIZE, udp_socket_wrap::UdpSocketWrap};
+    use ntest::timeout;
+    use std::net::UdpSocket;
+
+    fn config() -> ReliableConfig {
+        ReliableConfig {
+            retransmit_timeout: Duration::from_millis(50),
+            max_attempts: 3,
+        }
+    }
+
+    fn pair(addr: SocketAddr) -> (ReliableSender, ReliableReceiver) {
+        let socket = UdpSocketWrap::new_with_addr(None, addr).unwrap();
+        let clone = socket.try_clone().unwrap();
+        let (sender, mut receiver) = reliable_pair(Box::new(socket), Box::new(clone), config());
+        receiver.set_timeout(Some(Duration::from_millis(300)));
+        (sender, receiver)
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_deliver_once_and_clear_pending_after_ack_without_recv() {
+        let addr_a: SocketAddr = "127.0.0.1:59171".parse().unwrap();
+        let addr_b: SocketAddr = "127.0.0.1:59172".parse().unwrap();
+        // A solo envia: nunca llama a recv
+        let (mut sender_a, _receiver_a) = pair(addr_a);
+        let (_sender_b, mut receiver_b) = pair(addr_b);
+
+        sender_a.send_to(b"hola", &addr_b).unwrap();
+        assert_eq!(receiver_b.recv(4), Ok((b"hola".to_vec(), addr_a)));
+
+        while sender_a.pending() > 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(receiver_b.recv(4), Err(SocketError::Timeout));
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn a_polled_pair_should_process_acks_on_recv() {
+        let addr_a: SocketAddr = "127.0.0.1:59219".parse().unwrap();
+        let addr_b: SocketAddr = "127.0.0.1:59220".parse().unwrap();
+        let socket =
+            UdpSocketWrap::new_with_addr(Some(Duration::from_millis(300)), addr_a).unwrap();
+        let clone = socket.try_clone().unwrap();
+        let (mut sender_a, mut receiver_a) =
+            polled_pair(Box::new(socket), Box::new(clone), config());
+        let (_sender_b, mut receiver_b) = pair(addr_b);
+
+        sender_a.send_to(b"hola", &addr_b).unwrap();
+        assert_eq!(receiver_b.recv(4), Ok((b"hola".to_vec(), addr_a)));
+
+        assert_eq!(receiver_a.recv(4), Err(SocketError::Timeout));
+        assert_eq!(sender_a.pending(), 0);
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn a_polled_pair_should_retransmit_on_recv() {
+        let addr_a: SocketAddr = "127.0.0.1:59221".parse().unwrap();
+        let addr_raw: SocketAddr = "127.0.0.1:59222".parse().unwrap();
+        let raw = UdpSocket::bind(addr_raw).unwrap();
+        let socket =
+            UdpSocketWrap::new_with_addr(Some(Duration::from_millis(100)), addr_a).unwrap();
+        let clone = socket.try_clone().unwrap();
+        let (mut sender_a, mut receiver_a) =
+            polled_pair(Box::new(socket), Box::new(clone), config());
+
+        sender_a.send_to(b"hola", &addr_raw).unwrap();
+        let mut buf = [0; UDP_PACKET_SIZE];
+        let (n, _) = raw.recv_from(&mut buf).unwrap();
+        let first = buf[..n].to_vec();
+
+        // Sin ack, el siguiente recv retransmite la trama
+        assert_eq!(receiver_a.recv(4), Err(SocketError::Timeout));
+        assert_eq!(receiver_a.recv(4), Err(SocketError::Timeout));
+        let (n, _) = raw.recv_from(&mut buf).unwrap();
+        assert_eq!(buf[..n].to_vec(), first);
+    }
+
+    #[test]
+    fn seq_window_should_slide_with_the_highest_sequence_number() {
+        let mut window = SeqWindow::default();
+
+        assert!(matches!(window.receive(5000), Received::New));
+        // Fuera de orden pero dentro de la ventana
+        assert!(matches!(window.receive(4990), Received::New));
+        assert!(matches!(window.receive(4990), Received::Duplicate));
+        assert!(matches!(
+            window.receive(5000 - DEDUP_WINDOW - 1),
+            Received::Stale
+        ));
+
+        assert!(matches!(window.receive(5000 + DEDUP_WINDOW), Received::New));
+        assert!(matches!(window.receive(4990), Received::Stale));
+        assert!(matches!(window.receive(5000), Received::Duplicate));
+        assert_eq!(window.seen.len(), 2);
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_discard_duplicated_messages() {
+        let addr_raw: SocketAddr = "127.0.0.1:59173".parse().unwrap();
+        let addr_b: SocketAddr = "127.0.0.1:59174".parse().unwrap();
+        let raw = UdpSocket::bind(addr_raw).unwrap();
+        let (_sender_b, mut receiver_b) = pair(addr_b);
+
+        let frame = build_frame(DATA_BYTE, 7, b"hola");
+        raw.send_to(&frame, addr_b).unwrap();
+        raw.send_to(&frame, addr_b).unwrap();
+
+        assert_eq!(receiver_b.recv(4), Ok((b"hola".to_vec(), addr_raw)));
+        assert_eq!(receiver_b.recv(4), Err(SocketError::Timeout));
+
+        // Ambas copias se confirman
+        let ack = build_frame(ACK_BYTE, 7, &[]);
+        let mut buf = [0; UDP_PACKET_SIZE];
+        for _ in 0..2 {
+            let (n, _) = raw.recv_from(&mut buf).unwrap();
+            assert_eq!(buf[..ack.len()].to_vec(), ack);
+            assert!(n >= ack.len());
+        }
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_discard_frames_shorter_than_the_header() {
+        let addr_raw: SocketAddr = "127.0.0.1:59211".parse().unwrap();
+        let addr_b: SocketAddr = "127.0.0.1:59212".parse().unwrap();
+        let raw = UdpSocket::bind(addr_raw).unwrap();
+        let (_sender_b, mut receiver_b) = pair(addr_b);
+
+        raw.send_to(&[DATA_BYTE, 1, 2], addr_b).unwrap();
+        raw.send_to(&build_frame(DATA_BYTE, 7, b"hola"), addr_b)
+            .unwrap();
+
+        assert_eq!(receiver_b.recv(4), Ok((b"hola".to_vec(), addr_raw)));
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_retransmit_until_max_attempts_without_ack() {
+        let addr_a: SocketAddr = "127.0.0.1:59175".parse().unwrap();
+        let addr_raw: SocketAddr = "127.0.0.1:59176".parse().unwrap();
+        let raw = UdpSocket::bind(addr_raw).unwrap();
+        raw.set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        let (mut sender_a, _receiver_a) = pair(addr_a);
+
+        sender_a.send_to(b"hola", &addr_raw).unwrap();
+
+        let mut buf = [0; UDP_PACKET_SIZE];
+        let mut received = 0;
+        while raw.recv_from(&mut buf).is_ok() {
+            assert_eq!(buf[HEADER_SIZE..HEADER_SIZE + 4].to_vec(), b"hola".to_vec());
+            received += 1;
+        }
+        assert_eq!(received, config().max_attempts);
+        assert_eq!(sender_a.pending(), 0);
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::SocketAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// Cantidad de nonces recordados por emisor
+const MAX_NONCES: usize = 4096;
+
+#[derive(Default)]
+struct PeerWindow {
+    nonces: HashSet<u64>,
+    order: VecDeque<(u64, u64)>,
+    // Marcas de tiempo menores o iguales se rechazan: sus nonces ya se olvidaron
+    floor: u64,
+}
+
+/// Ventana anti-replay. Acepta un mensaje si su marca de tiempo esta
+/// a menos de `window` del reloj local y su nonce no se vio antes.
+/// Los nonces se olvidan al salir de la ventana o, si hay mas de
+/// `MAX_NONCES`, se rechaza todo lo anterior al ultimo olvidado.
+pub struct ReplayWindow {
+    window: Duration,
+    peers: HashMap<SocketAddr, PeerWindow>,
+}
+
+/// Milisegundos desde la epoca segun el reloj local
+#[must_use]
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis() as u64)
+}
+
+impl ReplayWindow {
+    #[must_use]
+    pub fn new(window: Duration) -> Self {
+        ReplayWindow {
+            window,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Registra el mensaje de `addr` con marca `timestamp` (ms) y
+    /// `nonce`. Devuelve falso si es viejo, del futuro o repetido.
+    pub fn accept(&mut self, addr: SocketAddr, timestamp: u64, nonce: u64, now: u64) -> bool {
+        let window = self.window.as_millis() as u64;
+        if timestamp.saturating_add(window) < now || timestamp > now.saturating_add(window) {
+            return false;
+        }
+        let peer = self.peers.entry(addr).or_default();
+        while let Some((oldest, nonce)) = peer.order.front().copied() {
+            if oldest.saturating_add(window) >= now {
+                break;
+            }
+            peer.order.pop_front();
+            peer.nonces.remove(&nonce);
+        }
+        if timestamp <= peer.floor || !peer.nonces.insert(nonce) {
+            return false;
+        }
+        peer.order.push_back((timestamp, nonce));
+        if peer.order.len() > MAX_NONCES {
+            if let Some((oldest, nonce)) = peer.order.pop_front() {
+                peer.nonces.remove(&nonce);
+                peer.floor = peer.floor.max(oldest);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_reject_repeated_nonces_inside_the_window() {
+        let addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let mut window = ReplayWindow::new(Duration::from_secs(1));
+
+        assert!(window.accept(addr, 1000, 7, 1000));
+        assert!(!window.accept(addr, 1000, 7, 1500));
+        assert!(window.accept(addr, 1000, 8, 1500));
+    }
+
+    #[test]
+    fn it_should_reject_old_and_future_timestamps() {
+        let addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let mut window = ReplayWindow::new(Duration::from_secs(1));
+
+        assert!(!window.accept(addr, 1000, 1, 2001));
+        assert!(!window.accept(addr, 3001, 2, 2000));
+        assert!(window.accept(addr, 2500, 3, 2000));
+    }
+
+    #[test]
+    fn it_should_reject_older_messages_once_nonces_are_forgotten() {
+        let addr: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let mut window = ReplayWindow::new(Duration::from_secs(60));
+
+        for nonce in 0..=MAX_NONCES as u64 {
+            assert!(window.accept(addr, 1000 + nonce, nonce, 1000));
+        }
+
+        // El nonce 0 se olvido, pero su marca de tiempo quedo bajo el piso
+        assert!(!window.accept(addr, 1000, 0, 1000));
+    }
+}
@@ -0,0 +1,31 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use super::{socket_error::SocketError, udp_socket_sender::UdpSocketSender};
+
+/// Emisor que se puede clonar sin importar la implementacion que
+/// envuelve. Los clones envian por el mismo emisor interno.
+#[derive(Clone)]
+pub struct SharedSocketSender {
+    sender: Arc<Mutex<Box<dyn UdpSocketSender + Send>>>,
+}
+
+impl SharedSocketSender {
+    #[must_use]
+    pub fn new(sender: Box<dyn UdpSocketSender + Send>) -> Self {
+        SharedSocketSender {
+            sender: Arc::new(Mutex::new(sender)),
+        }
+    }
+}
+
+impl UdpSocketSender for SharedSocketSender {
+    fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<(), SocketError> {
+        self.sender
+            .lock()
+            .expect("[Shared Socket Sender] Lock de emisor envenenado")
+            .send_to(buf, addr)
+    }
+}
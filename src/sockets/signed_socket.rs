@@ -0,0 +1,496 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::{TryFrom, TryInto},
+    fmt,
+    mem::size_of,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tracing::warn;
+
+use super::{
+    replay_window::{now_millis, ReplayWindow},
+    socket_error::SocketError,
+    udp_socket_receiver::UdpSocketReceiver,
+    udp_socket_sender::UdpSocketSender,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const TAG_SIZE: usize = 32;
+/// Marca de tiempo (ms) y nonce que preceden al mensaje firmado
+pub const FRESHNESS_SIZE: usize = 2 * size_of::<u64>();
+/// Identidad del emisor mas larga: familia, IPv6 y puerto
+pub const MAX_IDENTITY_SIZE: usize = 1 + 16 + size_of::<u16>();
+// Sal de la extraccion HKDF de las claves de enlace
+const LINK_SALT: &[u8] = b"alglobo-link";
+// Separa las claves de firma de las de cifrado aunque se repitan
+const SIGN_CONTEXT: &[u8] = b"alglobo-hmac";
+// Cantidad de puertos efimeros cuya identidad se recuerda
+const MAX_PEERS: usize = 1024;
+
+/// Claves compartidas para firmar mensajes, una por direccion. La
+/// clave de un enlace se deriva de las claves de sus dos extremos,
+/// identificados por su direccion en la tabla aunque envien desde un
+/// puerto efimero (conexiones TCP salientes, `retry`).
+#[derive(Clone, Default, PartialEq)]
+pub struct Keys {
+    keys: Arc<HashMap<SocketAddr, Vec<u8>>>,
+}
+
+impl Keys {
+    #[must_use]
+    pub fn new(keys: HashMap<SocketAddr, Vec<u8>>) -> Self {
+        Keys {
+            keys: Arc::new(keys),
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    #[must_use]
+    pub fn contains(&self, addr: &SocketAddr) -> bool {
+        self.keys.contains_key(addr)
+    }
+
+    /// Clave del enlace entre `a` y `b` para `context`, derivada con
+    /// HKDF-SHA256 de las direcciones y claves de ambos extremos, cada
+    /// una precedida por su largo. Los extremos se ordenan, por lo que
+    /// los dos lados derivan la misma clave. Devuelve `None` si alguno
+    /// de los extremos no tiene clave.
+    pub(crate) fn link_key(
+        &self,
+        a: SocketAddr,
+        b: SocketAddr,
+        context: &[u8],
+    ) -> Option<[u8; 32]> {
+        let mut ends = [(a, self.keys.get(&a)?), (b, self.keys.get(&b)?)];
+        ends.sort_by_key(|(addr, _)| *addr);
+        let mut extract = mac(LINK_SALT, &[]);
+        for (addr, key) in ends {
+            for field in [encode_identity(addr), key.clone()] {
+                let len = u32::try_from(field.len())
+                    .expect("[Signed Socket] Una clave no deberia ocupar mas de 4 GiB");
+                extract.update(&len.to_be_bytes());
+                extract.update(&field);
+            }
+        }
+        let prk = extract.finalize().into_bytes();
+        // Un unico bloque de HKDF-Expand alcanza para 32 bytes
+        let mut expand = mac(&prk, context);
+        expand.update(&[1]);
+        Some(expand.finalize().into_bytes().into())
+    }
+}
+
+// Las claves no se muestran para no filtrarlas en los logs
+impl fmt::Debug for Keys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.keys.keys()).finish()
+    }
+}
+
+fn mac(key: &[u8], payload: &[u8]) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(key).expect("[Signed Socket] HMAC acepta cualquier clave");
+    mac.update(payload);
+    mac
+}
+
+/// Codifica la identidad de un emisor: familia (4 o 6), IP y puerto
+pub(crate) fn encode_identity(identity: SocketAddr) -> Vec<u8> {
+    let mut bytes = match identity.ip() {
+        IpAddr::V4(ip) => [&[4][..], &ip.octets()].concat(),
+        IpAddr::V6(ip) => [&[6][..], &ip.octets()].concat(),
+    };
+    bytes.extend_from_slice(&identity.port().to_be_bytes());
+    bytes
+}
+
+/// Separa la identidad que encabeza `message` del resto del mensaje
+pub(crate) fn split_identity(message: &[u8]) -> Option<(SocketAddr, &[u8])> {
+    let (family, rest) = message.split_first()?;
+    let ip_size = match family {
+        4 => 4,
+        6 => 16,
+        _ => return None,
+    };
+    if rest.len() < ip_size + size_of::<u16>() {
+        return None;
+    }
+    let (ip, rest) = rest.split_at(ip_size);
+    let (port, rest) = rest.split_at(size_of::<u16>());
+    let ip: IpAddr = match ip_size {
+        4 => <[u8; 4]>::try_from(ip).ok()?.into(),
+        _ => <[u8; 16]>::try_from(ip).ok()?.into(),
+    };
+    let port = u16::from_be_bytes(port.try_into().ok()?);
+    Some((SocketAddr::new(ip, port), rest))
+}
+
+#[derive(Default)]
+struct PeersState {
+    order: VecDeque<SocketAddr>,
+    identities: HashMap<SocketAddr, SocketAddr>,
+}
+
+/// Identidades de los pares que envian desde puertos efimeros,
+/// aprendidas de sus mensajes autenticados para poder responderles.
+/// La comparten el emisor y el receptor de un mismo socket.
+#[derive(Clone, Default)]
+pub(crate) struct Peers {
+    state: Arc<Mutex<PeersState>>,
+}
+
+impl Peers {
+    /// Recuerda que `addr` envia con la identidad `identity`. Al
+    /// superar `MAX_PEERS` se olvida el mas antiguo.
+    pub(crate) fn learn(&self, addr: SocketAddr, identity: SocketAddr) {
+        if addr == identity {
+            return;
+        }
+        let mut state = self.state.lock().expect("[Signed Socket] Lock envenenado");
+        if state.identities.insert(addr, identity).is_none() {
+            state.order.push_back(addr);
+        }
+        if state.order.len() > MAX_PEERS {
+            if let Some(oldest) = state.order.pop_front() {
+                state.identities.remove(&oldest);
+            }
+        }
+    }
+
+    /// Identidad de `addr`: la propia direccion si esta en `keys`, o
+    /// la aprendida de sus mensajes
+    pub(crate) fn identity(&self, keys: &Keys, addr: SocketAddr) -> Option<SocketAddr> {
+        if keys.contains(&addr) {
+            return Some(addr);
+        }
+        self.state
+            .lock()
+            .expect("[Signed Socket] Lock envenenado")
+            .identities
+            .get(&addr)
+            .copied()
+    }
+}
+
+/// Emisor que antepone a cada mensaje la identidad con la que firma,
+/// su marca de tiempo y un nonce, y agrega el HMAC-SHA256 del
+/// conjunto con la clave del enlace. Los mensajes a un par sin
+/// identidad conocida no se envian.
+pub struct SignedSender {
+    sender: Box<dyn UdpSocketSender + Send>,
+    identity: SocketAddr,
+    keys: Keys,
+    peers: Peers,
+    next_nonce: u64,
+}
+
+impl UdpSocketSender for SignedSender {
+    fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<(), SocketError> {
+        let key = self
+            .peers
+            .identity(&self.keys, *addr)
+            .and_then(|peer| self.keys.link_key(self.identity, peer, SIGN_CONTEXT))
+            .ok_or(SocketError::UnknownPeer)?;
+        let nonce = self.next_nonce;
+        self.next_nonce = self.next_nonce.wrapping_add(1);
+        let mut signed = encode_identity(self.identity);
+        signed.extend_from_slice(&now_millis().to_be_bytes());
+        signed.extend_from_slice(&nonce.to_be_bytes());
+        signed.extend_from_slice(buf);
+        let tag = mac(&key, &signed).finalize().into_bytes();
+        signed.extend_from_slice(&tag);
+        self.sender.send_to(&signed, addr)
+    }
+}
+
+/// Receptor que verifica la firma de cada mensaje con la clave del
+/// enlace con la identidad que declara, y la quita antes de
+/// entregarlo. Se descartan los mensajes sin firma, con firma
+/// invalida o repetidos segun su `ReplayWindow`.
+pub struct SignedReceiver {
+    receiver: Box<dyn UdpSocketReceiver + Send>,
+    identity: SocketAddr,
+    keys: Keys,
+    peers: Peers,
+    replay_window: ReplayWindow,
+}
+
+impl SignedReceiver {
+    /// Verifica `message` y devuelve la identidad del emisor y el
+    /// contenido sin encabezado ni firma
+    fn open(&mut self, message: &[u8]) -> Result<(SocketAddr, Vec<u8>), SocketError> {
+        let (peer, rest) = split_identity(message).ok_or(SocketError::BadSignature)?;
+        if rest.len() < FRESHNESS_SIZE + TAG_SIZE {
+            return Err(SocketError::BadSignature);
+        }
+        let key = self
+            .keys
+            .link_key(self.identity, peer, SIGN_CONTEXT)
+            .ok_or(SocketError::BadSignature)?;
+        let (signed, tag) = message.split_at(message.len() - TAG_SIZE);
+        if mac(&key, signed).verify_slice(tag).is_err() {
+            return Err(SocketError::BadSignature);
+        }
+        let (freshness, payload) = rest[..rest.len() - TAG_SIZE].split_at(FRESHNESS_SIZE);
+        let (timestamp, nonce) = freshness.split_at(size_of::<u64>());
+        let timestamp = u64::from_be_bytes(
+            timestamp
+                .try_into()
+                .expect("[Signed Socket] La marca de tiempo deberia ocupar 8 bytes"),
+        );
+        let nonce = u64::from_be_bytes(
+            nonce
+                .try_into()
+                .expect("[Signed Socket] El nonce deberia ocupar 8 bytes"),
+        );
+        if !self
+            .replay_window
+            .accept(peer, timestamp, nonce, now_millis())
+        {
+            return Err(SocketError::Replayed);
+        }
+        Ok((peer, payload.to_vec()))
+    }
+}
+
+impl UdpSocketReceiver for SignedReceiver {
+    fn recv(&mut self, n: usize) -> Result<(Vec<u8>, SocketAddr), SocketError> {
+        loop {
+            let (message, addr) = self
+                .receiver
+                .recv(MAX_IDENTITY_SIZE + FRESHNESS_SIZE + n + TAG_SIZE)?;
+            match self.open(&message) {
+                Ok((peer, payload)) => {
+                    self.peers.learn(addr, peer);
+                    return Ok((payload, addr));
+                }
+                Err(err) => warn!(peer = %addr, error = %err, "Mensaje descartado"),
+            }
+        }
+    }
+
+    fn set_timeout(&mut self, opt_timeout: Option<Duration>) {
+        self.receiver.set_timeout(opt_timeout);
+    }
+}
+
+/// Firma los mensajes de emisor y receptor si hay claves
+/// configuradas, o los devuelve sin cambios en caso contrario.
+/// `identity` es la direccion de la tabla con la que firma el
+/// socket, aunque envie desde otro puerto. `replay_window` es la
+/// antiguedad maxima de un mensaje aceptado.
+#[must_use]
+pub fn maybe_signed(
+    sender: Box<dyn UdpSocketSender + Send>,
+    receiver: Box<dyn UdpSocketReceiver + Send>,
+    identity: SocketAddr,
+    keys: &Keys,
+    replay_window: Duration,
+) -> (
+    Box<dyn UdpSocketSender + Send>,
+    Box<dyn UdpSocketReceiver + Send>,
+) {
+    if keys.is_empty() {
+        return (sender, receiver);
+    }
+    let peers = Peers::default();
+    (
+        Box::new(SignedSender {
+            sender,
+            identity,
+            keys: keys.clone(),
+            peers: peers.clone(),
+            next_nonce: rand::random(),
+        }),
+        Box::new(SignedReceiver {
+            receiver,
+            identity,
+            keys: keys.clone(),
+            peers,
+            replay_window: ReplayWindow::new(replay_window),
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sockets::udp_socket_wrap::UdpSocketWrap;
+    use ntest::timeout;
+    use std::net::UdpSocket;
+
+    fn keys(addrs: &[SocketAddr]) -> Keys {
+        Keys::new(
+            addrs
+                .iter()
+                .enumerate()
+                .map(|(i, addr)| (*addr, vec![i as u8 + 1; 16]))
+                .collect(),
+        )
+    }
+
+    fn signed_pair(
+        socket: UdpSocketWrap,
+        identity: SocketAddr,
+        keys: &Keys,
+    ) -> (SignedSender, SignedReceiver) {
+        let clone = socket.try_clone().unwrap();
+        let peers = Peers::default();
+        (
+            SignedSender {
+                sender: Box::new(socket),
+                identity,
+                keys: keys.clone(),
+                peers: peers.clone(),
+                next_nonce: 0,
+            },
+            SignedReceiver {
+                receiver: Box::new(clone),
+                identity,
+                keys: keys.clone(),
+                peers,
+                replay_window: ReplayWindow::new(Duration::from_secs(30)),
+            },
+        )
+    }
+
+    fn signed(addr: SocketAddr, keys: &Keys) -> (SignedSender, SignedReceiver) {
+        let socket = UdpSocketWrap::new_with_addr(Some(Duration::from_millis(300)), addr).unwrap();
+        signed_pair(socket, addr, keys)
+    }
+
+    #[test]
+    fn link_key_should_be_the_same_from_both_ends_and_need_both_keys() {
+        let a: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let b: SocketAddr = "127.0.0.1:49153".parse().unwrap();
+        let c: SocketAddr = "127.0.0.1:49154".parse().unwrap();
+        let keys = keys(&[a, b]);
+
+        assert_eq!(
+            keys.link_key(a, b, SIGN_CONTEXT),
+            keys.link_key(b, a, SIGN_CONTEXT)
+        );
+        assert_ne!(
+            keys.link_key(a, b, SIGN_CONTEXT),
+            keys.link_key(a, b, b"otro contexto")
+        );
+        assert_eq!(keys.link_key(a, c, SIGN_CONTEXT), None);
+        assert_eq!(keys.link_key(c, a, SIGN_CONTEXT), None);
+    }
+
+    #[test]
+    fn link_key_should_frame_each_address_and_key() {
+        let a: SocketAddr = "127.0.0.1:49152".parse().unwrap();
+        let b: SocketAddr = "127.0.0.1:49153".parse().unwrap();
+        let split_first = Keys::new(HashMap::from([(a, vec![1, 2]), (b, vec![3])]));
+        let split_second = Keys::new(HashMap::from([(a, vec![1]), (b, vec![2, 3])]));
+
+        assert_ne!(
+            split_first.link_key(a, b, SIGN_CONTEXT),
+            split_second.link_key(a, b, SIGN_CONTEXT)
+        );
+    }
+
+    #[test]
+    fn split_identity_should_return_the_encoded_identity() {
+        for identity in ["127.0.0.1:49152", "[::1]:9000"] {
+            let identity: SocketAddr = identity.parse().unwrap();
+            let mut message = encode_identity(identity);
+            message.extend_from_slice(b"hola");
+
+            assert_eq!(split_identity(&message), Some((identity, &b"hola"[..])));
+        }
+        assert_eq!(split_identity(&[4, 127, 0]), None);
+        assert_eq!(split_identity(&[5, 127, 0, 0, 1, 0, 80]), None);
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_deliver_signed_messages_without_the_tag() {
+        let addr_a: SocketAddr = "127.0.0.1:59191".parse().unwrap();
+        let addr_b: SocketAddr = "127.0.0.1:59192".parse().unwrap();
+        let keys = keys(&[addr_a, addr_b]);
+        let (mut sender_a, _receiver_a) = signed(addr_a, &keys);
+        let (_sender_b, mut receiver_b) = signed(addr_b, &keys);
+
+        sender_a.send_to(b"hola", &addr_b).unwrap();
+
+        assert_eq!(receiver_b.recv(4), Ok((b"hola".to_vec(), addr_a)));
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_discard_unsigned_and_forged_messages() {
+        let addr_raw: SocketAddr = "127.0.0.1:59193".parse().unwrap();
+        let addr_b: SocketAddr = "127.0.0.1:59194".parse().unwrap();
+        let keys = keys(&[addr_raw, addr_b]);
+        let (_sender_b, mut receiver_b) = signed(addr_b, &keys);
+        let raw = UdpSocket::bind(addr_raw).unwrap();
+
+        raw.send_to(b"hola", addr_b).unwrap();
+        let mut forged = encode_identity(addr_raw);
+        forged.extend_from_slice(&[0; FRESHNESS_SIZE]);
+        forged.extend_from_slice(b"hola");
+        forged.extend_from_slice(&[0; TAG_SIZE]);
+        raw.send_to(&forged, addr_b).unwrap();
+
+        assert_eq!(receiver_b.recv(4), Err(SocketError::Timeout));
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_discard_a_replayed_message() {
+        let addr_a: SocketAddr = "127.0.0.1:59195".parse().unwrap();
+        let addr_b: SocketAddr = "127.0.0.1:59196".parse().unwrap();
+        let keys = keys(&[addr_a, addr_b]);
+        let (_sender_b, mut receiver_b) = signed(addr_b, &keys);
+        let raw = UdpSocket::bind(addr_a).unwrap();
+        let mut captured = encode_identity(addr_a);
+        captured.extend_from_slice(&now_millis().to_be_bytes());
+        captured.extend_from_slice(&7u64.to_be_bytes());
+        captured.extend_from_slice(b"hola");
+        let key = keys.link_key(addr_a, addr_b, SIGN_CONTEXT).unwrap();
+        let tag = mac(&key, &captured).finalize().into_bytes();
+        captured.extend_from_slice(&tag);
+
+        raw.send_to(&captured, addr_b).unwrap();
+        raw.send_to(&captured, addr_b).unwrap();
+
+        assert_eq!(receiver_b.recv(4), Ok((b"hola".to_vec(), addr_a)));
+        assert_eq!(receiver_b.recv(4), Err(SocketError::Timeout));
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_reply_to_a_peer_that_signs_from_an_ephemeral_port() {
+        let identity: SocketAddr = "127.0.0.1:59360".parse().unwrap();
+        let addr_b: SocketAddr = "127.0.0.1:59215".parse().unwrap();
+        let keys = keys(&[identity, addr_b]);
+        let ephemeral = UdpSocketWrap::new(Some(Duration::from_millis(300)));
+        let (mut sender_a, mut receiver_a) = signed_pair(ephemeral, identity, &keys);
+        let (mut sender_b, mut receiver_b) = signed(addr_b, &keys);
+        let unknown: SocketAddr = "127.0.0.1:9".parse().unwrap();
+
+        assert_eq!(
+            sender_b.send_to(b"hola", &unknown),
+            Err(SocketError::UnknownPeer)
+        );
+        sender_a.send_to(b"hola", &addr_b).unwrap();
+        let (message, addr_a) = receiver_b.recv(4).unwrap();
+        assert_eq!(message, b"hola".to_vec());
+        assert_ne!(addr_a, identity);
+        sender_b.send_to(b"chau", &addr_a).unwrap();
+
+        assert_eq!(receiver_a.recv(4), Ok((b"chau".to_vec(), addr_b)));
+    }
+}
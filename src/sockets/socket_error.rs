@@ -5,6 +5,13 @@ pub enum SocketError {
     CloneFailed,
     Timeout,
     ZeroBytes,
+    Truncated,
+    Malformed,
+    BadSignature,
+    Replayed,
+    DecryptFailed,
+    UnknownPeer,
+    ConnectFailed,
 }
 
 impl fmt::Display for SocketError {
@@ -13,6 +20,13 @@ impl fmt::Display for SocketError {
             SocketError::CloneFailed => write!(f, "Clonar un socket no deberia fallar"),
             SocketError::Timeout => write!(f, "Timeout"),
             SocketError::ZeroBytes => write!(f, "Cero bytes transmitidos"),
+            SocketError::Truncated => write!(f, "El mensaje no entra en el buffer de recepcion"),
+            SocketError::Malformed => write!(f, "Fragmento mal formado"),
+            SocketError::BadSignature => write!(f, "Firma ausente o invalida"),
+            SocketError::Replayed => write!(f, "Mensaje repetido o fuera de la ventana de tiempo"),
+            SocketError::DecryptFailed => write!(f, "No se pudo descifrar el mensaje"),
+            SocketError::UnknownPeer => write!(f, "No hay clave para el enlace con el par"),
+            SocketError::ConnectFailed => write!(f, "No se pudo conectar con el par"),
         }
     }
 }
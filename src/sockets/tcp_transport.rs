@@ -0,0 +1,372 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    io::{Read, Write},
+    mem::size_of,
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use tracing::warn;
+
+use super::{
+    channel_socket::{ChannelSocketReceiver, Datagram},
+    fragment::MAX_MESSAGE_SIZE,
+    socket_error::SocketError,
+    udp_socket_sender::UdpSocketSender,
+};
+
+const LEN_SIZE: usize = size_of::<u32>();
+// Un par caido no debe bloquear indefinidamente al emisor
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(1000);
+const WRITE_TIMEOUT: Duration = Duration::from_millis(1000);
+// Conexiones aceptadas abiertas a la vez. Las que exceden se cierran.
+const MAX_CONNECTIONS: usize = 64;
+// Una conexion aceptada sin mensajes durante este tiempo se cierra
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+type Pool = Arc<Mutex<HashMap<SocketAddr, TcpStream>>>;
+
+/// Transporte TCP con la misma interfaz que los sockets UDP. Cada
+/// mensaje viaja precedido por su largo (u32 big endian). Se mantiene
+/// una conexion por par, tanto las iniciadas como las aceptadas, y
+/// los mensajes recibidos por cualquiera de ellas se entregan por el
+/// `ChannelSocketReceiver` asociado. Las conexiones que anuncian un
+/// mensaje de mas de `MAX_MESSAGE_SIZE` bytes se cierran, y las
+/// aceptadas tambien si pasan `IDLE_TIMEOUT` sin recibir datos o si
+/// ya hay `MAX_CONNECTIONS` abiertas.
+#[derive(Clone)]
+pub struct TcpTransport {
+    pool: Pool,
+    incoming: mpsc::Sender<Datagram>,
+}
+
+impl TcpTransport {
+    /// Transporte que solo inicia conexiones
+    #[must_use]
+    pub fn connect_only() -> (TcpTransport, ChannelSocketReceiver) {
+        let (incoming, receiver) = mpsc::channel();
+        let transport = TcpTransport {
+            pool: Arc::new(Mutex::new(HashMap::new())),
+            incoming,
+        };
+        (transport, ChannelSocketReceiver::new(receiver))
+    }
+
+    /// Transporte que ademas acepta conexiones en `addr`
+    ///
+    /// # Errors
+    ///
+    /// Devuelve error si no fue posible escuchar en esa addr
+    pub fn listen(addr: SocketAddr) -> Result<(TcpTransport, ChannelSocketReceiver), String> {
+        TcpTransport::listen_with(addr, MAX_CONNECTIONS, IDLE_TIMEOUT)
+    }
+
+    fn listen_with(
+        addr: SocketAddr,
+        max_connections: usize,
+        idle_timeout: Duration,
+    ) -> Result<(TcpTransport, ChannelSocketReceiver), String> {
+        let listener = TcpListener::bind(addr)
+            .map_err(|_| format!("[Tcp Transport] Bind a {} ha fallado", addr))?;
+        let (transport, receiver) = TcpTransport::connect_only();
+        let acceptor = transport.clone();
+        let open = Arc::new(AtomicUsize::new(0));
+        thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                let peer = match stream.peer_addr() {
+                    Ok(peer) => peer,
+                    Err(_) => continue,
+                };
+                if open.load(Ordering::SeqCst) >= max_connections {
+                    warn!(peer = %peer, "Demasiadas conexiones abiertas, se rechaza");
+                    continue;
+                }
+                if stream.set_read_timeout(Some(idle_timeout)).is_err() {
+                    continue;
+                }
+                open.fetch_add(1, Ordering::SeqCst);
+                let open = open.clone();
+                acceptor.register(peer, stream, move || {
+                    open.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        Ok((transport, receiver))
+    }
+
+    /// Agrega la conexion con `peer` al pool y lee sus mensajes en un
+    /// hilo propio. `on_close` se llama al cerrarse la conexion.
+    fn register(
+        &self,
+        peer: SocketAddr,
+        stream: TcpStream,
+        on_close: impl FnOnce() + Send + 'static,
+    ) {
+        let reader = stream
+            .set_write_timeout(Some(WRITE_TIMEOUT))
+            .and_then(|()| stream.try_clone());
+        let reader = match reader {
+            Ok(reader) => reader,
+            Err(_) => return on_close(),
+        };
+        self.pool
+            .lock()
+            .expect("[Tcp Transport] Lock del pool envenenado")
+            .insert(peer, stream);
+        let pool = self.pool.clone();
+        let incoming = self.incoming.clone();
+        thread::spawn(move || {
+            read_frames(reader, peer, &pool, &incoming);
+            on_close();
+        });
+    }
+
+    fn connection(&self, addr: &SocketAddr) -> Result<TcpStream, SocketError> {
+        if let Some(stream) = self
+            .pool
+            .lock()
+            .expect("[Tcp Transport] Lock del pool envenenado")
+            .get(addr)
+        {
+            return stream.try_clone().map_err(|_| SocketError::CloneFailed);
+        }
+        let stream = TcpStream::connect_timeout(addr, CONNECT_TIMEOUT)
+            .map_err(|_| SocketError::ConnectFailed)?;
+        let writer = stream.try_clone().map_err(|_| SocketError::CloneFailed)?;
+        self.register(*addr, stream, || {});
+        Ok(writer)
+    }
+
+    fn drop_connection(&self, addr: &SocketAddr) {
+        self.pool
+            .lock()
+            .expect("[Tcp Transport] Lock del pool envenenado")
+            .remove(addr);
+    }
+}
+
+fn read_frames(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    pool: &Pool,
+    incoming: &mpsc::Sender<Datagram>,
+) {
+    loop {
+        let mut len_bytes = [0; LEN_SIZE];
+        if stream.read_exact(&mut len_bytes).is_err() {
+            break;
+        }
+        let len = u32::from_be_bytes(len_bytes) as usize;
+        if len > MAX_MESSAGE_SIZE {
+            warn!(peer = %peer, len, "Mensaje demasiado largo, se cierra la conexion");
+            let _drop = stream.shutdown(Shutdown::Both);
+            break;
+        }
+        let mut message = vec![0; len];
+        if stream.read_exact(&mut message).is_err() || incoming.send((message, peer)).is_err() {
+            break;
+        }
+    }
+    pool.lock()
+        .expect("[Tcp Transport] Lock del pool envenenado")
+        .remove(&peer);
+}
+
+impl UdpSocketSender for TcpTransport {
+    fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<(), SocketError> {
+        if buf.len() > MAX_MESSAGE_SIZE {
+            return Err(SocketError::Truncated);
+        }
+        let len: u32 = buf.len().try_into().map_err(|_| SocketError::Truncated)?;
+        let mut frame = len.to_be_bytes().to_vec();
+        frame.extend_from_slice(buf);
+        // Si la conexion del pool se cerro se reintenta con una nueva
+        for _ in 0..2 {
+            let mut stream = self.connection(addr)?;
+            if stream.write_all(&frame).is_ok() {
+                return Ok(());
+            }
+            self.drop_connection(addr);
+        }
+        Err(SocketError::ZeroBytes)
+    }
+}
+
+/// Emisor que envia por TCP a las direcciones de `tcp_addrs` y por
+/// el emisor UDP al resto
+pub struct RoutedSender {
+    udp: Box<dyn UdpSocketSender + Send>,
+    tcp: Box<dyn UdpSocketSender + Send>,
+    tcp_addrs: HashSet<SocketAddr>,
+}
+
+impl UdpSocketSender for RoutedSender {
+    fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<(), SocketError> {
+        if self.tcp_addrs.contains(addr) {
+            self.tcp.send_to(buf, addr)
+        } else {
+            self.udp.send_to(buf, addr)
+        }
+    }
+}
+
+/// Agrega transporte TCP hacia `tcp_addrs` a un emisor UDP. Devuelve
+/// el emisor que elige el transporte segun el destino y, si hay
+/// direcciones TCP, el receptor de los mensajes que llegan por esas
+/// conexiones, que el usuario debe recibir junto con los del socket
+/// UDP. Las conexiones TCP salen de puertos efimeros; la seguridad del
+/// enlace se aplica por encima de ambos transportes.
+#[must_use]
+pub fn route_tcp(
+    udp_sender: Box<dyn UdpSocketSender + Send>,
+    tcp_addrs: HashSet<SocketAddr>,
+) -> (
+    Box<dyn UdpSocketSender + Send>,
+    Option<ChannelSocketReceiver>,
+) {
+    if tcp_addrs.is_empty() {
+        return (udp_sender, None);
+    }
+    let (tcp, tcp_receiver) = TcpTransport::connect_only();
+    let sender = RoutedSender {
+        udp: udp_sender,
+        tcp: Box::new(tcp),
+        tcp_addrs,
+    };
+    (Box::new(sender), Some(tcp_receiver))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sockets::{
+        constants::UDP_PACKET_SIZE, udp_socket_receiver::UdpSocketReceiver,
+        udp_socket_sender::MockUdpSocketSender,
+    };
+    use ntest::timeout;
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_deliver_framed_messages_and_answer_through_the_same_connection() {
+        let server_addr: SocketAddr = "127.0.0.1:59181".parse().unwrap();
+        let (mut server, mut server_receiver) = TcpTransport::listen(server_addr).unwrap();
+        let (mut client, mut client_receiver) = TcpTransport::connect_only();
+
+        client.send_to(b"hola", &server_addr).unwrap();
+        client.send_to(b"chau", &server_addr).unwrap();
+
+        let (first, peer) = server_receiver.recv(UDP_PACKET_SIZE).unwrap();
+        let (second, same_peer) = server_receiver.recv(UDP_PACKET_SIZE).unwrap();
+        assert_eq!(first, b"hola".to_vec());
+        assert_eq!(second, b"chau".to_vec());
+        // Ambos mensajes usaron la misma conexion del pool
+        assert_eq!(peer, same_peer);
+
+        server.send_to(b"ok", &peer).unwrap();
+        assert_eq!(
+            client_receiver.recv(UDP_PACKET_SIZE),
+            Ok((b"ok".to_vec(), server_addr))
+        );
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_close_connections_that_announce_oversized_messages() {
+        let server_addr: SocketAddr = "127.0.0.1:59213".parse().unwrap();
+        let (_server, mut server_receiver) = TcpTransport::listen(server_addr).unwrap();
+
+        let mut stream = TcpStream::connect(server_addr).unwrap();
+        stream.write_all(&u32::MAX.to_be_bytes()).unwrap();
+        // El servidor cierra la conexion sin esperar el mensaje
+        let mut buf = [0; 1];
+        assert!(matches!(stream.read(&mut buf), Ok(0) | Err(_)));
+
+        let (mut client, _client_receiver) = TcpTransport::connect_only();
+        client.send_to(b"hola", &server_addr).unwrap();
+        assert_eq!(
+            server_receiver.recv(UDP_PACKET_SIZE).unwrap().0,
+            b"hola".to_vec()
+        );
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_limit_the_accepted_connections_and_close_idle_ones() {
+        let server_addr: SocketAddr = "127.0.0.1:59218".parse().unwrap();
+        let (_server, mut server_receiver) =
+            TcpTransport::listen_with(server_addr, 1, Duration::from_millis(300)).unwrap();
+        let frame = |message: &[u8]| {
+            let mut frame = (message.len() as u32).to_be_bytes().to_vec();
+            frame.extend_from_slice(message);
+            frame
+        };
+
+        let mut first = TcpStream::connect(server_addr).unwrap();
+        first.write_all(&frame(b"uno")).unwrap();
+        assert_eq!(
+            server_receiver.recv(UDP_PACKET_SIZE).unwrap().0,
+            b"uno".to_vec()
+        );
+        // No hay lugar para una segunda conexion
+        let mut second = TcpStream::connect(server_addr).unwrap();
+        let mut buf = [0; 1];
+        assert!(matches!(second.read(&mut buf), Ok(0) | Err(_)));
+        // La primera se cierra por inactividad y libera su lugar
+        assert!(matches!(first.read(&mut buf), Ok(0) | Err(_)));
+        thread::sleep(Duration::from_millis(50));
+
+        let mut third = TcpStream::connect(server_addr).unwrap();
+        third.write_all(&frame(b"tres")).unwrap();
+        assert_eq!(
+            server_receiver.recv(UDP_PACKET_SIZE).unwrap().0,
+            b"tres".to_vec()
+        );
+    }
+
+    #[test]
+    fn it_should_report_a_failed_connection() {
+        // Nadie escucha en el puerto 9 de la maquina local
+        let addr: SocketAddr = "127.0.0.1:9".parse().unwrap();
+        let (mut client, _client_receiver) = TcpTransport::connect_only();
+
+        assert_eq!(
+            client.send_to(b"hola", &addr),
+            Err(SocketError::ConnectFailed)
+        );
+    }
+
+    #[test]
+    #[timeout(3000)]
+    fn routed_sender_should_send_by_udp_to_addresses_not_in_tcp_addrs() {
+        let tcp_addr: SocketAddr = "127.0.0.1:59182".parse().unwrap();
+        let udp_addr: SocketAddr = "127.0.0.1:59183".parse().unwrap();
+        let (_server, mut server_receiver) = TcpTransport::listen(tcp_addr).unwrap();
+
+        let mut mock_udp = MockUdpSocketSender::new();
+        mock_udp
+            .expect_send_to()
+            .withf(move |buf, addr| buf == b"udp" && *addr == udp_addr)
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let (tcp, _receiver) = TcpTransport::connect_only();
+        let mut sender = RoutedSender {
+            udp: Box::new(mock_udp),
+            tcp: Box::new(tcp),
+            tcp_addrs: HashSet::from([tcp_addr]),
+        };
+
+        sender.send_to(b"udp", &udp_addr).unwrap();
+        sender.send_to(b"tcp", &tcp_addr).unwrap();
+
+        assert_eq!(
+            server_receiver.recv(UDP_PACKET_SIZE).unwrap().0,
+            b"tcp".to_vec()
+        );
+    }
+}
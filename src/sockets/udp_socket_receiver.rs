@@ -1,9 +1,12 @@
 use super::socket_error::SocketError;
+use std::net::SocketAddr;
 use std::time::Duration;
 
 #[cfg(test)]
 use mockall::automock;
 
+/// Receptor de mensajes. Pese al nombre no depende del transporte:
+/// lo implementan tanto `UdpSocketWrap` como los receptores por canal.
 #[cfg_attr(test, automock)]
 pub trait UdpSocketReceiver {
     /// # Errors
@@ -11,7 +14,7 @@ pub trait UdpSocketReceiver {
     /// `SocketError::ZeroBytes` => Aún quedan bytes por recibir,
     /// pero el socket interno no recibió ningun byte en el último intento
     /// `SocketError::Timeout` => Paso demasiado tiempo sin recibir ningún byte.
-    fn recv(&mut self, n: usize) -> Result<(Vec<u8>, String), SocketError>;
+    fn recv(&mut self, n: usize) -> Result<(Vec<u8>, SocketAddr), SocketError>;
 
     fn set_timeout(&mut self, opt_timeout: Option<Duration>);
 }
@@ -1,13 +1,18 @@
 use super::socket_error::SocketError;
+use std::net::SocketAddr;
 
 #[cfg(test)]
 use mockall::automock;
 
+/// Emisor de mensajes. Pese al nombre no depende del transporte:
+/// lo implementan tanto `UdpSocketWrap` como `TcpTransport`.
 #[cfg_attr(test, automock)]
 pub trait UdpSocketSender {
     /// # Errors
     ///
     /// `SocketError::ZeroBytes` => Aún quedan bytes por enviar,
     /// pero el socket interno no envió ningun byte en el último intento
-    fn send_to(&mut self, buf: &[u8], addr: &str) -> Result<(), SocketError>;
+    /// `SocketError::ConnectFailed` => No se pudo abrir la conexion
+    /// con `addr` (transportes orientados a conexion)
+    fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<(), SocketError>;
 }
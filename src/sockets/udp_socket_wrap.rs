@@ -1,13 +1,28 @@
 use crate::sockets::constants::UDP_PACKET_SIZE;
-use std::{net::UdpSocket, time::Duration};
+use std::{
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 use super::{
-    socket_error::SocketError, udp_socket_receiver::UdpSocketReceiver,
+    fragment::{fragment, Reassembler},
+    socket_error::SocketError,
+    udp_socket_receiver::UdpSocketReceiver,
     udp_socket_sender::UdpSocketSender,
 };
 
+/// Socket UDP que preserva los limites de cada mensaje: un envio es
+/// un datagrama y una recepcion devuelve un unico mensaje. Los mensajes
+/// de mas de `UDP_PACKET_SIZE` bytes se fragmentan y se rearman al
+/// recibirlos. Los clones comparten los ids de mensaje y el rearmador.
 pub struct UdpSocketWrap {
     socket: UdpSocket,
+    next_msg_id: Arc<AtomicU32>,
+    reassembler: Arc<Mutex<Reassembler>>,
 }
 
 impl UdpSocketWrap {
@@ -17,7 +32,15 @@ impl UdpSocketWrap {
         socket
             .set_read_timeout(opt_timeout)
             .expect("[UdpSocketWrap] Set timeout ha fallado");
-        UdpSocketWrap { socket }
+        UdpSocketWrap::from_socket(socket)
+    }
+
+    fn from_socket(socket: UdpSocket) -> Self {
+        UdpSocketWrap {
+            socket,
+            next_msg_id: Arc::new(AtomicU32::new(rand::random())),
+            reassembler: Arc::new(Mutex::new(Reassembler::new())),
+        }
     }
 
     /// # Errors
@@ -25,7 +48,7 @@ impl UdpSocketWrap {
     /// Devuelve error si no fue posible inicializar un socket con esa addr
     pub fn new_with_addr(
         opt_timeout: Option<Duration>,
-        addr: String,
+        addr: SocketAddr,
     ) -> Result<UdpSocketWrap, String> {
         let socket_result = UdpSocket::bind(addr);
         if let Ok(socket_result) = socket_result {
@@ -33,10 +56,9 @@ impl UdpSocketWrap {
             socket
                 .set_read_timeout(opt_timeout)
                 .expect("[UdpSocketWrap] Set timeout ha fallado");
-            let udp_socket_wrap = UdpSocketWrap { socket };
-            Ok(udp_socket_wrap)
+            Ok(UdpSocketWrap::from_socket(socket))
         } else {
-            Err("Todo mal".to_string())
+            Err(format!("[UdpSocketWrap] Bind a {} ha fallado", addr))
         }
     }
     /// # Errors
@@ -49,54 +71,54 @@ impl UdpSocketWrap {
         };
         Ok(UdpSocketWrap {
             socket: socket_clone,
+            next_msg_id: self.next_msg_id.clone(),
+            reassembler: self.reassembler.clone(),
         })
     }
 }
 
 impl UdpSocketSender for UdpSocketWrap {
-    fn send_to(&mut self, bytes_vec: &[u8], addr: &str) -> Result<(), SocketError> {
-        let mut buf = [0; UDP_PACKET_SIZE];
-        for (i, x) in bytes_vec.iter().enumerate() {
-            if i >= buf.len() {
-                break;
-            }
-            buf[i] = *x;
-        }
-
-        let mut total_bytes_sent = 0;
-        while total_bytes_sent < bytes_vec.len() {
+    fn send_to(&mut self, bytes_vec: &[u8], addr: &SocketAddr) -> Result<(), SocketError> {
+        let msg_id = self.next_msg_id.fetch_add(1, Ordering::SeqCst);
+        for datagram in fragment(bytes_vec, msg_id)? {
             let bytes_sent = self
                 .socket
-                .send_to(&buf[total_bytes_sent..], addr)
-                .expect("[UdpSocketWrap] Version de direccion IP incorrecta");
-            if bytes_sent == 0 {
+                .send_to(&datagram, addr)
+                .map_err(|_| SocketError::ZeroBytes)?;
+            if bytes_sent != datagram.len() {
                 return Err(SocketError::ZeroBytes);
             }
-            total_bytes_sent += bytes_sent;
         }
         Ok(())
     }
 }
 impl UdpSocketReceiver for UdpSocketWrap {
-    fn recv(&mut self, n_bytes: usize) -> Result<(Vec<u8>, String), SocketError> {
-        let mut buf = [0; UDP_PACKET_SIZE];
-        let mut from_addr = "".to_string();
-        let mut total_bytes_recv = 0;
-        while total_bytes_recv < n_bytes {
-            let res = self.socket.recv_from(&mut buf[total_bytes_recv..]);
-            let (bytes_recv, addr) = match res {
+    fn recv(&mut self, n_bytes: usize) -> Result<(Vec<u8>, SocketAddr), SocketError> {
+        // Un byte extra permite detectar datagramas mas largos que el maximo
+        let mut buf = [0; UDP_PACKET_SIZE + 1];
+        loop {
+            let (bytes_recv, addr) = match self.socket.recv_from(&mut buf) {
                 Ok(value) => value,
                 Err(_) => return Err(SocketError::Timeout),
             };
-
             if bytes_recv == 0 {
                 return Err(SocketError::ZeroBytes);
             }
-            total_bytes_recv += bytes_recv;
-            from_addr = addr.to_string();
+            if bytes_recv > UDP_PACKET_SIZE {
+                return Err(SocketError::Truncated);
+            }
+            let complete = self
+                .reassembler
+                .lock()
+                .expect("[UdpSocketWrap] Lock del rearmador envenenado")
+                .push(&buf[..bytes_recv], addr)?;
+            if let Some(message) = complete {
+                if message.len() > n_bytes {
+                    return Err(SocketError::Truncated);
+                }
+                return Ok((message, addr));
+            }
         }
-        let res = buf[..n_bytes].to_vec();
-        Ok((res, from_addr))
     }
 
     fn set_timeout(&mut self, opt_timeout: Option<Duration>) {
@@ -115,7 +137,7 @@ mod tests {
 
     #[test]
     fn it_should_send_a_message() {
-        let addr = "127.0.0.1:59153";
+        let addr: SocketAddr = "127.0.0.1:59153".parse().unwrap();
         let socket = UdpSocket::bind(addr).unwrap();
         let mut client = UdpSocketWrap::new(None);
 
@@ -124,7 +146,7 @@ mod tests {
         assert_eq!(MSG_LEN, message.len());
         let mut buf = [0; MSG_LEN];
 
-        let res = client.send_to(&message, addr);
+        let res = client.send_to(&message, &addr);
         assert!(res.is_ok());
 
         let (_, _) = socket.recv_from(&mut buf).unwrap();
@@ -133,18 +155,18 @@ mod tests {
 
     #[test]
     fn it_should_recv_a_message() {
-        let addr = "127.0.0.1:59154"; // Test en paralelo => Usar un puerto distinto
+        let addr: SocketAddr = "127.0.0.1:59154".parse().unwrap(); // Test en paralelo => Usar un puerto distinto
         let socket = UdpSocket::bind(addr).unwrap();
         let mut client = UdpSocketWrap::new(None);
 
         let message = "a message".as_bytes().to_vec();
         let mut buf = [0; UDP_PACKET_SIZE];
 
-        client.send_to(&message, addr).unwrap();
+        client.send_to(&message, &addr).unwrap();
         let (_, client_addr) = socket.recv_from(&mut buf).unwrap();
         socket.send_to(&buf[..message.len()], client_addr).unwrap();
 
-        let res = client.recv(message.len());
+        let res = client.recv(UDP_PACKET_SIZE);
 
         assert!(res.is_ok());
         let res_vec = res.unwrap().0;
@@ -158,7 +180,7 @@ mod tests {
         let mut client = UdpSocketWrap::new(some_timeout);
         let res = client.recv(1);
         match res {
-            Ok(_) => assert!(false),
+            Ok(_) => unreachable!(),
             Err(err) => assert_eq!(err, SocketError::Timeout),
         };
     }
@@ -166,7 +188,7 @@ mod tests {
     #[test]
     #[timeout(5000)]
     fn it_should_be_send_from_the_original_and_receive_from_the_cloned() {
-        let addr = "127.0.0.1:59155"; // Test en paralelo => Usar un puerto distinto
+        let addr: SocketAddr = "127.0.0.1:59155".parse().unwrap(); // Test en paralelo => Usar un puerto distinto
         let socket = UdpSocket::bind(addr).unwrap();
         let original = UdpSocketWrap::new(None);
         let clone = original.try_clone().unwrap();
@@ -177,7 +199,7 @@ mod tests {
         let message = "a message".as_bytes().to_vec();
         let mut buf = [0; UDP_PACKET_SIZE];
 
-        let send_res = original_box.send_to(&message, addr);
+        let send_res = original_box.send_to(&message, &addr);
         assert!(send_res.is_ok());
 
         let (_, sender_addr) = socket.recv_from(&mut buf).unwrap();
@@ -189,4 +211,57 @@ mod tests {
         let result_message = recv_res.unwrap().0;
         assert_eq!(result_message, message);
     }
+
+    #[test]
+    #[timeout(5000)]
+    fn it_should_send_exactly_the_message_without_padding() {
+        let addr: SocketAddr = "127.0.0.1:59156".parse().unwrap();
+        let socket = UdpSocket::bind(addr).unwrap();
+        let mut client = UdpSocketWrap::new(None);
+
+        client.send_to(b"hola", &addr).unwrap();
+
+        let mut buf = [0; UDP_PACKET_SIZE];
+        let (bytes_recv, _) = socket.recv_from(&mut buf).unwrap();
+        assert_eq!(bytes_recv, 4);
+    }
+
+    #[test]
+    #[timeout(5000)]
+    fn it_should_recv_one_datagram_at_a_time() {
+        let addr: SocketAddr = "127.0.0.1:59157".parse().unwrap();
+        let mut receiver = UdpSocketWrap::new_with_addr(None, addr).unwrap();
+        let mut sender = UdpSocketWrap::new(None);
+
+        sender.send_to(b"ab", &addr).unwrap();
+        sender.send_to(b"cd", &addr).unwrap();
+
+        assert_eq!(receiver.recv(4).unwrap().0, b"ab".to_vec());
+        assert_eq!(receiver.recv(4).unwrap().0, b"cd".to_vec());
+    }
+
+    #[test]
+    #[timeout(5000)]
+    fn it_should_return_truncated_if_the_message_is_longer_than_n_bytes() {
+        let addr: SocketAddr = "127.0.0.1:59158".parse().unwrap();
+        let mut receiver = UdpSocketWrap::new_with_addr(None, addr).unwrap();
+        let mut sender = UdpSocketWrap::new(None);
+
+        sender.send_to(b"a message", &addr).unwrap();
+
+        assert_eq!(receiver.recv(4), Err(SocketError::Truncated));
+    }
+
+    #[test]
+    #[timeout(5000)]
+    fn it_should_fragment_and_reassemble_large_messages() {
+        let addr: SocketAddr = "127.0.0.1:59159".parse().unwrap();
+        let mut receiver = UdpSocketWrap::new_with_addr(None, addr).unwrap();
+        let mut sender = UdpSocketWrap::new(None);
+        let message: Vec<u8> = (0..3000).map(|i| (i % 251) as u8).collect();
+
+        sender.send_to(&message, &addr).unwrap();
+
+        assert_eq!(receiver.recv(message.len()).unwrap().0, message);
+    }
 }
@@ -0,0 +1,2 @@
+pub mod submit_queue;
+pub mod submit_server;
@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use crate::alglobo::transaction::Transaction;
+
+/// Cantidad maxima de pedidos esperando a ser procesados
+pub const SUBMIT_QUEUE_CAPACITY: usize = 1024;
+
+/// Las reservas recibidas por SUBMIT se ejecutan con ids a partir de
+/// este valor, que el lider asigna en orden sin importar el id que
+/// envio el cliente, para no confundirlas con los ids del archivo de
+/// transacciones, con los reintentos ni entre si
+pub const SUBMIT_ID_BASE: u64 = 1 << 62;
+
+/// Canal por el que se avisa al cliente si su reserva se commiteo
+pub struct Reply(Sender<bool>);
+
+impl Reply {
+    /// Avisa el resultado al cliente, si todavia espera
+    pub fn send(self, commited: bool) {
+        let _drop = self.0.send(commited);
+    }
+}
+
+/// Pedido SUBMIT encolado, con el id que envio el cliente
+pub struct Submission {
+    pub transaction: Transaction,
+    pub reply: Reply,
+}
+
+#[derive(Default)]
+struct State {
+    open: bool,
+    pending: VecDeque<Submission>,
+}
+
+/// Cola de pedidos SUBMIT que procesa el `TransactionManager` del
+/// lider. Solo acepta pedidos mientras el nodo es lider y, al encolar
+/// uno, despierta al manager si se encuentra esperando reintentos.
+#[derive(Clone)]
+pub struct SubmitQueue {
+    state: Arc<Mutex<State>>,
+    ended: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl SubmitQueue {
+    #[must_use]
+    pub fn new(ended: Arc<(Mutex<bool>, Condvar)>) -> Self {
+        SubmitQueue {
+            state: Arc::new(Mutex::new(State::default())),
+            ended,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("[Submit Queue] Lock de la cola envenenado")
+    }
+
+    /// Empieza a aceptar pedidos, al asumir el liderazgo
+    pub fn open(&self) {
+        self.state().open = true;
+    }
+
+    /// Deja de aceptar pedidos y descarta los pendientes, cuyos
+    /// clientes reciben un error
+    pub fn close(&self) {
+        let mut state = self.state();
+        state.open = false;
+        state.pending.clear();
+    }
+
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        self.state().open
+    }
+
+    /// Cantidad de pedidos esperando a ser procesados
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.state().pending.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn pop(&self) -> Option<Submission> {
+        self.state().pending.pop_front()
+    }
+
+    /// Encola la transaccion y devuelve el canal por el que llegara
+    /// su resultado
+    /// # Errors:
+    /// Devuelve el motivo si el nodo no es lider o la cola esta llena
+    pub fn push(&self, transaction: Transaction) -> Result<Receiver<bool>, String> {
+        let (sender, receiver) = channel();
+        {
+            let mut state = self.state();
+            if !state.open {
+                return Err("el nodo no es el lider".to_string());
+            }
+            if state.pending.len() >= SUBMIT_QUEUE_CAPACITY {
+                return Err("cola de reservas llena".to_string());
+            }
+            state.pending.push_back(Submission {
+                transaction,
+                reply: Reply(sender),
+            });
+        }
+        let _ended = self
+            .ended
+            .0
+            .lock()
+            .expect("[Submit Queue] Lock de finalizacion envenenado");
+        self.ended.1.notify_all();
+        Ok(receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alglobo::transactionable::Transactionable;
+    use std::collections::HashMap;
+
+    fn transaction(id: u64) -> Transaction {
+        Transaction::new(id, &HashMap::new())
+    }
+
+    #[test]
+    fn it_should_only_queue_submissions_while_open() {
+        let queue = SubmitQueue::new(Arc::new((Mutex::new(true), Condvar::new())));
+        assert!(queue.push(transaction(0)).is_err());
+
+        queue.open();
+        let first = queue.push(transaction(1)).unwrap();
+        let second = queue.push(transaction(2)).unwrap();
+        assert_eq!(queue.len(), 2);
+
+        let submission = queue.pop().unwrap();
+        assert_eq!(submission.transaction.get_id(), 1);
+        submission.reply.send(true);
+        assert_eq!(first.recv(), Ok(true));
+
+        queue.close();
+        assert!(queue.is_empty());
+        assert!(second.recv().is_err());
+        assert!(queue.push(transaction(3)).is_err());
+    }
+}
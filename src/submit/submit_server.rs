@@ -0,0 +1,133 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread::{self, JoinHandle},
+};
+
+use tracing::{debug, info, warn, Span};
+
+use super::submit_queue::SubmitQueue;
+use crate::alglobo::transactionable::Transactionable;
+use crate::file_reader::json_lines_iterator::parse_booking;
+use crate::metrics::registry::metrics;
+
+static SUBMIT_COMMAND: &str = "SUBMIT ";
+
+/// Atiende pedidos `SUBMIT <reserva>` en `addr`, uno por linea, con la
+/// reserva en el mismo formato que una linea de un archivo JSON Lines.
+/// Cada pedido se responde una vez decidido con `COMMIT <id>`,
+/// `ABORT <id>` o `ERROR <motivo>`.
+///
+/// # Errors
+///
+/// Devuelve error si no se pudo escuchar en `addr`
+pub fn serve(addr: SocketAddr, queue: SubmitQueue) -> io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr)?;
+    info!(addr = %addr, "Recibiendo reservas por SUBMIT");
+    let span = Span::current();
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let queue = queue.clone();
+            let span = span.clone();
+            // Cada cliente espera sus respuestas sin demorar a los demas
+            thread::spawn(move || {
+                let _entered = span.enter();
+                if let Err(err) = attend(stream, &queue) {
+                    warn!(error = %err, "Conexion SUBMIT fallida");
+                }
+            });
+        }
+    }))
+}
+
+fn attend(stream: TcpStream, queue: &SubmitQueue) -> io::Result<()> {
+    let peer = stream.peer_addr()?;
+    let reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = answer(line, queue);
+        debug!(client = %peer, reply = %reply, "Pedido SUBMIT respondido");
+        writeln!(stream, "{}", reply)?;
+        stream.flush()?;
+    }
+    Ok(())
+}
+
+fn answer(line: &str, queue: &SubmitQueue) -> String {
+    let (result, reply) = match submit(line, queue) {
+        Ok((id, true)) => ("commit", format!("COMMIT {}", id)),
+        Ok((id, false)) => ("abort", format!("ABORT {}", id)),
+        Err(reason) => ("error", format!("ERROR {}", reason)),
+    };
+    metrics().submissions.inc(&[("result", result)]);
+    reply
+}
+
+/// Encola la reserva y espera a que el lider la decida
+fn submit(line: &str, queue: &SubmitQueue) -> Result<(u64, bool), String> {
+    let booking = line
+        .strip_prefix(SUBMIT_COMMAND)
+        .ok_or_else(|| "comando desconocido".to_string())?;
+    let transaction = parse_booking(booking)?;
+    let id = transaction.get_id();
+    let commited = queue
+        .push(transaction)?
+        .recv()
+        .map_err(|_| "el lider dejo de serlo antes de decidir la reserva".to_string())?;
+    Ok((id, commited))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ntest::timeout;
+    use std::sync::{Arc, Condvar, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    #[timeout(3000)]
+    fn it_should_reply_each_submission_with_its_outcome() {
+        let queue = SubmitQueue::new(Arc::new((Mutex::new(true), Condvar::new())));
+        queue.open();
+        let addr: SocketAddr = "127.0.0.1:59203".parse().unwrap();
+        serve(addr, queue.clone()).unwrap();
+        // Hace de lider: commitea las reservas con id par
+        let leader_queue = queue.clone();
+        thread::spawn(move || loop {
+            match leader_queue.pop() {
+                Some(submission) => {
+                    let commited = submission.transaction.get_id() % 2 == 0;
+                    submission.reply.send(commited);
+                }
+                None => thread::sleep(Duration::from_millis(10)),
+            }
+        });
+
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut stream = stream;
+        write!(
+            stream,
+            "HOLA\r\n\
+             SUBMIT {{\"id\":1}}\n\
+             SUBMIT {{\"id\":2,\"fees\":{{\"airline\":1,\"hotel\":1,\"bank\":1}}}}\n\
+             SUBMIT {{\"id\":3,\"fees\":{{\"airline\":1,\"hotel\":1,\"bank\":1}}}}\n"
+        )
+        .unwrap();
+
+        let mut replies = Vec::new();
+        for _ in 0..4 {
+            let mut reply = String::new();
+            reader.read_line(&mut reply).unwrap();
+            replies.push(reply.trim_end().to_string());
+        }
+        assert_eq!(replies[0], "ERROR comando desconocido");
+        assert!(replies[1].starts_with("ERROR JSON invalido"));
+        assert_eq!(replies[2..], ["COMMIT 2", "ABORT 3"]);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod span_exporter;
+pub mod trace_context;
+pub mod trace_span;
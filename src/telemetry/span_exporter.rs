@@ -0,0 +1,117 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+use tracing::warn;
+
+use super::trace_span::{SpanKind, TraceSpan};
+
+static EXPORTER: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// Instala el exportador del proceso: cada span terminado se agrega a
+/// `path` como una linea OTLP/JSON, el formato que escribe y lee el
+/// file exporter del OpenTelemetry Collector. Sin exportador los
+/// spans se descartan.
+///
+/// # Errors
+///
+/// Devuelve error si no se pudo abrir el archivo o ya habia un
+/// exportador instalado
+pub fn init(path: &str) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    EXPORTER.set(Mutex::new(file)).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "ya hay un exportador de spans instalado",
+        )
+    })
+}
+
+/// Exporta `span` si hay un exportador instalado
+pub fn export(span: &TraceSpan) {
+    if let Some(exporter) = EXPORTER.get() {
+        let mut line = to_otlp(span).to_string();
+        line.push('\n');
+        let mut file = exporter
+            .lock()
+            .expect("[Span Exporter] Lock del archivo de spans envenenado");
+        if let Err(err) = file.write_all(line.as_bytes()) {
+            warn!(error = %err, "No se pudo exportar el span");
+        }
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos())
+        .to_string()
+}
+
+fn string_attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+/// Arma el `ExportTraceServiceRequest` de OTLP/JSON con un unico span
+#[must_use]
+pub fn to_otlp(span: &TraceSpan) -> Value {
+    let kind = match span.kind {
+        SpanKind::Internal => 1,
+        SpanKind::Server => 2,
+        SpanKind::Client => 3,
+    };
+    let attributes: Vec<Value> = span
+        .attributes
+        .iter()
+        .map(|(key, value)| string_attribute(key, value))
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": { "attributes": [string_attribute("service.name", &span.service)] },
+            "scopeSpans": [{
+                "scope": { "name": "alglobo" },
+                "spans": [{
+                    "traceId": span.context.trace_id_hex(),
+                    "spanId": span.context.span_id_hex(),
+                    "parentSpanId": span.parent_span_id.map(hex::encode).unwrap_or_default(),
+                    "name": span.name,
+                    "kind": kind,
+                    "startTimeUnixNano": unix_nanos(span.start),
+                    "endTimeUnixNano": unix_nanos(span.end.unwrap_or(span.start)),
+                    "attributes": attributes,
+                }]
+            }]
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::trace_context::TraceContext;
+
+    #[test]
+    fn to_otlp_should_link_the_span_with_its_parent() {
+        let parent = TraceContext::new_root();
+        let mut span = TraceSpan::child_of("Airline", "vote", SpanKind::Server, &parent);
+        span.set_attribute("transaction.id", 7);
+
+        let otlp = to_otlp(&span);
+        let exported = &otlp["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+
+        assert_eq!(
+            otlp["resourceSpans"][0]["resource"]["attributes"][0]["value"]["stringValue"],
+            "Airline"
+        );
+        assert_eq!(exported["traceId"], parent.trace_id_hex());
+        assert_eq!(exported["parentSpanId"], parent.span_id_hex());
+        assert_eq!(exported["spanId"], span.context.span_id_hex());
+        assert_eq!(exported["name"], "vote");
+        assert_eq!(exported["kind"], 2);
+        assert_eq!(exported["attributes"][0]["key"], "transaction.id");
+        assert_eq!(exported["attributes"][0]["value"]["stringValue"], "7");
+    }
+}
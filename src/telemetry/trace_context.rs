@@ -0,0 +1,127 @@
+use std::{convert::TryInto, fmt};
+
+pub const TRACE_ID_SIZE: usize = 16;
+pub const SPAN_ID_SIZE: usize = 8;
+/// Bytes que ocupa el contexto de traza dentro de un mensaje
+pub const TRACE_CONTEXT_SIZE: usize = TRACE_ID_SIZE + SPAN_ID_SIZE;
+
+/// Contexto de traza que viaja en los pedidos y respuestas de una
+/// transaccion, con el formato de ids de W3C Trace Context: la traza
+/// identifica a la transaccion y el span a quien envio el mensaje.
+/// Los ids en cero no son validos y representan un mensaje sin traza.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TraceContext {
+    pub trace_id: [u8; TRACE_ID_SIZE],
+    pub span_id: [u8; SPAN_ID_SIZE],
+}
+
+fn random_span_id() -> [u8; SPAN_ID_SIZE] {
+    loop {
+        let span_id: [u8; SPAN_ID_SIZE] = rand::random();
+        if span_id != [0; SPAN_ID_SIZE] {
+            return span_id;
+        }
+    }
+}
+
+impl TraceContext {
+    /// Contexto de una traza nueva
+    #[must_use]
+    pub fn new_root() -> Self {
+        let trace_id = loop {
+            let trace_id: [u8; TRACE_ID_SIZE] = rand::random();
+            if trace_id != [0; TRACE_ID_SIZE] {
+                break trace_id;
+            }
+        };
+        TraceContext {
+            trace_id,
+            span_id: random_span_id(),
+        }
+    }
+
+    /// Contexto de un span nuevo dentro de la misma traza
+    #[must_use]
+    pub fn child(&self) -> Self {
+        TraceContext {
+            trace_id: self.trace_id,
+            span_id: random_span_id(),
+        }
+    }
+
+    /// Serializa el contexto. `None` se serializa en cero.
+    #[must_use]
+    pub fn to_bytes(context_opt: Option<&TraceContext>) -> Vec<u8> {
+        match context_opt {
+            Some(context) => {
+                let mut bytes = context.trace_id.to_vec();
+                bytes.extend_from_slice(&context.span_id);
+                bytes
+            }
+            None => vec![0; TRACE_CONTEXT_SIZE],
+        }
+    }
+
+    /// Lee el contexto del comienzo de `bytes`. Devuelve `None` si no
+    /// alcanzan los bytes o si los ids estan en cero.
+    #[must_use]
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < TRACE_CONTEXT_SIZE {
+            return None;
+        }
+        let trace_id: [u8; TRACE_ID_SIZE] = bytes[..TRACE_ID_SIZE]
+            .try_into()
+            .expect("[Trace Context] El id de traza deberia ocupar 16 bytes");
+        let span_id: [u8; SPAN_ID_SIZE] = bytes[TRACE_ID_SIZE..TRACE_CONTEXT_SIZE]
+            .try_into()
+            .expect("[Trace Context] El id de span deberia ocupar 8 bytes");
+        if trace_id == [0; TRACE_ID_SIZE] || span_id == [0; SPAN_ID_SIZE] {
+            return None;
+        }
+        Some(TraceContext { trace_id, span_id })
+    }
+
+    #[must_use]
+    pub fn trace_id_hex(&self) -> String {
+        hex::encode(self.trace_id)
+    }
+
+    #[must_use]
+    pub fn span_id_hex(&self) -> String {
+        hex::encode(self.span_id)
+    }
+}
+
+/// Formato `traceparent` de W3C, para los logs
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "00-{}-{}-01", self.trace_id_hex(), self.span_id_hex())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_round_trip_through_bytes() {
+        let context = TraceContext::new_root();
+        let child = context.child();
+
+        let bytes = TraceContext::to_bytes(Some(&child));
+
+        assert_eq!(bytes.len(), TRACE_CONTEXT_SIZE);
+        assert_eq!(TraceContext::from_bytes(&bytes), Some(child));
+        assert_eq!(child.trace_id, context.trace_id);
+        assert_ne!(child.span_id, context.span_id);
+    }
+
+    #[test]
+    fn zeroed_bytes_should_be_a_message_without_trace() {
+        assert_eq!(
+            TraceContext::from_bytes(&TraceContext::to_bytes(None)),
+            None
+        );
+        assert_eq!(TraceContext::from_bytes(&[1; 4]), None);
+    }
+}
@@ -0,0 +1,74 @@
+use std::time::SystemTime;
+
+use super::{span_exporter, trace_context::TraceContext};
+
+/// Tipo de span segun OpenTelemetry
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SpanKind {
+    Internal,
+    Server,
+    Client,
+}
+
+/// Tramo de una traza: una fase de la transaccion en el coordinador
+/// o la atencion de un pedido en un servicio. Al terminarlo se
+/// exporta con `span_exporter` si hay un exportador instalado.
+#[derive(Debug, Clone)]
+pub struct TraceSpan {
+    pub service: String,
+    pub name: &'static str,
+    pub kind: SpanKind,
+    pub context: TraceContext,
+    pub parent_span_id: Option<[u8; 8]>,
+    pub start: SystemTime,
+    pub end: Option<SystemTime>,
+    pub attributes: Vec<(&'static str, String)>,
+}
+
+impl TraceSpan {
+    /// Inicia el span raiz de una traza nueva
+    #[must_use]
+    pub fn root(service: &str, name: &'static str, kind: SpanKind) -> Self {
+        TraceSpan::start(service, name, kind, TraceContext::new_root(), None)
+    }
+
+    /// Inicia un span hijo del span de `parent`, en su misma traza
+    #[must_use]
+    pub fn child_of(
+        service: &str,
+        name: &'static str,
+        kind: SpanKind,
+        parent: &TraceContext,
+    ) -> Self {
+        TraceSpan::start(service, name, kind, parent.child(), Some(parent.span_id))
+    }
+
+    fn start(
+        service: &str,
+        name: &'static str,
+        kind: SpanKind,
+        context: TraceContext,
+        parent_span_id: Option<[u8; 8]>,
+    ) -> Self {
+        TraceSpan {
+            service: service.to_string(),
+            name,
+            kind,
+            context,
+            parent_span_id,
+            start: SystemTime::now(),
+            end: None,
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn set_attribute(&mut self, key: &'static str, value: impl ToString) {
+        self.attributes.push((key, value.to_string()));
+    }
+
+    /// Termina el span y lo exporta
+    pub fn end(mut self) {
+        self.end = Some(SystemTime::now());
+        span_exporter::export(&self);
+    }
+}
@@ -1,7 +1,11 @@
+pub mod transaction_checkpoint;
 pub mod transaction_code;
+pub mod transaction_handoff;
 pub mod transaction_info;
 pub mod transaction_log;
 pub mod transaction_request;
 pub mod transaction_response;
 pub mod transaction_retry;
+pub mod transaction_retry_ack;
+pub mod transaction_retry_queue;
 pub mod types;
@@ -0,0 +1,84 @@
+use std::{convert::TryInto, mem::size_of};
+
+use super::types::CHECKPOINT_BYTE;
+
+const COMMITED_BYTE: u8 = b'C';
+const ABORTED_BYTE: u8 = b'A';
+
+/// Mensaje con el que el lider replica su progreso luego de decidir
+/// cada transaccion: el id, si se commiteo y el offset del archivo de
+/// transacciones desde el que se retoma la lectura. Con estos datos un
+/// nuevo lider elegido tras una caida no depende del orden de los ids.
+pub struct TransactionCheckpoint;
+
+impl TransactionCheckpoint {
+    #[must_use]
+    pub fn size() -> usize {
+        TransactionCheckpoint::build(0, true, 0).len()
+    }
+
+    #[must_use]
+    pub fn build(id: u64, commited: bool, offset: u64) -> Vec<u8> {
+        let mut message = vec![CHECKPOINT_BYTE];
+        message.append(&mut id.to_be_bytes().to_vec());
+        message.push(if commited {
+            COMMITED_BYTE
+        } else {
+            ABORTED_BYTE
+        });
+        message.append(&mut offset.to_be_bytes().to_vec());
+        message
+    }
+
+    /// Devuelve el id, si se commiteo y el offset, o `None` si el
+    /// mensaje esta incompleto o tiene un codigo de decision desconocido
+    #[must_use]
+    pub fn parse(message: &[u8]) -> Option<(u64, bool, u64)> {
+        let id = TransactionCheckpoint::parse_u64(message, 1)?;
+        let commited = match *message.get(1 + size_of::<u64>())? {
+            COMMITED_BYTE => true,
+            ABORTED_BYTE => false,
+            _ => return None,
+        };
+        let offset = TransactionCheckpoint::parse_u64(message, 2 + size_of::<u64>())?;
+        Some((id, commited, offset))
+    }
+
+    fn parse_u64(message: &[u8], begin: usize) -> Option<u64> {
+        let bytes: [u8; size_of::<u64>()] = message
+            .get(begin..begin + size_of::<u64>())?
+            .try_into()
+            .ok()?;
+        Some(u64::from_be_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_should_return_id_decision_and_offset_of_build() {
+        let commit_msg = TransactionCheckpoint::build(7, true, 1024);
+        let abort_msg = TransactionCheckpoint::build(3, false, 0);
+
+        assert_eq!(
+            TransactionCheckpoint::parse(&commit_msg),
+            Some((7, true, 1024))
+        );
+        assert_eq!(
+            TransactionCheckpoint::parse(&abort_msg),
+            Some((3, false, 0))
+        );
+    }
+
+    #[test]
+    fn parse_should_return_none_for_short_messages_and_unknown_decisions() {
+        let message = TransactionCheckpoint::build(7, true, 1024);
+        let mut unknown_msg = message.clone();
+        unknown_msg[1 + size_of::<u64>()] = b'X';
+
+        assert_eq!(TransactionCheckpoint::parse(&message[..5]), None);
+        assert_eq!(TransactionCheckpoint::parse(&unknown_msg), None);
+    }
+}
@@ -0,0 +1,164 @@
+use std::{convert::TryInto, mem::size_of};
+
+use crate::alglobo::decisions::{Decisions, DECISIONS_CAPACITY};
+
+use super::types::HANDOFF_BYTE;
+
+const COMMITED_BYTE: u8 = b'C';
+const ABORTED_BYTE: u8 = b'A';
+const DECISION_SIZE: usize = size_of::<u64>() + 1;
+const HEADER_SIZE: usize = 1 + 3 * size_of::<u64>();
+/// Decisiones de las filas del archivo mas las de reintentos y SUBMIT
+const MAX_DECISIONS: usize = 2 * DECISIONS_CAPACITY;
+
+/// Mensaje con el que un lider que renuncia traspasa a su sucesor la
+/// posicion de lectura del archivo de transacciones, las decisiones
+/// tomadas desde esa posicion, las de los reintentos y reservas SUBMIT
+/// y la posicion de la proxima reserva SUBMIT. Se envia en un unico mensaje, que el
+/// socket fragmenta si es mas largo que un datagrama, para que el
+/// sucesor no retome con un traspaso incompleto.
+pub struct TransactionHandoff;
+
+impl TransactionHandoff {
+    /// Largo de un traspaso sin decisiones
+    #[must_use]
+    pub fn size() -> usize {
+        HEADER_SIZE
+    }
+
+    /// Largo de un traspaso con todas las decisiones que se conservan
+    #[must_use]
+    pub fn max_size() -> usize {
+        HEADER_SIZE + MAX_DECISIONS * DECISION_SIZE
+    }
+
+    #[must_use]
+    pub fn build(offset: u64, decisions: &Decisions) -> Vec<u8> {
+        let mut message = vec![HANDOFF_BYTE];
+        message.append(&mut offset.to_be_bytes().to_vec());
+        message.append(&mut decisions.next_submit().to_be_bytes().to_vec());
+        message.append(&mut (decisions.len() as u64).to_be_bytes().to_vec());
+        for (id, commited) in decisions.iter() {
+            message.append(&mut id.to_be_bytes().to_vec());
+            message.push(if commited {
+                COMMITED_BYTE
+            } else {
+                ABORTED_BYTE
+            });
+        }
+        message
+    }
+
+    /// Devuelve el offset y las decisiones, o `None` si el mensaje esta
+    /// incompleto, tiene demasiadas decisiones o un codigo de decision
+    /// desconocido
+    #[must_use]
+    pub fn parse(message: &[u8]) -> Option<(u64, Decisions)> {
+        let offset = TransactionHandoff::parse_u64(message, 1)?;
+        let next_submit = TransactionHandoff::parse_u64(message, 1 + size_of::<u64>())?;
+        let n_decisions = TransactionHandoff::parse_u64(message, 1 + 2 * size_of::<u64>())?;
+        if n_decisions > MAX_DECISIONS as u64 {
+            return None;
+        }
+        let mut decisions = Decisions::new();
+        decisions.skip_submits(next_submit);
+        let mut begin = HEADER_SIZE;
+        for _ in 0..n_decisions {
+            let id = TransactionHandoff::parse_u64(message, begin)?;
+            let commited = match *message.get(begin + size_of::<u64>())? {
+                COMMITED_BYTE => true,
+                ABORTED_BYTE => false,
+                _ => return None,
+            };
+            decisions.insert(id, commited, offset);
+            begin += DECISION_SIZE;
+        }
+        Some((offset, decisions))
+    }
+
+    fn parse_u64(message: &[u8], begin: usize) -> Option<u64> {
+        let bytes: [u8; size_of::<u64>()] = message
+            .get(begin..begin + size_of::<u64>())?
+            .try_into()
+            .ok()?;
+        Some(u64::from_be_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alglobo::retry_queue::RETRY_ID_BASE;
+    use crate::sockets::fragment::MAX_MESSAGE_SIZE;
+    use crate::submit::submit_queue::SUBMIT_ID_BASE;
+
+    fn decisions() -> Decisions {
+        let mut decisions = Decisions::new();
+        decisions.insert(7, true, 1024);
+        decisions.insert(8, false, 1024);
+        decisions
+    }
+
+    #[test]
+    fn parse_should_keep_the_retry_and_submit_decisions_and_the_next_submit() {
+        let mut decisions = Decisions::new();
+        decisions.insert(RETRY_ID_BASE, true, 512);
+        decisions.insert(SUBMIT_ID_BASE + 3, false, 512);
+        decisions.insert(7, true, 1024);
+        // Aunque se haya descartado la decision de la ultima reserva
+        decisions.skip_submits(9);
+
+        let message = TransactionHandoff::build(1024, &decisions);
+
+        let (offset, parsed) = TransactionHandoff::parse(&message).unwrap();
+        assert_eq!((offset, parsed.next_submit()), (1024, 9));
+        assert_eq!(parsed, decisions);
+    }
+
+    #[test]
+    fn max_size_should_fit_every_decision_that_is_kept() {
+        let mut decisions = Decisions::new();
+        for id in 0..DECISIONS_CAPACITY as u64 {
+            decisions.insert(id, true, 0);
+            decisions.insert(SUBMIT_ID_BASE + id, false, 0);
+        }
+
+        let message = TransactionHandoff::build(0, &decisions);
+
+        assert_eq!(message.len(), TransactionHandoff::max_size());
+        assert!(message.len() <= MAX_MESSAGE_SIZE);
+        assert_eq!(TransactionHandoff::parse(&message), Some((0, decisions)));
+    }
+
+    #[test]
+    fn parse_should_return_offset_and_decisions_of_build() {
+        let message = TransactionHandoff::build(1024, &decisions());
+
+        assert_eq!(
+            message.len(),
+            TransactionHandoff::size() + 2 * DECISION_SIZE
+        );
+        assert_eq!(
+            TransactionHandoff::parse(&message),
+            Some((1024, decisions()))
+        );
+    }
+
+    #[test]
+    fn parse_should_return_none_for_short_messages_and_unknown_decisions() {
+        let message = TransactionHandoff::build(1024, &decisions());
+        let mut unknown_msg = message.clone();
+        unknown_msg[HEADER_SIZE + size_of::<u64>()] = b'X';
+        let mut too_many_msg = TransactionHandoff::build(1024, &Decisions::new());
+        too_many_msg[1 + 2 * size_of::<u64>()..HEADER_SIZE]
+            .copy_from_slice(&(MAX_DECISIONS as u64 + 1).to_be_bytes());
+
+        assert_eq!(TransactionHandoff::parse(&message[..12]), None);
+        assert_eq!(
+            TransactionHandoff::parse(&message[..message.len() - 1]),
+            None
+        );
+        assert_eq!(TransactionHandoff::parse(&unknown_msg), None);
+        assert_eq!(TransactionHandoff::parse(&too_many_msg), None);
+    }
+}
@@ -1,15 +1,13 @@
-use std::cmp::max;
-
 pub struct TransactionInfo;
 
 impl TransactionInfo {
     #[must_use]
     pub const fn size() -> usize {
-        36
+        42
     }
 
     pub fn add_padding(message: &mut Vec<u8>) {
-        let padding_len = max(0, TransactionInfo::size() - message.len());
+        let padding_len = TransactionInfo::size().saturating_sub(message.len());
         for _ in 0..padding_len {
             message.push(0);
         }
@@ -19,8 +17,9 @@ impl TransactionInfo {
 #[cfg(test)]
 mod tests {
     use crate::transaction_messages::{
+        transaction_checkpoint::TransactionCheckpoint, transaction_handoff::TransactionHandoff,
         transaction_log::TransactionLog, transaction_response::TransactionResponse,
-        transaction_retry::TransactionRetry,
+        transaction_retry::TransactionRetry, transaction_retry_queue::TransactionRetryQueue,
     };
 
     use super::*;
@@ -29,7 +28,13 @@ mod tests {
     fn size_should_return_the_max_size_of_all_transaction_info_messages() {
         let size = max(
             max(TransactionLog::size(), TransactionResponse::size()),
-            TransactionRetry::size(),
+            max(
+                TransactionRetry::size(),
+                max(
+                    max(TransactionHandoff::size(), TransactionCheckpoint::size()),
+                    TransactionRetryQueue::size(),
+                ),
+            ),
         );
         assert_eq!(TransactionInfo::size(), size);
     }
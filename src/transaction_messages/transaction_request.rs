@@ -1,5 +1,12 @@
+use std::{convert::TryInto, mem::size_of};
+
+use crate::telemetry::trace_context::TraceContext;
+
 use super::transaction_code::TransactionCode;
 
+// El contexto de traza va despues del codigo, el id y el monto
+const TRACE_OFFSET: usize = 1 + size_of::<u64>() + size_of::<f64>();
+
 pub struct TransactionRequest;
 
 impl TransactionRequest {
@@ -8,15 +15,42 @@ impl TransactionRequest {
         TransactionRequest::build(TransactionCode::Prepare, 0, 0.0).len()
     }
 
+    /// Arma un pedido sin contexto de traza
     #[must_use]
     pub fn build(code: TransactionCode, id: u64, fee: f64) -> Vec<u8> {
+        TransactionRequest::build_with_trace(code, id, fee, None)
+    }
+
+    #[must_use]
+    pub fn build_with_trace(
+        code: TransactionCode,
+        id: u64,
+        fee: f64,
+        trace_opt: Option<&TraceContext>,
+    ) -> Vec<u8> {
         let code = TransactionRequest::map_transaction_code(code);
         let mut message = vec![code];
         message.append(&mut id.to_be_bytes().to_vec());
         message.append(&mut fee.to_be_bytes().to_vec());
+        message.append(&mut TraceContext::to_bytes(trace_opt));
         message
     }
 
+    /// Id de la transaccion de un pedido armado con `build`
+    #[must_use]
+    pub fn parse_id(message: &[u8]) -> u64 {
+        let id_bytes: [u8; size_of::<u64>()] = message[1..=size_of::<u64>()]
+            .try_into()
+            .expect("[Transaction Request] Los ids deberian ocupar 8 bytes");
+        u64::from_be_bytes(id_bytes)
+    }
+
+    /// Contexto de traza del pedido, si lo tiene
+    #[must_use]
+    pub fn parse_trace(message: &[u8]) -> Option<TraceContext> {
+        TraceContext::from_bytes(message.get(TRACE_OFFSET..)?)
+    }
+
     pub(crate) fn map_transaction_code(code: TransactionCode) -> u8 {
         let err_msg = format!("[Transaction Response] No hay solicitud para {}", code);
         match code {
@@ -26,11 +60,31 @@ impl TransactionRequest {
             TransactionCode::Accept => panic!("{}", err_msg),
         }
     }
+
+    /// Indica si `message` tiene el largo y el codigo de un pedido.
+    /// El contexto de traza es opcional.
+    #[must_use]
+    pub fn is_valid(message: &[u8]) -> bool {
+        message.len() >= TRACE_OFFSET && matches!(message[0], b'P' | b'A' | b'C')
+    }
+
+    /// Codigo de un pedido armado con `build`. Como en los servicios,
+    /// todo lo que no es PREPARAR ni ABORTAR se toma como COMMIT.
+    #[must_use]
+    pub fn parse_code(message: &[u8]) -> TransactionCode {
+        match message.first() {
+            Some(b'P') => TransactionCode::Prepare,
+            Some(b'A') => TransactionCode::Abort,
+            _ => TransactionCode::Commit,
+        }
+    }
 }
 
 mod tests {
     #[allow(unused_imports)]
     use super::*;
+    #[allow(unused_imports)]
+    use crate::telemetry::trace_context::TRACE_CONTEXT_SIZE;
 
     #[test]
     fn build_should_return_message_p_with_id_and_fee_when_code_is_prepare() {
@@ -41,6 +95,7 @@ mod tests {
         let mut expected = vec![b'P'];
         expected.append(&mut id.to_be_bytes().to_vec());
         expected.append(&mut fee.to_be_bytes().to_vec());
+        expected.append(&mut vec![0; TRACE_CONTEXT_SIZE]);
 
         assert_eq!(message, expected);
     }
@@ -54,7 +109,33 @@ mod tests {
         let mut expected = vec![b'A'];
         expected.append(&mut id.to_be_bytes().to_vec());
         expected.append(&mut fee.to_be_bytes().to_vec());
+        expected.append(&mut vec![0; TRACE_CONTEXT_SIZE]);
 
         assert_eq!(message, expected);
     }
+
+    #[test]
+    fn parse_code_should_return_the_code_of_a_built_request() {
+        for code in [
+            TransactionCode::Prepare,
+            TransactionCode::Abort,
+            TransactionCode::Commit,
+        ] {
+            let message = TransactionRequest::build(code, 7, 100.0);
+            assert_eq!(TransactionRequest::parse_code(&message), code);
+        }
+    }
+
+    #[test]
+    fn parse_trace_should_return_the_context_of_a_traced_request() {
+        let trace = TraceContext::new_root();
+        let message =
+            TransactionRequest::build_with_trace(TransactionCode::Prepare, 7, 100.0, Some(&trace));
+        let untraced = TransactionRequest::build(TransactionCode::Prepare, 7, 100.0);
+
+        assert_eq!(message.len(), TransactionRequest::size());
+        assert_eq!(TransactionRequest::parse_id(&message), 7);
+        assert_eq!(TransactionRequest::parse_trace(&message), Some(trace));
+        assert_eq!(TransactionRequest::parse_trace(&untraced), None);
+    }
 }
@@ -1,10 +1,14 @@
 use std::{convert::TryInto, mem::size_of};
 
+use crate::telemetry::trace_context::TraceContext;
+
 use super::{transaction_code::TransactionCode, types::RESPONSE_BYTE};
 
 const ACCEPT_BYTE: u8 = b'o';
 const ABORT_BYTE: u8 = b'A';
 const COMMIT_BYTE: u8 = b'C';
+// El contexto de traza va despues del tipo, el codigo y el id
+const TRACE_OFFSET: usize = 2 + size_of::<u64>();
 
 pub struct TransactionResponse;
 
@@ -14,29 +18,53 @@ impl TransactionResponse {
         TransactionResponse::build(TransactionCode::Accept, 0).len()
     }
 
+    /// Arma una respuesta sin contexto de traza
     #[must_use]
     pub fn build(code: TransactionCode, id: u64) -> Vec<u8> {
+        TransactionResponse::build_with_trace(code, id, None)
+    }
+
+    #[must_use]
+    pub fn build_with_trace(
+        code: TransactionCode,
+        id: u64,
+        trace_opt: Option<&TraceContext>,
+    ) -> Vec<u8> {
         let mut message = vec![RESPONSE_BYTE];
         message.push(TransactionResponse::map_transaction_code(code));
         message.append(&mut id.to_be_bytes().to_vec());
+        message.append(&mut TraceContext::to_bytes(trace_opt));
         message
     }
 
+    /// Contexto de traza de la respuesta, si lo tiene
+    #[must_use]
+    pub fn parse_trace(message: &[u8]) -> Option<TraceContext> {
+        TraceContext::from_bytes(message.get(TRACE_OFFSET..)?)
+    }
+
     /// # Panics
     ///
     /// Esta funcion paniquea si:
     /// - se recibio un codigo de transaccion desconocido
     #[must_use]
     pub fn transaction_code(code: u8) -> TransactionCode {
-        let err_msg = format!(
-            "[Transaction Response] Codigo de transaccion desconocido: {}",
-            code
-        );
+        TransactionResponse::try_transaction_code(code).unwrap_or_else(|| {
+            panic!(
+                "[Transaction Response] Codigo de transaccion desconocido: {}",
+                code
+            )
+        })
+    }
+
+    /// Codigo de transaccion, o `None` si el byte es desconocido
+    #[must_use]
+    pub fn try_transaction_code(code: u8) -> Option<TransactionCode> {
         match code {
-            ACCEPT_BYTE => TransactionCode::Accept,
-            ABORT_BYTE => TransactionCode::Abort,
-            COMMIT_BYTE => TransactionCode::Commit,
-            _ => panic!("{}", err_msg),
+            ACCEPT_BYTE => Some(TransactionCode::Accept),
+            ABORT_BYTE => Some(TransactionCode::Abort),
+            COMMIT_BYTE => Some(TransactionCode::Commit),
+            _ => None,
         }
     }
 
@@ -53,17 +81,28 @@ impl TransactionResponse {
     #[must_use]
     pub fn parse(message: &[u8]) -> (TransactionCode, u64) {
         let code = TransactionResponse::transaction_code(message[1]);
+        (code, TransactionResponse::parse_id(message))
+    }
+
+    /// Igual que `parse`, pero sin paniquear si el codigo es desconocido
+    #[must_use]
+    pub fn try_parse(message: &[u8]) -> (Option<TransactionCode>, u64) {
+        let code = TransactionResponse::try_transaction_code(message[1]);
+        (code, TransactionResponse::parse_id(message))
+    }
+
+    fn parse_id(message: &[u8]) -> u64 {
         let id_bytes: [u8; size_of::<u64>()] = message[2..2 + size_of::<u64>()]
             .try_into()
             .expect("[Transaction Response] Los ids deberian ocupar 8 bytes");
-        let id = u64::from_be_bytes(id_bytes);
-        (code, id)
+        u64::from_be_bytes(id_bytes)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::transaction_messages::transaction_info::TransactionInfo;
 
     #[test]
     fn accept_should_return_ok_with_id() {
@@ -71,6 +110,7 @@ mod tests {
         let message = TransactionResponse::build(TransactionCode::Accept, id);
         let mut expected = vec![RESPONSE_BYTE, b'o'];
         expected.append(&mut id.to_be_bytes().to_vec());
+        expected.append(&mut TraceContext::to_bytes(None));
 
         assert_eq!(message, expected);
     }
@@ -82,6 +122,28 @@ mod tests {
         assert_eq!(code, TransactionCode::Accept);
     }
 
+    #[test]
+    fn parse_trace_should_return_the_context_of_a_padded_response() {
+        let trace = TraceContext::new_root();
+        let mut message =
+            TransactionResponse::build_with_trace(TransactionCode::Commit, 7, Some(&trace));
+        TransactionInfo::add_padding(&mut message);
+
+        assert_eq!(
+            TransactionResponse::parse(&message),
+            (TransactionCode::Commit, 7)
+        );
+        assert_eq!(TransactionResponse::parse_trace(&message), Some(trace));
+    }
+
+    #[test]
+    fn try_parse_should_keep_the_id_of_a_response_with_unknown_code() {
+        let mut message = TransactionResponse::build(TransactionCode::Abort, 9);
+        message[1] = b'?';
+
+        assert_eq!(TransactionResponse::try_parse(&message), (None, 9));
+    }
+
     #[test]
     fn size_should_be_the_len_of_result_of_build() {
         let id = 0;
@@ -0,0 +1,118 @@
+use std::{convert::TryInto, fmt, mem::size_of};
+
+use super::types::RETRY_ACK_BYTE;
+
+const GRANTED_BYTE: u8 = b'G';
+const QUEUE_FULL_BYTE: u8 = b'F';
+const NOT_LEADER_BYTE: u8 = b'N';
+const ALREADY_COMMITED_BYTE: u8 = b'D';
+const COMMITED_BYTE: u8 = b'C';
+const ABORTED_BYTE: u8 = b'A';
+
+/// Motivo por el que un nodo deniega un reintento
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DenyReason {
+    QueueFull,
+    NotLeader,
+    AlreadyCommited,
+}
+
+impl fmt::Display for DenyReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DenyReason::QueueFull => write!(f, "cola de reintentos llena"),
+            DenyReason::NotLeader => write!(f, "el nodo no es el lider"),
+            DenyReason::AlreadyCommited => write!(f, "la transaccion ya fue commiteada"),
+        }
+    }
+}
+
+/// Respuesta a un reintento: primero si se concedio, es decir, si se
+/// encolo y, una vez decidida la transaccion, su resultado
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RetryReply {
+    Granted,
+    Denied(DenyReason),
+    Commited,
+    Aborted,
+}
+
+impl fmt::Display for RetryReply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RetryReply::Granted => write!(f, "CONCEDIDO"),
+            RetryReply::Denied(reason) => write!(f, "DENEGADO ({})", reason),
+            RetryReply::Commited => write!(f, "COMMITEADO"),
+            RetryReply::Aborted => write!(f, "ABORTADO"),
+        }
+    }
+}
+
+/// Mensaje con el que un nodo le responde al cliente de un reintento
+pub struct TransactionRetryAck;
+
+impl TransactionRetryAck {
+    #[must_use]
+    pub fn size() -> usize {
+        TransactionRetryAck::build(0, RetryReply::Granted).len()
+    }
+
+    #[must_use]
+    pub fn build(id: u64, reply: RetryReply) -> Vec<u8> {
+        let mut message = vec![RETRY_ACK_BYTE];
+        message.append(&mut id.to_be_bytes().to_vec());
+        message.push(match reply {
+            RetryReply::Granted => GRANTED_BYTE,
+            RetryReply::Denied(DenyReason::QueueFull) => QUEUE_FULL_BYTE,
+            RetryReply::Denied(DenyReason::NotLeader) => NOT_LEADER_BYTE,
+            RetryReply::Denied(DenyReason::AlreadyCommited) => ALREADY_COMMITED_BYTE,
+            RetryReply::Commited => COMMITED_BYTE,
+            RetryReply::Aborted => ABORTED_BYTE,
+        });
+        message
+    }
+
+    /// Devuelve el id y la respuesta, o `None` si el mensaje no es
+    /// una respuesta a un reintento
+    #[must_use]
+    pub fn parse(message: &[u8]) -> Option<(u64, RetryReply)> {
+        if message.len() < TransactionRetryAck::size() || message[0] != RETRY_ACK_BYTE {
+            return None;
+        }
+        let id_bytes: [u8; size_of::<u64>()] = message[1..1 + size_of::<u64>()]
+            .try_into()
+            .expect("[Transaction Retry Ack] Los ids deberian ocupar 8 bytes");
+        let reply = match message[1 + size_of::<u64>()] {
+            GRANTED_BYTE => RetryReply::Granted,
+            QUEUE_FULL_BYTE => RetryReply::Denied(DenyReason::QueueFull),
+            NOT_LEADER_BYTE => RetryReply::Denied(DenyReason::NotLeader),
+            ALREADY_COMMITED_BYTE => RetryReply::Denied(DenyReason::AlreadyCommited),
+            COMMITED_BYTE => RetryReply::Commited,
+            ABORTED_BYTE => RetryReply::Aborted,
+            _ => return None,
+        };
+        Some((u64::from_be_bytes(id_bytes), reply))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_should_return_id_and_reply_of_build() {
+        let replies = [
+            RetryReply::Granted,
+            RetryReply::Denied(DenyReason::QueueFull),
+            RetryReply::Denied(DenyReason::NotLeader),
+            RetryReply::Denied(DenyReason::AlreadyCommited),
+            RetryReply::Commited,
+            RetryReply::Aborted,
+        ];
+        for reply in replies.iter() {
+            let message = TransactionRetryAck::build(9, *reply);
+            assert_eq!(TransactionRetryAck::parse(&message), Some((9, *reply)));
+        }
+        assert_eq!(TransactionRetryAck::parse(&[RETRY_ACK_BYTE]), None);
+    }
+}
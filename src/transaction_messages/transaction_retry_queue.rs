@@ -0,0 +1,148 @@
+use std::{cmp::max, collections::HashMap, convert::TryInto, mem::size_of};
+
+use crate::{alglobo::retry_queue::QueuedRetry, services::service_name::ServiceName};
+
+use super::types::RETRY_QUEUE_BYTE;
+
+const ENQUEUE_BYTE: u8 = b'E';
+const DONE_BYTE: u8 = b'D';
+
+/// Mensajes con los que el lider replica su cola de reintentos: uno
+/// al encolar cada reintento y otro cuando termina de ejecutarlo. Asi
+/// un nuevo lider ejecuta los reintentos pendientes del anterior.
+pub struct TransactionRetryQueue;
+
+impl TransactionRetryQueue {
+    #[must_use]
+    pub fn size() -> usize {
+        let retry = QueuedRetry {
+            seq: 0,
+            id: 0,
+            fees: HashMap::new(),
+            client_opt: None,
+        };
+        max(
+            TransactionRetryQueue::build_enqueue(&retry).len(),
+            TransactionRetryQueue::build_done(0).len(),
+        )
+    }
+
+    /// Los montos faltantes se envian en cero
+    #[must_use]
+    pub fn build_enqueue(retry: &QueuedRetry) -> Vec<u8> {
+        let mut message = vec![RETRY_QUEUE_BYTE, ENQUEUE_BYTE];
+        message.append(&mut retry.seq.to_be_bytes().to_vec());
+        message.append(&mut retry.id.to_be_bytes().to_vec());
+        for name in TransactionRetryQueue::services_names() {
+            let fee = retry.fees.get(&name).copied().unwrap_or(0.0);
+            message.append(&mut fee.to_be_bytes().to_vec());
+        }
+        message
+    }
+
+    #[must_use]
+    pub fn build_done(seq: u64) -> Vec<u8> {
+        let mut message = vec![RETRY_QUEUE_BYTE, DONE_BYTE];
+        message.append(&mut seq.to_be_bytes().to_vec());
+        message
+    }
+
+    #[must_use]
+    pub fn is_done(message: &[u8]) -> bool {
+        message.get(1) == Some(&DONE_BYTE)
+    }
+
+    /// Devuelve el reintento encolado, sin el cliente que lo pidio, o
+    /// `None` si el mensaje esta incompleto
+    #[must_use]
+    pub fn parse_enqueue(message: &[u8]) -> Option<QueuedRetry> {
+        let seq = TransactionRetryQueue::parse_u64(message, 2)?;
+        let id = TransactionRetryQueue::parse_u64(message, 2 + size_of::<u64>())?;
+        let mut begin = 2 + 2 * size_of::<u64>();
+        let mut fees = HashMap::new();
+        for name in TransactionRetryQueue::services_names() {
+            let fee = f64::from_bits(TransactionRetryQueue::parse_u64(message, begin)?);
+            fees.insert(name, fee);
+            begin += size_of::<f64>();
+        }
+        Some(QueuedRetry {
+            seq,
+            id,
+            fees,
+            client_opt: None,
+        })
+    }
+
+    /// Devuelve `None` si el mensaje esta incompleto
+    #[must_use]
+    pub fn parse_done(message: &[u8]) -> Option<u64> {
+        TransactionRetryQueue::parse_u64(message, 2)
+    }
+
+    fn services_names() -> [String; 3] {
+        [
+            ServiceName::Airline.string_name(),
+            ServiceName::Hotel.string_name(),
+            ServiceName::Bank.string_name(),
+        ]
+    }
+
+    fn parse_u64(message: &[u8], begin: usize) -> Option<u64> {
+        let bytes: [u8; size_of::<u64>()] = message
+            .get(begin..begin + size_of::<u64>())?
+            .try_into()
+            .ok()?;
+        Some(u64::from_be_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_should_return_the_retry_and_seq_of_build() {
+        let retry = QueuedRetry {
+            seq: 4,
+            id: 28,
+            fees: HashMap::from([
+                (ServiceName::Airline.string_name(), 100.0),
+                (ServiceName::Hotel.string_name(), 200.0),
+                (ServiceName::Bank.string_name(), 300.0),
+            ]),
+            client_opt: Some("127.0.0.1:49160".parse().unwrap()),
+        };
+        let enqueue_msg = TransactionRetryQueue::build_enqueue(&retry);
+        let done_msg = TransactionRetryQueue::build_done(4);
+
+        assert!(!TransactionRetryQueue::is_done(&enqueue_msg));
+        assert_eq!(
+            TransactionRetryQueue::parse_enqueue(&enqueue_msg),
+            Some(QueuedRetry {
+                client_opt: None,
+                ..retry
+            })
+        );
+        assert!(TransactionRetryQueue::is_done(&done_msg));
+        assert_eq!(TransactionRetryQueue::parse_done(&done_msg), Some(4));
+    }
+
+    #[test]
+    fn parse_should_return_none_for_short_messages() {
+        let retry = QueuedRetry {
+            seq: 4,
+            id: 28,
+            fees: HashMap::new(),
+            client_opt: None,
+        };
+        let enqueue_msg = TransactionRetryQueue::build_enqueue(&retry);
+        let done_msg = TransactionRetryQueue::build_done(4);
+
+        assert_eq!(
+            TransactionRetryQueue::parse_enqueue(&enqueue_msg[..enqueue_msg.len() - 1]),
+            None
+        );
+        assert_eq!(TransactionRetryQueue::parse_done(&done_msg[..6]), None);
+        assert!(!TransactionRetryQueue::is_done(&done_msg[..1]));
+    }
+}
@@ -1,3 +1,7 @@
+pub const HANDOFF_BYTE: u8 = b'H';
 pub const LOG_BYTE: u8 = b'L';
 pub const RESPONSE_BYTE: u8 = b'R';
 pub const RETRY_BYTE: u8 = b'Y';
+pub const CHECKPOINT_BYTE: u8 = b'K';
+pub const RETRY_ACK_BYTE: u8 = b'Q';
+pub const RETRY_QUEUE_BYTE: u8 = b'U';